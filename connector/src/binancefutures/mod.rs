@@ -261,6 +261,11 @@ impl Connector for BinanceFutures {
                             order.qty,
                             order.order_type,
                             order.time_in_force,
+                            order.trigger().as_ref(),
+                            order.expire_timestamp,
+                            order.display_qty,
+                            order.reduce_only,
+                            order.position_side,
                         )
                         .await;
                     match result {
@@ -1,4 +1,4 @@
-use hftbacktest::types::{OrdType, Side, Status, TimeInForce};
+use hftbacktest::types::{OrdType, PositionSide, Side, Status, TimeInForce, TriggerSource};
 use serde::{
     de::{Error, Unexpected},
     Deserialize,
@@ -22,6 +22,19 @@ where
     }
 }
 
+fn from_str_to_position_side<'de, D>(deserializer: D) -> Result<PositionSide, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: &str = Deserialize::deserialize(deserializer)?;
+    match s {
+        "BOTH" => Ok(PositionSide::Both),
+        "LONG" => Ok(PositionSide::Long),
+        "SHORT" => Ok(PositionSide::Short),
+        s => Err(Error::invalid_value(Unexpected::Other(s), &"BOTH,LONG,SHORT")),
+    }
+}
+
 fn from_str_to_status<'de, D>(deserializer: D) -> Result<Status, D::Error>
 where
     D: Deserializer<'de>,
@@ -50,12 +63,27 @@ where
     match s {
         "LIMIT" => Ok(OrdType::Limit),
         "MARKET" => Ok(OrdType::Market),
-        // "STOP" => Ok(OrdType::StopLimit),
-        // "TAKE_PROFIT" => Ok(OrdType::TakeProfitLimit),
-        // "STOP_MARKET" => Ok(OrdType::StopMarket),
-        // "TAKE_PROFIT_MARKET" => Ok(OrdType::TakeProfitMarket),
-        // "TRAILING_STOP_MARKET" => Ok(OrdType::TrailingStopMarket),
-        s => Err(Error::invalid_value(Unexpected::Other(s), &"LIMIT,MARKET")),
+        "STOP" => Ok(OrdType::StopLimit),
+        "TAKE_PROFIT" => Ok(OrdType::TakeProfitLimit),
+        "STOP_MARKET" => Ok(OrdType::StopMarket),
+        "TAKE_PROFIT_MARKET" => Ok(OrdType::TakeProfitMarket),
+        "TRAILING_STOP_MARKET" => Ok(OrdType::TrailingStopMarket),
+        s => Err(Error::invalid_value(
+            Unexpected::Other(s),
+            &"LIMIT,MARKET,STOP,TAKE_PROFIT,STOP_MARKET,TAKE_PROFIT_MARKET,TRAILING_STOP_MARKET",
+        )),
+    }
+}
+
+fn from_str_to_trigger_source<'de, D>(deserializer: D) -> Result<TriggerSource, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: &str = Deserialize::deserialize(deserializer)?;
+    match s {
+        "CONTRACT_PRICE" => Ok(TriggerSource::LastPrice),
+        "MARK_PRICE" => Ok(TriggerSource::MarkPrice),
+        s => Err(Error::invalid_value(Unexpected::Other(s), &"CONTRACT_PRICE,MARK_PRICE")),
     }
 }
 
@@ -69,10 +97,10 @@ where
         "IOC" => Ok(TimeInForce::IOC),
         "FOK" => Ok(TimeInForce::FOK),
         "GTX" => Ok(TimeInForce::GTX),
-        // "GTD" => Ok(TimeInForce::GTD),
+        "GTD" => Ok(TimeInForce::GTD),
         s => Err(Error::invalid_value(
             Unexpected::Other(s),
-            &"GTC,IOC,FOK,GTX",
+            &"GTC,IOC,FOK,GTX,GTD",
         )),
     }
 }
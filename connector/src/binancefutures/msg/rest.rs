@@ -1,7 +1,14 @@
-use hftbacktest::types::{OrdType, Side, Status, TimeInForce};
+use hftbacktest::types::{OrdType, PositionSide, Side, Status, TimeInForce, TriggerSource};
 use serde::Deserialize;
 
-use super::{from_str_to_side, from_str_to_status, from_str_to_tif, from_str_to_type};
+use super::{
+    from_str_to_position_side,
+    from_str_to_side,
+    from_str_to_status,
+    from_str_to_tif,
+    from_str_to_trigger_source,
+    from_str_to_type,
+};
 use crate::utils::{from_str_to_f64, from_str_to_f64_opt};
 
 #[derive(Deserialize, Debug)]
@@ -48,7 +55,8 @@ pub struct OrderResponse {
     #[serde(deserialize_with = "from_str_to_side")]
     pub side: Side,
     #[serde(rename = "positionSide")]
-    pub position_side: String,
+    #[serde(deserialize_with = "from_str_to_position_side")]
+    pub position_side: PositionSide,
     #[serde(deserialize_with = "from_str_to_status")]
     pub status: Status,
     #[serde(rename = "stopPrice")]
@@ -84,7 +92,8 @@ pub struct OrderResponse {
     #[serde(rename = "updateTime")]
     pub update_time: i64,
     #[serde(rename = "workingType")]
-    pub working_type: String,
+    #[serde(deserialize_with = "from_str_to_trigger_source")]
+    pub working_type: TriggerSource,
     #[serde(rename = "priceProtect")]
     pub price_protect: bool,
     #[serde(rename = "priceMatch")]
@@ -135,7 +144,8 @@ pub struct PositionInformationV2 {
     #[serde(rename = "unRealizedProfit")]
     pub unrealized_pnl: String,
     #[serde(rename = "positionSide")]
-    pub position_side: String,
+    #[serde(deserialize_with = "from_str_to_position_side")]
+    pub position_side: PositionSide,
     #[serde(rename = "updateTime")]
     pub update_time: i64,
 }
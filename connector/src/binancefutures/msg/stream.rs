@@ -1,8 +1,15 @@
-use hftbacktest::types::{OrdType, Side, Status, TimeInForce};
+use hftbacktest::types::{OrdType, PositionSide, Side, Status, TimeInForce, TriggerSource};
 use serde::Deserialize;
 
-use super::{from_str_to_side, from_str_to_status, from_str_to_tif, from_str_to_type};
-use crate::utils::from_str_to_f64;
+use super::{
+    from_str_to_position_side,
+    from_str_to_side,
+    from_str_to_status,
+    from_str_to_tif,
+    from_str_to_trigger_source,
+    from_str_to_type,
+};
+use crate::utils::{from_str_to_f64, from_str_to_f64_opt};
 
 #[derive(Deserialize, Debug)]
 #[serde(tag = "e")]
@@ -120,7 +127,8 @@ pub struct Position {
     #[serde(rename = "iw")]
     pub isolated_wallet: Option<String>,
     #[serde(rename = "ps")]
-    pub position_side: String,
+    #[serde(deserialize_with = "from_str_to_position_side")]
+    pub position_side: PositionSide,
 }
 
 #[derive(Deserialize, Debug)]
@@ -190,20 +198,25 @@ pub struct Order {
     // pub ask_notional: String,
     // #[serde(rename = "m")]
     // pub is_maker_side: bool,
-    // #[serde(rename = "R")]
-    // pub is_reduce_only: bool,
-    // #[serde(rename = "wt")]
-    // pub stop_price_working_type: String,
-    // #[serde(rename = "ot")]
-    // pub original_order_type: String,
-    // #[serde(rename = "ps")]
-    // pub position_side: String,
+    #[serde(rename = "R")]
+    pub is_reduce_only: bool,
+    #[serde(rename = "wt")]
+    #[serde(deserialize_with = "from_str_to_trigger_source")]
+    pub stop_price_working_type: TriggerSource,
+    #[serde(rename = "ot")]
+    #[serde(deserialize_with = "from_str_to_type")]
+    pub original_order_type: OrdType,
+    #[serde(rename = "ps")]
+    #[serde(deserialize_with = "from_str_to_position_side")]
+    pub position_side: PositionSide,
     // #[serde(rename = "cp")]
     // pub close_all: Option<String>,
     // #[serde(rename = "AP")]
     // pub activation_price: Option<String>,
-    // #[serde(rename = "cr")]
-    // pub callback_rate: Option<String>,
+    #[serde(rename = "cr")]
+    #[serde(default)]
+    #[serde(deserialize_with = "from_str_to_f64_opt")]
+    pub callback_rate: Option<f64>,
     // #[serde(rename = "pP")]
     // pub price_protection: bool,
     // #[serde(rename = "si")]
@@ -216,8 +229,8 @@ pub struct Order {
     // pub stp_mode: String,
     // #[serde(rename = "pm")]
     // pub price_match_mode: String,
-    // #[serde(rename = "gtd")]
-    // pub gtd_auto_cancel_time: i64,
+    #[serde(rename = "gtd")]
+    pub gtd_auto_cancel_time: i64,
 }
 
 #[derive(Deserialize, Debug)]
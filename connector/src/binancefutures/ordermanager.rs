@@ -4,7 +4,7 @@ use std::{
 };
 
 use chrono::Utc;
-use hftbacktest::types::{Order, OrderId, Status};
+use hftbacktest::types::{Order, OrderId, RejectReason, Status};
 use tracing::{debug, error};
 
 use crate::{
@@ -122,6 +122,17 @@ impl OrderManager {
             // Invalid information
             q: Box::new(()),
             maker: false,
+            reject_reason: RejectReason::None,
+            trigger_price_tick: (resp.stop_price / order.tick_size).round() as i64,
+            trailing_offset: resp.price_rate.unwrap_or(0.0),
+            trailing_unit: order.trailing_unit,
+            trigger_source: resp.working_type,
+            // The type of a conditional order changes from the original type once triggered.
+            triggered: resp.ty != resp.orig_type,
+            expire_timestamp: resp.good_till_date * 1_000_000,
+            display_qty: order.display_qty,
+            reduce_only: resp.reduce_only,
+            position_side: resp.position_side,
         };
         self.update_from_rest(symbol, resp.client_order_id, order)
     }
@@ -183,6 +194,17 @@ impl OrderManager {
             // Invalid information
             q: Box::new(()),
             maker: false,
+            reject_reason: RejectReason::None,
+            trigger_price_tick: (resp.stop_price / order.tick_size).round() as i64,
+            trailing_offset: resp.price_rate.unwrap_or(0.0),
+            trailing_unit: order.trailing_unit,
+            trigger_source: resp.working_type,
+            // The type of a conditional order changes from the original type once triggered.
+            triggered: resp.ty != resp.orig_type,
+            expire_timestamp: resp.good_till_date * 1_000_000,
+            display_qty: order.display_qty,
+            reduce_only: resp.reduce_only,
+            position_side: resp.position_side,
         };
         self.update_from_rest(symbol, resp.client_order_id, order)
     }
@@ -1,5 +1,13 @@
 use chrono::Utc;
-use hftbacktest::types::{OrdType, Side, TimeInForce};
+use hftbacktest::types::{
+    OrdType,
+    PositionSide,
+    Side,
+    TimeInForce,
+    TrailingUnit,
+    Trigger,
+    TriggerSource,
+};
 use serde::Deserialize;
 
 use super::msg::{rest, rest::PositionInformationV2};
@@ -168,7 +176,16 @@ impl BinanceFuturesClient {
         qty: f64,
         order_type: OrdType,
         time_in_force: TimeInForce,
+        trigger: Option<&Trigger>,
+        expire_timestamp: i64,
+        display_qty: f64,
+        reduce_only: bool,
+        position_side: PositionSide,
     ) -> Result<OrderResponse, BinanceFuturesError> {
+        // Binance Futures doesn't support the iceberg and hidden orders.
+        if display_qty.is_finite() {
+            return Err(BinanceFuturesError::InvalidRequest);
+        }
         let mut body = String::with_capacity(200);
         body.push_str("newClientOrderId=");
         body.push_str(client_order_id);
@@ -176,14 +193,52 @@ impl BinanceFuturesClient {
         body.push_str(symbol);
         body.push_str("&side=");
         body.push_str(side.as_ref());
-        body.push_str("&price=");
-        body.push_str(&format!("{:.prec$}", price, prec = price_prec));
+        // The conditional market orders don't take the price.
+        if !matches!(
+            order_type,
+            OrdType::StopMarket | OrdType::TakeProfitMarket | OrdType::TrailingStopMarket
+        ) {
+            body.push_str("&price=");
+            body.push_str(&format!("{:.prec$}", price, prec = price_prec));
+        }
         body.push_str("&quantity=");
         body.push_str(&format!("{:.5}", qty));
         body.push_str("&type=");
         body.push_str(order_type.as_ref());
         body.push_str("&timeInForce=");
         body.push_str(time_in_force.as_ref());
+        if time_in_force == TimeInForce::GTD {
+            // Binance takes the expiry time in milliseconds.
+            body.push_str("&goodTillDate=");
+            body.push_str(&(expire_timestamp / 1_000_000).to_string());
+        }
+        if reduce_only {
+            body.push_str("&reduceOnly=true");
+        }
+        // The position side is only sent in hedge mode.
+        if position_side != PositionSide::Both {
+            body.push_str("&positionSide=");
+            body.push_str(position_side.as_ref());
+        }
+        if order_type.is_conditional() {
+            let trigger = trigger.ok_or(BinanceFuturesError::InvalidRequest)?;
+            if order_type == OrdType::TrailingStopMarket {
+                // Binance only supports the callback rate in percent.
+                if trigger.trailing_unit != TrailingUnit::Percent {
+                    return Err(BinanceFuturesError::InvalidRequest);
+                }
+                body.push_str("&callbackRate=");
+                body.push_str(&format!("{:.1}", trigger.trailing_offset));
+            } else {
+                body.push_str("&stopPrice=");
+                body.push_str(&format!("{:.prec$}", trigger.price, prec = price_prec));
+            }
+            body.push_str("&workingType=");
+            body.push_str(match trigger.source {
+                TriggerSource::LastPrice => "CONTRACT_PRICE",
+                TriggerSource::MarkPrice => "MARK_PRICE",
+            });
+        }
 
         let resp: OrderResponseResult = self.post("/fapi/v1/order", body).await?;
         match resp {
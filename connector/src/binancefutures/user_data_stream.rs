@@ -74,6 +74,7 @@ impl UserDataStream {
                 .send(PublishMessage::LiveEvent(LiveEvent::Position {
                     symbol: position.symbol.to_lowercase(),
                     qty: position.position_amount,
+                    position_side: position.position_side,
                 }))
                 .unwrap();
         });
@@ -82,6 +83,7 @@ impl UserDataStream {
                 .send(PublishMessage::LiveEvent(LiveEvent::Position {
                     symbol,
                     qty: 0.0,
+                    position_side: PositionSide::Both,
                 }))
                 .unwrap();
         }
@@ -104,6 +106,7 @@ impl UserDataStream {
                         .send(PublishMessage::LiveEvent(LiveEvent::Position {
                             symbol: position.symbol.to_lowercase(),
                             qty: position.position_amount,
+                            position_side: position.position_side,
                         }))
                         .unwrap();
                 }
@@ -135,6 +138,22 @@ impl UserDataStream {
                             // Invalid information
                             q: Box::new(()),
                             maker: false,
+                            reject_reason: RejectReason::None,
+                            trigger_price_tick: (data.order.stop_price / asset_info.tick_size)
+                                .round() as i64,
+                            trailing_offset: data.order.callback_rate.unwrap_or(0.0),
+                            // Binance only supports the callback rate in percent.
+                            trailing_unit: if data.order.callback_rate.is_some() {
+                                TrailingUnit::Percent
+                            } else {
+                                TrailingUnit::Ticks
+                            },
+                            trigger_source: data.order.stop_price_working_type,
+                            triggered: data.order.order_type != data.order.original_order_type,
+                            expire_timestamp: data.order.gtd_auto_cancel_time * 1_000_000,
+                            display_qty: f64::INFINITY,
+                            reduce_only: data.order.is_reduce_only,
+                            position_side: data.order.position_side,
                         };
 
                         let order = self.order_manager.lock().unwrap().update_from_ws(
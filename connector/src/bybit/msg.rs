@@ -1,6 +1,6 @@
 use std::{collections::HashMap, fmt, fmt::Debug};
 
-use hftbacktest::types::{OrdType, Side, Status, TimeInForce};
+use hftbacktest::types::{OrdType, PositionSide, Side, Status, TimeInForce};
 use serde::{
     de,
     de::{Error, Unexpected, Visitor},
@@ -116,13 +116,13 @@ impl<'de> Visitor<'de> for StatusVisitor {
         match s {
             "New" => Ok(Status::New),
             "PartiallyFilled" => Ok(Status::PartiallyFilled),
-            "Untriggered" => Ok(Status::Unsupported),
+            "Untriggered" => Ok(Status::New),
             "Rejected" => Ok(Status::Expired),
             "PartiallyFilledCanceled" => Ok(Status::Canceled),
             "Filled" => Ok(Status::Filled),
             "Cancelled" => Ok(Status::Canceled),
-            "Triggered" => Ok(Status::Unsupported),
-            "Deactivated" => Ok(Status::Unsupported),
+            "Triggered" => Ok(Status::New),
+            "Deactivated" => Ok(Status::Canceled),
             s => Err(Error::invalid_value(Unexpected::Other(s), &"IOC or GTC")),
         }
     }
@@ -135,6 +135,19 @@ where
     deserializer.deserialize_str(StatusVisitor)
 }
 
+fn from_i64_to_position_side<'de, D>(deserializer: D) -> Result<PositionSide, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let position_idx: i64 = Deserialize::deserialize(deserializer)?;
+    match position_idx {
+        0 => Ok(PositionSide::Both),
+        1 => Ok(PositionSide::Long),
+        2 => Ok(PositionSide::Short),
+        idx => Err(Error::invalid_value(Unexpected::Signed(idx), &"0, 1 or 2")),
+    }
+}
+
 #[derive(Serialize, Debug)]
 pub struct Op {
     pub req_id: String,
@@ -262,7 +275,8 @@ where
 #[derive(Deserialize, Debug)]
 pub struct Position {
     #[serde(rename = "positionIdx")]
-    pub position_idx: i64,
+    #[serde(deserialize_with = "from_i64_to_position_side")]
+    pub position_idx: PositionSide,
     #[serde(rename = "tradeMode")]
     pub trade_mode: i64,
     #[serde(rename = "riskId")]
@@ -335,6 +349,18 @@ pub struct Position {
     pub is_reduce_only: bool,
 }
 
+impl Position {
+    /// Returns the signed position quantity, since Bybit reports the size without a sign along
+    /// with the side of the position.
+    pub fn qty(&self) -> f64 {
+        if self.side == "Sell" {
+            -self.size
+        } else {
+            self.size
+        }
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Execution {
     pub category: String,
@@ -467,7 +493,8 @@ pub struct PrivateOrder {
     #[serde(rename = "blockTradeId")]
     pub block_trade_id: String,
     #[serde(rename = "positionIdx")]
-    pub position_idx: i64,
+    #[serde(deserialize_with = "from_i64_to_position_side")]
+    pub position_idx: PositionSide,
     #[serde(rename = "cumExecFee")]
     pub cum_exec_fee: String,
     #[serde(rename = "createdTime")]
@@ -572,6 +599,23 @@ pub struct Order {
     pub time_in_force: Option<String>,
     #[serde(rename = "orderLinkId")]
     pub order_link_id: String,
+    #[serde(rename = "triggerPrice")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<String>,
+    /// 1: triggered when the price rises to the trigger price, 2: when it falls to it.
+    #[serde(rename = "triggerDirection")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_direction: Option<i64>,
+    #[serde(rename = "triggerBy")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_by: Option<String>,
+    /// 0: one-way mode, 1: the buy side of hedge mode, 2: the sell side of hedge mode.
+    #[serde(rename = "positionIdx")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_idx: Option<i64>,
+    #[serde(rename = "reduceOnly")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reduce_only: Option<bool>,
 }
 
 #[derive(Deserialize, Debug)]
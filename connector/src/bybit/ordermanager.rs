@@ -5,7 +5,16 @@ use std::{
 
 use hftbacktest::{
     prelude::get_precision,
-    types::{OrdType, Order, OrderId, Side, Status, TimeInForce},
+    types::{
+        OrdType,
+        Order,
+        OrderId,
+        PositionSide,
+        Side,
+        Status,
+        TimeInForce,
+        TriggerSource,
+    },
 };
 
 use crate::{
@@ -56,6 +65,11 @@ impl OrderManager {
         order_info.order.req = Status::None;
         order_info.order.status = data.order_status;
         order_info.order.exch_timestamp = data.updated_time * 1_000_000;
+        // The conditional order can be executed only after it is triggered.
+        if order_info.order.order_type.is_conditional() && data.cum_exec_qty > 0.0 {
+            order_info.order.order_type = data.order_type;
+            order_info.order.triggered = true;
+        }
         let is_active = order_info.order.active();
         if !is_active {
             Ok(self.orders.remove(&order_id).unwrap())
@@ -97,8 +111,37 @@ impl OrderManager {
         category: &str,
         order: Order,
     ) -> Result<BybitOrder, BybitError> {
+        // Bybit doesn't support the iceberg and hidden orders.
+        if order.display_qty.is_finite() {
+            return Err(BybitError::InvalidArg("display_qty"));
+        }
         let price_prec = get_precision(order.tick_size);
         let rand_id = gen_random_string(8);
+        // Bybit places a conditional order as a market or limit order with a trigger price, and a
+        // trigger direction in which the price should move to trigger it.
+        let (trigger_price, trigger_direction, trigger_by) = match order.order_type {
+            OrdType::StopMarket
+            | OrdType::StopLimit
+            | OrdType::TakeProfitMarket
+            | OrdType::TakeProfitLimit => {
+                let is_stop = matches!(order.order_type, OrdType::StopMarket | OrdType::StopLimit);
+                let rises = match order.side {
+                    Side::Buy => is_stop,
+                    Side::Sell => !is_stop,
+                    Side::None | Side::Unsupported => return Err(BybitError::InvalidArg("side")),
+                };
+                let trigger_by = match order.trigger_source {
+                    TriggerSource::LastPrice => "LastPrice",
+                    TriggerSource::MarkPrice => "MarkPrice",
+                };
+                (
+                    Some(format!("{:.prec$}", order.trigger_price(), prec = price_prec)),
+                    Some(if rises { 1 } else { 2 }),
+                    Some(trigger_by.to_string()),
+                )
+            }
+            _ => (None, None, None),
+        };
         let bybit_order = BybitOrder {
             symbol: symbol.to_string(),
             side: Some({
@@ -110,9 +153,16 @@ impl OrderManager {
             }),
             order_type: Some({
                 match order.order_type {
-                    OrdType::Limit => "Limit".to_string(),
-                    OrdType::Market => "Market".to_string(),
-                    OrdType::Unsupported => return Err(BybitError::InvalidArg("order_type")),
+                    OrdType::Limit | OrdType::StopLimit | OrdType::TakeProfitLimit => {
+                        "Limit".to_string()
+                    }
+                    OrdType::Market | OrdType::StopMarket | OrdType::TakeProfitMarket => {
+                        "Market".to_string()
+                    }
+                    // The trailing stop is only available as a position's trading stop.
+                    OrdType::TrailingStopMarket | OrdType::Unsupported => {
+                        return Err(BybitError::InvalidArg("order_type"));
+                    }
                 }
             }),
             qty: Some(format!("{:.5}", order.qty)),
@@ -128,12 +178,23 @@ impl OrderManager {
                     TimeInForce::GTX => "PostOnly".to_string(),
                     TimeInForce::FOK => "FOK".to_string(),
                     TimeInForce::IOC => "IOC".to_string(),
-                    TimeInForce::Unsupported => {
+                    // Bybit doesn't support the good-till-date orders.
+                    TimeInForce::GTD | TimeInForce::Unsupported => {
                         return Err(BybitError::InvalidArg("time_in_force"));
                     }
                 }
             }),
             order_link_id: format!("{}{}{}", self.prefix, rand_id, order.order_id),
+            trigger_price,
+            trigger_direction,
+            trigger_by,
+            position_idx: Some(match order.position_side {
+                PositionSide::Both => 0,
+                PositionSide::Long => 1,
+                PositionSide::Short => 2,
+                PositionSide::Unsupported => return Err(BybitError::InvalidArg("position_side")),
+            }),
+            reduce_only: order.reduce_only.then_some(true),
         };
         match self.orders.entry(order.order_id) {
             Entry::Occupied(_) => {
@@ -169,6 +230,11 @@ impl OrderManager {
             category: category.to_string(),
             time_in_force: None,
             order_link_id: order_info.order_link_id.clone(),
+            trigger_price: None,
+            trigger_direction: None,
+            trigger_by: None,
+            position_idx: None,
+            reduce_only: None,
         };
         Ok(bybit_order)
     }
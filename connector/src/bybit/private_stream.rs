@@ -85,7 +85,8 @@ impl PrivateStream {
                 self.ev_tx
                     .send(PublishMessage::LiveEvent(LiveEvent::Position {
                         symbol: symbol.clone(),
-                        qty: position.size,
+                        qty: position.qty(),
+                        position_side: position.position_idx,
                     }))
                     .unwrap();
             });
@@ -128,8 +129,9 @@ impl PrivateStream {
                 for item in data.data {
                     self.ev_tx
                         .send(PublishMessage::LiveEvent(LiveEvent::Position {
+                            qty: item.qty(),
+                            position_side: item.position_idx,
                             symbol: item.symbol,
-                            qty: item.size,
                         }))
                         .unwrap();
                 }
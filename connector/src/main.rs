@@ -109,12 +109,15 @@ async fn run_publish_task(
             } => {
                 let symbol = symbol.to_lowercase();
 
-                if let Some(qty) = position.get(&symbol) {
-                    let lev = LiveEvent::Position {
-                        symbol: symbol.clone(),
-                        qty: *qty,
-                    };
-                    bot_tx.send(id, &lev)?;
+                for ((position_symbol, position_side), qty) in position.iter() {
+                    if *position_symbol == symbol {
+                        let lev = LiveEvent::Position {
+                            symbol: symbol.clone(),
+                            qty: *qty,
+                            position_side: *position_side,
+                        };
+                        bot_tx.send(id, &lev)?;
+                    }
                 }
 
                 match depth.entry(symbol) {
@@ -163,7 +166,7 @@ async fn run_publish_task(
 fn handle_ev(
     ev: &LiveEvent,
     depth: &mut HashMap<String, FusedHashMapMarketDepth>,
-    position: &mut HashMap<String, f64>,
+    position: &mut HashMap<(String, PositionSide), f64>,
 ) -> bool {
     match ev {
         LiveEvent::Feed { symbol, event } => {
@@ -184,12 +187,12 @@ fn handle_ev(
                 depth_.clear_depth(Side::None, 0.0);
             }
         }
-        LiveEvent::Position { symbol, qty } => {
-            if position.contains_key(symbol) {
-                *position.get_mut(symbol).unwrap() = *qty;
-            } else {
-                position.insert(symbol.clone(), *qty);
-            }
+        LiveEvent::Position {
+            symbol,
+            qty,
+            position_side,
+        } => {
+            position.insert((symbol.clone(), *position_side), *qty);
         }
         _ => {}
     }
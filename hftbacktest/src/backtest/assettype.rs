@@ -1,10 +1,14 @@
 /// Calculates the value amount and the equity according to the asset type.
-pub trait AssetType {
+pub trait AssetType: Send {
     /// Calculates the value amount.
     fn amount(&self, price: f64, qty: f64) -> f64;
 
     /// Calculates the equity.
     fn equity(&self, price: f64, balance: f64, position: f64, fee: f64) -> f64;
+
+    /// Calculates the amount to be added to the balance when the funding is settled for the
+    /// position. A positive funding rate means that long positions pay short positions.
+    fn funding(&self, mark_price: f64, position: f64, rate: f64) -> f64;
 }
 
 /// The common type of asset where the contract's notional value is linear to the quote currency.
@@ -28,6 +32,10 @@ impl AssetType for LinearAsset {
     fn equity(&self, price: f64, balance: f64, position: f64, fee: f64) -> f64 {
         balance + self.contract_size * position * price - fee
     }
+
+    fn funding(&self, mark_price: f64, position: f64, rate: f64) -> f64 {
+        -self.contract_size * position * mark_price * rate
+    }
 }
 
 /// The contract’s notional value is denominated in the quote currency.
@@ -51,4 +59,9 @@ impl AssetType for InverseAsset {
     fn equity(&self, price: f64, balance: f64, position: f64, fee: f64) -> f64 {
         -balance - self.contract_size * position / price - fee
     }
+
+    fn funding(&self, mark_price: f64, position: f64, rate: f64) -> f64 {
+        // The balance is negated in the equity.
+        self.contract_size * position / mark_price * rate
+    }
 }
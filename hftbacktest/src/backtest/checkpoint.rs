@@ -0,0 +1,256 @@
+use std::{
+    io::{Error as IoError, ErrorKind},
+    mem::{self, size_of},
+};
+
+use bincode::{config, Decode, Encode};
+
+use crate::{
+    backtest::{
+        data::{Data, DataPtr, Reader},
+        BacktestError,
+    },
+    depth::{ApplySnapshot, L3MarketDepth},
+    types::{Event, Order, OrderId, Side, StateValues},
+};
+
+/// Encodes the value into the bytes stored in a checkpoint.
+pub fn encode<T: Encode>(value: &T) -> Result<Vec<u8>, BacktestError> {
+    bincode::encode_to_vec(value, config::standard())
+        .map_err(|error| BacktestError::DataError(IoError::new(ErrorKind::InvalidData, error)))
+}
+
+/// Decodes the value from the bytes stored in a checkpoint.
+pub fn decode<T: Decode>(bytes: &[u8]) -> Result<T, BacktestError> {
+    let (value, _) = bincode::decode_from_slice(bytes, config::standard())
+        .map_err(|error| BacktestError::DataError(IoError::new(ErrorKind::InvalidData, error)))?;
+    Ok(value)
+}
+
+/// Reloads the data that was being read when the checkpoint was made. `data_num` is the
+/// [`Reader`]'s position at that time.
+pub fn restore_data(
+    reader: &mut Reader<Event>,
+    data: &mut Data<Event>,
+    data_num: usize,
+) -> Result<(), BacktestError> {
+    if !data.is_empty() {
+        reader.release(mem::replace(data, Data::empty()));
+    }
+    if data_num > 0 {
+        reader.seek(data_num - 1);
+        *data = reader.next_data()?;
+    }
+    Ok(())
+}
+
+/// The market depth saved in a checkpoint.
+#[derive(Encode, Decode)]
+pub struct DepthCheckpoint {
+    snapshot: Vec<Event>,
+    // (order ID, side, price in ticks, quantity, timestamp) of the Level-3 orders.
+    orders: Vec<(OrderId, Side, i64, f64, i64)>,
+}
+
+impl DepthCheckpoint {
+    /// Saves the price levels of the Level-2 market depth.
+    pub fn l2<MD>(depth: &MD) -> Self
+    where
+        MD: ApplySnapshot,
+    {
+        Self {
+            snapshot: depth.snapshot(),
+            orders: Vec::new(),
+        }
+    }
+
+    /// Saves both the price levels and the orders of the Level-3 market depth.
+    pub fn l3<MD>(depth: &MD) -> Self
+    where
+        MD: L3MarketDepth + ApplySnapshot,
+    {
+        let mut orders: Vec<_> = depth
+            .orders()
+            .values()
+            .map(|order| {
+                (
+                    order.order_id,
+                    order.side,
+                    order.price_tick,
+                    order.qty,
+                    order.timestamp,
+                )
+            })
+            .collect();
+        orders.sort_by_key(|order| order.0);
+        Self {
+            snapshot: depth.snapshot(),
+            orders,
+        }
+    }
+
+    /// Restores the price levels of the Level-2 market depth.
+    pub fn restore_l2<MD>(&self, depth: &mut MD)
+    where
+        MD: ApplySnapshot,
+    {
+        depth.apply_snapshot(&self.snapshot_data());
+    }
+
+    /// Restores both the price levels and the orders of the Level-3 market depth.
+    pub fn restore_l3<MD>(&self, depth: &mut MD) -> Result<(), BacktestError>
+    where
+        MD: L3MarketDepth + ApplySnapshot,
+        BacktestError: From<<MD as L3MarketDepth>::Error>,
+    {
+        depth.clear_orders(Side::None);
+        for &(order_id, side, price_tick, qty, timestamp) in &self.orders {
+            let px = price_tick as f64 * depth.tick_size();
+            match side {
+                Side::Buy => {
+                    depth.add_buy_order(order_id, px, qty, timestamp)?;
+                }
+                Side::Sell => {
+                    depth.add_sell_order(order_id, px, qty, timestamp)?;
+                }
+                Side::None | Side::Unsupported => unreachable!(),
+            }
+        }
+        // Overwrites the quantities accumulated by adding the orders with the saved ones, since
+        // the order in which the quantities are summed may differ.
+        depth.apply_snapshot(&self.snapshot_data());
+        Ok(())
+    }
+
+    fn snapshot_data(&self) -> Data<Event> {
+        if self.snapshot.is_empty() {
+            return Data::empty();
+        }
+        let mut data = unsafe {
+            Data::from_data_ptr(DataPtr::new(self.snapshot.len() * size_of::<Event>()), 0)
+        };
+        for (i, event) in self.snapshot.iter().enumerate() {
+            data[i] = event.clone();
+        }
+        data
+    }
+}
+
+/// The local processor's state saved in a checkpoint.
+#[derive(Encode, Decode)]
+pub struct LocalCheckpoint {
+    pub data_num: usize,
+    pub row_num: usize,
+    pub depth: DepthCheckpoint,
+    pub state_values: StateValues,
+    pub orders: Vec<Order>,
+    // The order buses are shared with the exchange processor, so only the local processor saves
+    // them.
+    pub orders_to: Vec<(Order, i64)>,
+    pub orders_from: Vec<(Order, i64)>,
+    pub trades: Vec<Event>,
+    pub last_feed_latency: Option<(i64, i64)>,
+    pub last_order_latency: Option<(i64, i64, i64)>,
+    // The state of the order latency model, of which the local processor has its own copy.
+    pub order_latency: Vec<u8>,
+    // The state of the fault injector, which is empty without one.
+    pub fault_injector: Vec<u8>,
+}
+
+/// The exchange processor's state saved in a checkpoint.
+#[derive(Encode, Decode)]
+pub struct ExchCheckpoint {
+    pub data_num: usize,
+    pub row_num: usize,
+    pub depth: DepthCheckpoint,
+    pub state_values: StateValues,
+    // The open orders with their encoded queue positions. The Level-3 exchange models hold the
+    // open orders in the queue model instead.
+    pub orders: Vec<(Order, Vec<u8>)>,
+    pub queue: Vec<u8>,
+    // The untriggered conditional orders with the reference prices.
+    pub conditional_orders: Vec<u8>,
+    // The expiry schedule of the good-till-date orders.
+    pub order_expiry: Vec<u8>,
+    // The reduce-only orders to cancel once they would increase the position.
+    pub reduce_only_orders: Vec<u8>,
+    // The requests counted against the rate limits, which is empty without a rate limit model.
+    pub rate_limit: Vec<u8>,
+    // (price tick, quantity) of the liquidity consumed by the taker fills, which only the
+    // PartialFillExchange tracks.
+    pub consumed_bid: Vec<(i64, f64)>,
+    pub consumed_ask: Vec<(i64, f64)>,
+    // The state of the order latency model, of which the exchange processor has its own copy.
+    pub order_latency: Vec<u8>,
+}
+
+/// The [`Backtest`](crate::backtest::Backtest)'s state saved in a checkpoint.
+#[derive(Encode, Decode)]
+pub struct BacktestCheckpoint {
+    pub cur_ts: i64,
+    pub evs_timestamp: Vec<i64>,
+    pub evs_invalid: usize,
+    pub local: Vec<Vec<u8>>,
+    pub exch: Vec<Vec<u8>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DepthCheckpoint;
+    use crate::{
+        backtest::BacktestError,
+        depth::{
+            ApplySnapshot,
+            BTreeMarketDepth,
+            HashMapMarketDepth,
+            L3MarketDepth,
+            ROIVectorMarketDepth,
+        },
+    };
+
+    fn restore_l3_depth<MD>(depth: fn() -> MD)
+    where
+        MD: L3MarketDepth<Error = BacktestError> + ApplySnapshot,
+    {
+        let mut saved = depth();
+        saved.add_buy_order(1, 99.0, 2.0, 10).unwrap();
+        saved.add_buy_order(2, 99.0, 3.0, 20).unwrap();
+        saved.add_buy_order(3, 97.0, 1.0, 30).unwrap();
+        saved.add_sell_order(4, 101.0, 4.0, 40).unwrap();
+        saved.add_sell_order(5, 103.0, 5.0, 50).unwrap();
+        saved.delete_order(2, 60).unwrap();
+        let checkpoint = DepthCheckpoint::l3(&saved);
+
+        let mut restored = depth();
+        restored.add_sell_order(6, 100.0, 1.0, 0).unwrap();
+        checkpoint.restore_l3(&mut restored).unwrap();
+        assert_eq!(restored.best_bid_tick(), 99);
+        assert_eq!(restored.best_ask_tick(), 101);
+        for price_tick in 95..=105 {
+            assert_eq!(
+                restored.bid_qty_at_tick(price_tick),
+                saved.bid_qty_at_tick(price_tick)
+            );
+            assert_eq!(
+                restored.ask_qty_at_tick(price_tick),
+                saved.ask_qty_at_tick(price_tick)
+            );
+        }
+        let mut order_ids: Vec<_> = restored.orders().keys().copied().collect();
+        order_ids.sort();
+        assert_eq!(order_ids, [1, 3, 4, 5]);
+
+        // The restored orders can be modified and deleted.
+        restored.modify_order(4, 102.0, 4.0, 70).unwrap();
+        assert_eq!(restored.best_ask_tick(), 102);
+        restored.delete_order(1, 80).unwrap();
+        assert_eq!(restored.best_bid_tick(), 97);
+    }
+
+    #[test]
+    fn restore_every_l3_market_depth() {
+        restore_l3_depth(|| HashMapMarketDepth::new(1.0, 1.0));
+        restore_l3_depth(|| BTreeMarketDepth::new(1.0, 1.0));
+        restore_l3_depth(|| ROIVectorMarketDepth::new(1.0, 1.0, 0.0, 200.0));
+    }
+}
@@ -6,12 +6,20 @@ use std::{
     mem::size_of,
     ops::{Index, IndexMut},
     ptr::null_mut,
-    rc::Rc,
     slice::SliceIndex,
+    sync::Arc,
 };
 
 pub use npy::{read_npy_file, read_npz_file, write_npy, Field, NpyDTyped, NpyHeader};
-pub use reader::{Cache, DataPreprocess, DataSource, FeedLatencyAdjustment, Reader, ReaderBuilder};
+pub use reader::{
+    Cache,
+    DataPreprocess,
+    DataSource,
+    FeedLatencyAdjustment,
+    LocalFeedDegradation,
+    Reader,
+    ReaderBuilder,
+};
 
 use crate::utils::{AlignedArray, CACHE_LINE_SIZE};
 
@@ -23,12 +31,17 @@ use crate::utils::{AlignedArray, CACHE_LINE_SIZE};
 pub unsafe trait POD: Sized {}
 
 /// Provides access to an array of structs from the buffer.
+///
+/// Cloning `Data` does not copy the buffer, and the clones can be sent to other threads, which
+/// allows backtests running in parallel to share the same data loaded in memory. Writing to `Data`
+/// whose buffer is shared with its clones copies the buffer first, so the clones never see the
+/// change.
 #[derive(Clone, Debug)]
 pub struct Data<D>
 where
     D: POD + Clone,
 {
-    ptr: Rc<DataPtr>,
+    ptr: Arc<DataPtr>,
     offset: usize,
     _d_marker: PhantomData<D>,
 }
@@ -66,7 +79,7 @@ where
     /// offset.
     pub unsafe fn from_data_ptr(ptr: DataPtr, offset: usize) -> Self {
         Self {
-            ptr: Rc::new(ptr),
+            ptr: Arc::new(ptr),
             offset,
             _d_marker: PhantomData,
         }
@@ -86,7 +99,17 @@ where
 
     /// Returns `true` if the two `Data` point to the same data.
     pub fn data_eq(&self, other: &Self) -> bool {
-        Rc::ptr_eq(&self.ptr, &other.ptr)
+        Arc::ptr_eq(&self.ptr, &other.ptr)
+    }
+
+    /// Returns the buffer to write to, which is copied first if it is shared with other `Data`.
+    fn make_mut(&mut self) -> &DataPtr {
+        if Arc::get_mut(&mut self.ptr).is_none() {
+            let mut ptr = DataPtr::new(self.ptr.len());
+            ptr[..].copy_from_slice(&self.ptr[..]);
+            self.ptr = Arc::new(ptr);
+        }
+        &self.ptr
     }
 }
 
@@ -117,7 +140,7 @@ where
         if i + size > self.ptr.len() {
             panic!("Out of the size.");
         }
-        unsafe { &mut *(self.ptr.at(i) as *mut D) }
+        unsafe { &mut *(self.make_mut().at(i) as *mut D) }
     }
 }
 
@@ -127,6 +150,11 @@ pub struct DataPtr {
     managed: bool,
 }
 
+// `Data` writes to the buffer only while it holds the buffer exclusively, and copies the buffer
+// otherwise, so the buffer is read-only once it can be accessed from multiple threads.
+unsafe impl Send for DataPtr {}
+unsafe impl Sync for DataPtr {}
+
 impl DataPtr {
     pub fn new(size: usize) -> Self {
         let arr = AlignedArray::<u8, CACHE_LINE_SIZE>::new(size);
@@ -1,15 +1,15 @@
 use std::{
-    cell::RefCell,
     collections::HashMap,
     io::{Error as IoError, ErrorKind},
-    rc::Rc,
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc,
+        Mutex,
     },
     thread,
 };
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use uuid::Uuid;
 
 use crate::{
@@ -21,7 +21,15 @@ use crate::{
         },
         BacktestError,
     },
-    types::Event,
+    types::{
+        Event,
+        BUY_EVENT,
+        DEPTH_BBO_EVENT,
+        DEPTH_EVENT,
+        LOCAL_EVENT,
+        SELL_EVENT,
+        TRADE_EVENT,
+    },
 };
 
 /// Data source for the [`Reader`].
@@ -86,8 +94,11 @@ where
 
 /// Provides a data cache that allows both the local processor and exchange processor to access the
 /// same or different data based on their timestamps without the need for reloading.
+///
+/// The cached [`Data`] is shared, not copied, by every clone of the `Cache`, including clones
+/// owned by other threads.
 #[derive(Clone, Debug)]
-pub struct Cache<D>(Rc<RefCell<HashMap<String, CachedData<D>>>>)
+pub struct Cache<D>(Arc<Mutex<HashMap<String, CachedData<D>>>>)
 where
     D: POD + Clone;
 
@@ -102,19 +113,19 @@ where
 
     /// Inserts a key-value pair into the `Cache`.
     pub fn insert(&mut self, key: String, data: Data<D>) {
-        self.0.borrow_mut().insert(key, CachedData::new(data));
+        self.0.lock().unwrap().insert(key, CachedData::new(data));
     }
 
     /// Prepares cached data by inserting a key-value pair with empty data into the `Cache`.
     /// This placeholder will be replaced when the actual data is ready.
     pub fn prepare(&mut self, key: String) {
-        self.0.borrow_mut().insert(key, CachedData::empty());
+        self.0.lock().unwrap().insert(key, CachedData::empty());
     }
 
     /// Removes the [`Data`] if all retrieved [`Data`] are released.
     pub fn remove(&mut self, data: Data<D>) {
         let mut remove = None;
-        for (key, cached_data) in self.0.borrow_mut().iter_mut() {
+        for (key, cached_data) in self.0.lock().unwrap().iter_mut() {
             if data.data_eq(&cached_data.data) {
                 if cached_data.turn_in() {
                     remove = Some(key.clone());
@@ -123,25 +134,25 @@ where
             }
         }
         if let Some(key) = remove {
-            self.0.borrow_mut().remove(&key).unwrap();
+            self.0.lock().unwrap().remove(&key).unwrap();
         }
     }
 
     /// Returns `true` if the `Cache` contains the [`Data`] for the specified key.
     pub fn contains(&self, key: &str) -> bool {
-        self.0.borrow().contains_key(key)
+        self.0.lock().unwrap().contains_key(key)
     }
 
     /// Returns the [`Data`] corresponding to the key.
     pub fn get(&mut self, key: &str) -> Data<D> {
-        let mut borrowed = self.0.borrow_mut();
+        let mut borrowed = self.0.lock().unwrap();
         let cached_data = borrowed.get_mut(key).unwrap();
         cached_data.checkout()
     }
 
     /// Sets the [`Data`] for the specified key and marks it as ready.
     pub fn set(&mut self, key: &str, data: Data<D>) {
-        let mut borrowed = self.0.borrow_mut();
+        let mut borrowed = self.0.lock().unwrap();
         let cached_data = borrowed.get_mut(key).unwrap();
         cached_data.set(data);
         cached_data.ready = true;
@@ -149,7 +160,7 @@ where
 
     /// Returns `true` if the [`Data`] for the specified key is ready.
     pub fn is_ready(&self, key: &str) -> bool {
-        self.0.borrow().get(key).unwrap().ready
+        self.0.lock().unwrap().get(key).unwrap().ready
     }
 }
 
@@ -162,9 +173,9 @@ where
     }
 }
 
-/// Directly implementing `Send` for `Data` may lead to unsafe sharing between threads. To mitigate
-/// this risk, `DataSend` is used to wrap `Data`, which implements the `Send` marker trait. This
-/// transfer ownership between threads while requiring careful consideration.
+/// `Data` is `Send` only if `D` is, while the `Reader` doesn't require it. `DataSend` wraps `Data`
+/// to transfer the data loaded by the loading thread to the `Reader` regardless of `D`, which is
+/// always plain old data in practice.
 struct DataSend<D>(Data<D>)
 where
     D: NpyDTyped + Clone;
@@ -295,6 +306,8 @@ where
         let mut cache = self.cache.clone();
         for (key, mut data) in self.temporary_data {
             if let Some(p) = &self.preprocessor {
+                // The in-memory data can be shared with other readers, in which case it is copied
+                // before being preprocessed so that the others still read the original data.
                 p.preprocess(&mut data)?;
             }
             cache.insert(key, data)
@@ -306,7 +319,7 @@ where
             cache,
             data_num: 0,
             tx,
-            rx: Rc::new(rx),
+            rx: Arc::new(Mutex::new(rx)),
             parallel_load: self.parallel_load,
             preprocessor: self.preprocessor.clone(),
         })
@@ -323,7 +336,7 @@ where
     cache: Cache<D>,
     data_num: usize,
     tx: Sender<LoadDataResult<D>>,
-    rx: Rc<Receiver<LoadDataResult<D>>>,
+    rx: Arc<Mutex<Receiver<LoadDataResult<D>>>>,
     parallel_load: bool,
     preprocessor: Option<Arc<Box<dyn DataPreprocess<D> + Sync + Send + 'static>>>,
 }
@@ -343,6 +356,17 @@ where
         self.cache.remove(data);
     }
 
+    /// Returns the number of [`Data`] retrieved so far.
+    pub fn data_num(&self) -> usize {
+        self.data_num
+    }
+
+    /// Moves the position so that the next call to [`next_data`](Self::next_data) retrieves the
+    /// `data_num`-th [`Data`], counting from zero.
+    pub fn seek(&mut self, data_num: usize) {
+        self.data_num = data_num;
+    }
+
     /// Retrieves the next [`Data`] based on the order of your additions.
     pub fn next_data(&mut self) -> Result<Data<D>, BacktestError> {
         if self.data_num < self.data_key_list.len() {
@@ -357,7 +381,8 @@ where
             }
 
             while !self.cache.is_ready(&key) {
-                match self.rx.recv().unwrap() {
+                let load_data_result = self.rx.lock().unwrap().recv().unwrap();
+                match load_data_result {
                     LoadDataResult {
                         key,
                         result: Ok(data),
@@ -489,3 +514,308 @@ impl DataPreprocess<Event> for FeedLatencyAdjustment {
         Ok(())
     }
 }
+
+impl<D, P1, P2> DataPreprocess<D> for (P1, P2)
+where
+    D: POD + Clone,
+    P1: DataPreprocess<D>,
+    P2: DataPreprocess<D>,
+{
+    fn preprocess(&self, data: &mut Data<D>) -> Result<(), IoError> {
+        self.0.preprocess(data)?;
+        self.1.preprocess(data)
+    }
+}
+
+/// Pre-processes the feed data to degrade the feed that the local receives, while the exchange
+/// keeps receiving the true feed. This shows how a strategy performs when its local market depth
+/// is stale, such as when it runs remotely rather than colocated.
+///
+/// The following are applied in order to the local side of the events.
+/// * Random loss: each depth, best bid and offer, or trade event is lost with the given
+///   probability.
+/// * Burst outages: outages of the given duration occur with exponentially distributed gaps, and
+///   the events during an outage are delivered when the outage ends.
+/// * Conflation: the events are delivered at the end of each conflation interval, and only the
+///   last of the depth events at the same price within the interval is delivered.
+///
+/// Lost or conflated events remain as exchange events, so only the local misses them. Depth clear
+/// and snapshot events, as well as the other kinds of events, are never lost or conflated, but are
+/// delayed along with the others to keep `local_ts` in order. As losing market-by-order events
+/// breaks the order book, this is intended for the L2 feed.
+///
+/// The random loss and the outages are drawn from a random number generator seeded by the given
+/// seed and the first exchange timestamp of each data, so the same data is always degraded in the
+/// same way.
+///
+/// For the same reason, nothing about the degradation is saved in a checkpoint: a restored
+/// backtest loads the data again and degrades it exactly as the original did. This holds for data
+/// given in memory as well, since the data held by the caller is copied before it is degraded.
+#[derive(Clone, Debug)]
+pub struct LocalFeedDegradation {
+    seed: u64,
+    loss_prob: f64,
+    outage_interval: i64,
+    outage_duration: i64,
+    conflation_interval: i64,
+}
+
+impl LocalFeedDegradation {
+    /// Constructs a `LocalFeedDegradation` that degrades nothing until it is configured.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            loss_prob: 0.0,
+            outage_interval: 0,
+            outage_duration: 0,
+            conflation_interval: 0,
+        }
+    }
+
+    /// Sets the probability that an event is lost.
+    pub fn loss_prob(self, loss_prob: f64) -> Self {
+        Self { loss_prob, ..self }
+    }
+
+    /// Makes outages that last `duration` occur with exponentially distributed gaps whose mean is
+    /// `mean_interval`, both in nanoseconds.
+    pub fn outages(self, mean_interval: i64, duration: i64) -> Self {
+        Self {
+            outage_interval: mean_interval,
+            outage_duration: duration,
+            ..self
+        }
+    }
+
+    /// Sets the conflation interval in nanoseconds.
+    pub fn conflation_interval(self, conflation_interval: i64) -> Self {
+        Self {
+            conflation_interval,
+            ..self
+        }
+    }
+
+    fn next_outage(&self, rng: &mut StdRng, after: i64) -> (i64, i64) {
+        let u = 1.0 - rng.gen::<f64>();
+        let start = after + (-u.ln() * self.outage_interval as f64) as i64;
+        (start, start + self.outage_duration)
+    }
+}
+
+impl DataPreprocess<Event> for LocalFeedDegradation {
+    fn preprocess(&self, data: &mut Data<Event>) -> Result<(), IoError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed ^ data[0].exch_ts as u64);
+        let has_outages = self.outage_interval > 0 && self.outage_duration > 0;
+        let mut outage = None;
+        // The last depth event at each price within the current conflation interval.
+        let mut conflated = HashMap::new();
+        let mut conflation_end = i64::MIN;
+
+        for i in 0..data.len() {
+            let ev = data[i].ev;
+            if ev & LOCAL_EVENT == 0 {
+                continue;
+            }
+            let kind = ev & 0xff;
+            let lossy = kind == DEPTH_EVENT || kind == DEPTH_BBO_EVENT || kind == TRADE_EVENT;
+            if lossy && self.loss_prob > 0.0 && rng.gen_bool(self.loss_prob) {
+                data[i].ev &= !LOCAL_EVENT;
+                continue;
+            }
+
+            if has_outages {
+                let local_ts = data[i].local_ts;
+                let (mut start, mut end) =
+                    outage.unwrap_or_else(|| self.next_outage(&mut rng, local_ts));
+                while end <= local_ts {
+                    (start, end) = self.next_outage(&mut rng, end);
+                }
+                outage = Some((start, end));
+                if start <= local_ts {
+                    data[i].local_ts = end;
+                }
+            }
+
+            if self.conflation_interval > 0 {
+                let interval = self.conflation_interval;
+                let local_ts = data[i].local_ts;
+                let end = (local_ts + interval - 1).div_euclid(interval) * interval;
+                if end != conflation_end {
+                    conflated.clear();
+                    conflation_end = end;
+                }
+                data[i].local_ts = end;
+                if kind == DEPTH_EVENT || kind == DEPTH_BBO_EVENT {
+                    let key = (ev & (BUY_EVENT | SELL_EVENT | 0xff), data[i].px.to_bits());
+                    if let Some(prev) = conflated.insert(key, i) {
+                        data[prev].ev &= !LOCAL_EVENT;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs::{self, File},
+        process,
+    };
+
+    use crate::{
+        backtest::{
+            data::{write_npy, DataSource, LocalFeedDegradation},
+            fixture::{asset_builder, event, to_data},
+            Backtest,
+        },
+        depth::{HashMapMarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
+        prelude::{Bot, OrdType, Status, TimeInForce},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn degrade_local_feed() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_200, 99.0, 3.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_400, 99.0, 2.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+        let build = |degradation: LocalFeedDegradation| {
+            Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .local_feed_degradation(degradation)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+
+        // The local receives only the last update of each price level in the interval, once the
+        // interval ends, while the exchange fills the order against the true market depth.
+        let mut hbt = build(LocalFeedDegradation::new(1).conflation_interval(1_000));
+        hbt.elapse(500).unwrap();
+        assert_eq!(hbt.depth(0).best_bid_tick(), INVALID_MIN);
+        hbt.submit_sell_order(0, 1, 99.0, 1.0, TimeInForce::IOC, OrdType::Market, true)
+            .unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_price_tick, 99);
+
+        hbt.elapse(1_000).unwrap();
+        assert_eq!(hbt.depth(0).best_bid_tick(), 99);
+        assert_eq!(hbt.depth(0).bid_qty_at_tick(99), 2.0);
+
+        // The local loses every feed event.
+        let mut hbt = build(LocalFeedDegradation::new(1).loss_prob(1.0));
+        hbt.elapse(20_000).unwrap();
+        assert_eq!(hbt.depth(0).best_bid_tick(), INVALID_MIN);
+        assert_eq!(hbt.depth(0).best_ask_tick(), INVALID_MAX);
+    }
+
+    #[test]
+    fn degrade_local_feed_after_restore() {
+        let events: Vec<_> = (0..200)
+            .map(|i| event(BUY_EVENT | DEPTH_EVENT, (i + 1) * 1_000, 99.0, (i % 7 + 1) as f64))
+            .collect();
+        let file_name = format!("degrade_local_feed_{}.npy", process::id());
+        let file_path = env::temp_dir().join(file_name);
+        write_npy(&mut File::create(&file_path).unwrap(), &events).unwrap();
+        let build = || {
+            Backtest::builder()
+                .add_asset(
+                    asset_builder(&[])
+                        .data(vec![DataSource::File(file_path.to_str().unwrap().to_string())])
+                        .local_feed_degradation(
+                            LocalFeedDegradation::new(1)
+                                .loss_prob(0.3)
+                                .outages(20_000, 3_000),
+                        )
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+        let run = |hbt: &mut Backtest<HashMapMarketDepth>| {
+            let mut bid_qty = Vec::new();
+            while hbt.elapse(1_000).unwrap() {
+                bid_qty.push(hbt.depth(0).bid_qty_at_tick(99));
+            }
+            bid_qty
+        };
+
+        let mut hbt = build();
+        for _ in 0..100 {
+            hbt.elapse(1_000).unwrap();
+        }
+        let mut checkpoint = Vec::new();
+        hbt.checkpoint(&mut checkpoint).unwrap();
+        let expected = run(&mut hbt);
+
+        // The restored backtest loads the file again, which is degraded in the same way.
+        let mut restored = build();
+        restored.restore(&mut checkpoint.as_slice()).unwrap();
+        let actual = run(&mut restored);
+        fs::remove_file(&file_path).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn degrade_in_memory_data_after_restore() {
+        let events: Vec<_> = (0..200)
+            .map(|i| event(BUY_EVENT | DEPTH_EVENT, (i + 1) * 1_000, 99.0, (i % 7 + 1) as f64))
+            .collect();
+        let data = [to_data(&events)];
+        let build = || {
+            Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .local_feed_degradation(
+                            LocalFeedDegradation::new(1)
+                                .loss_prob(0.3)
+                                .outages(20_000, 3_000),
+                        )
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+        let run = |hbt: &mut Backtest<HashMapMarketDepth>| {
+            let mut bid_qty = Vec::new();
+            while hbt.elapse(1_000).unwrap() {
+                bid_qty.push(hbt.depth(0).bid_qty_at_tick(99));
+            }
+            bid_qty
+        };
+
+        let mut hbt = build();
+        for _ in 0..100 {
+            hbt.elapse(1_000).unwrap();
+        }
+        let mut checkpoint = Vec::new();
+        hbt.checkpoint(&mut checkpoint).unwrap();
+        let expected = run(&mut hbt);
+
+        // The data is degraded in a copy, so the restored backtest degrades the original data
+        // again rather than degrading the already degraded data.
+        let mut restored = build();
+        restored.restore(&mut checkpoint.as_slice()).unwrap();
+        let actual = run(&mut restored);
+        assert_eq!(actual, expected);
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(data[0][i].local_ts, event.local_ts);
+            assert_eq!(data[0][i].ev, event.ev);
+        }
+    }
+}
@@ -67,6 +67,18 @@ impl EventSet {
         })
     }
 
+    /// Returns the timestamps of the event sets and the number of invalidated ones, which together
+    /// determine the next event.
+    pub fn cursor(&self) -> (Vec<i64>, usize) {
+        (self.timestamp.as_slice().to_vec(), self.invalid)
+    }
+
+    /// Restores the cursor returned by [`cursor`](Self::cursor).
+    pub fn set_cursor(&mut self, timestamp: &[i64], invalid: usize) {
+        self.timestamp.as_mut_slice().copy_from_slice(timestamp);
+        self.invalid = invalid;
+    }
+
     #[inline]
     fn update(&mut self, evst_no: usize, timestamp: i64) {
         let item = unsafe { self.timestamp.get_unchecked_mut(evst_no) };
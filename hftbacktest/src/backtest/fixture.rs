@@ -0,0 +1,156 @@
+use std::mem::size_of_val;
+
+use crate::{
+    backtest::{
+        assettype::LinearAsset,
+        data::{Data, DataPtr, DataSource},
+        models::{
+            CommonFees,
+            ConstantLatency,
+            L3FIFOQueueModel,
+            RiskAdverseQueueModel,
+            TradingValueFeeModel,
+        },
+        Backtest,
+        ExchangeKind,
+        L2AssetBuilder,
+        L3AssetBuilder,
+    },
+    depth::{ApplySnapshot, HashMapMarketDepth, L2MarketDepth, MarketDepth},
+    types::{
+        Event,
+        OrdType,
+        OrderRequest,
+        PositionSide,
+        Side,
+        TimeInForce,
+        EXCH_EVENT,
+        LOCAL_EVENT,
+    },
+};
+
+/// Returns an event that both the exchange and the local receive, with a feed latency of 5.
+pub(crate) fn event(ev: u64, exch_ts: i64, px: f64, qty: f64) -> Event {
+    Event {
+        ev: EXCH_EVENT | LOCAL_EVENT | ev,
+        exch_ts,
+        local_ts: exch_ts + 5,
+        px,
+        qty,
+        order_id: 0,
+        ival: 0,
+        fval: 0.0,
+    }
+}
+
+/// Returns a market-by-order event for the given order that both the exchange and the local
+/// receive, with a feed latency of 5.
+pub(crate) fn l3_event(ev: u64, exch_ts: i64, order_id: u64, px: f64, qty: f64) -> Event {
+    Event {
+        order_id,
+        ..event(ev, exch_ts, px, qty)
+    }
+}
+
+pub(crate) fn to_data(events: &[Event]) -> Data<Event> {
+    let mut data = unsafe { Data::from_data_ptr(DataPtr::new(size_of_val(events)), 0) };
+    for (i, event) in events.iter().enumerate() {
+        data[i] = event.clone();
+    }
+    data
+}
+
+/// Returns a GTC limit order request with the other options unset.
+pub(crate) fn limit_order(order_id: u64, side: Side, price: f64, qty: f64) -> OrderRequest {
+    OrderRequest {
+        order_id,
+        price,
+        qty,
+        side,
+        time_in_force: TimeInForce::GTC,
+        order_type: OrdType::Limit,
+        trigger: None,
+        expire_timestamp: 0,
+        display_qty: None,
+        reduce_only: false,
+        position_side: PositionSide::Both,
+    }
+}
+
+/// Returns the builder for a linear asset with a tick size and a lot size of 1 and a constant
+/// order latency of 10 in each direction, which trades on the [`NoPartialFillExchange`].
+///
+/// [`NoPartialFillExchange`]: crate::backtest::proc::NoPartialFillExchange
+pub(crate) fn asset_builder(
+    data: &[Data<Event>],
+) -> L2AssetBuilder<
+    ConstantLatency,
+    LinearAsset,
+    RiskAdverseQueueModel<HashMapMarketDepth>,
+    HashMapMarketDepth,
+    TradingValueFeeModel<CommonFees>,
+> {
+    asset_builder_with_depth(data, || HashMapMarketDepth::new(1.0, 1.0))
+}
+
+/// Returns the builder of [`asset_builder`] with the market depth built by the given function.
+pub(crate) fn asset_builder_with_depth<MD>(
+    data: &[Data<Event>],
+    depth: fn() -> MD,
+) -> L2AssetBuilder<
+    ConstantLatency,
+    LinearAsset,
+    RiskAdverseQueueModel<MD>,
+    MD,
+    TradingValueFeeModel<CommonFees>,
+>
+where
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot + 'static,
+{
+    L2AssetBuilder::new()
+        .data(
+            data.iter()
+                .map(|data| DataSource::Data(data.clone()))
+                .collect(),
+        )
+        .latency_model(ConstantLatency::new(10, 10))
+        .asset_type(LinearAsset::new(1.0))
+        .fee_model(TradingValueFeeModel::new(CommonFees::new(-0.0001, 0.0005)))
+        .exchange(ExchangeKind::NoPartialFillExchange)
+        .queue_model(RiskAdverseQueueModel::new())
+        .depth(depth)
+}
+
+pub(crate) fn build_backtest(data: &[Data<Event>]) -> Backtest<HashMapMarketDepth> {
+    Backtest::builder()
+        .add_asset(asset_builder(data).build().unwrap())
+        .build()
+        .unwrap()
+}
+
+/// Returns the builder for a Level-3 linear asset with the same settings as [`asset_builder`],
+/// which trades on the [`L3NoPartialFillExchange`].
+///
+/// [`L3NoPartialFillExchange`]: crate::backtest::proc::L3NoPartialFillExchange
+pub(crate) fn l3_asset_builder(
+    data: &[Data<Event>],
+) -> L3AssetBuilder<
+    ConstantLatency,
+    LinearAsset,
+    L3FIFOQueueModel,
+    HashMapMarketDepth,
+    TradingValueFeeModel<CommonFees>,
+> {
+    L3AssetBuilder::new()
+        .data(
+            data.iter()
+                .map(|data| DataSource::Data(data.clone()))
+                .collect(),
+        )
+        .latency_model(ConstantLatency::new(10, 10))
+        .asset_type(LinearAsset::new(1.0))
+        .fee_model(TradingValueFeeModel::new(CommonFees::new(-0.0001, 0.0005)))
+        .exchange(ExchangeKind::NoPartialFillExchange)
+        .queue_model(L3FIFOQueueModel::new())
+        .depth(|| HashMapMarketDepth::new(1.0, 1.0))
+}
@@ -1,38 +1,61 @@
-use std::{collections::HashMap, io::Error as IoError, marker::PhantomData};
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind, Read, Write},
+    marker::PhantomData,
+};
 
 pub use data::DataSource;
 use data::Reader;
 use models::FeeModel;
+pub use observer::BacktestObserver;
+pub use sweep::ParamSweep;
 use thiserror::Error;
 
 pub use crate::backtest::{
     models::L3QueueModel,
-    proc::{L3Local, L3NoPartialFillExchange},
+    proc::{L3Local, L3NoPartialFillExchange, L3PartialFillExchange},
 };
 use crate::{
     backtest::{
         assettype::AssetType,
-        data::FeedLatencyAdjustment,
+        checkpoint::BacktestCheckpoint,
+        data::{FeedLatencyAdjustment, LocalFeedDegradation},
         evs::{EventIntentKind, EventSet},
-        models::{LatencyModel, QueueModel},
+        models::{
+            FaultInjector,
+            LatencyModel,
+            MarginModel,
+            NoMarginModel,
+            QueueModel,
+            RateLimitModel,
+        },
         order::OrderBus,
         proc::{Local, LocalProcessor, NoPartialFillExchange, PartialFillExchange, Processor},
         state::State,
     },
-    depth::{HashMapMarketDepth, L2MarketDepth, L3MarketDepth, MarketDepth},
+    depth::{
+        ApplySnapshot,
+        HashMapMarketDepth,
+        L2MarketDepth,
+        L3MarketDepth,
+        MarketDepth,
+        INVALID_MAX,
+        INVALID_MIN,
+    },
     prelude::{
         Bot,
         OrdType,
         Order,
         OrderId,
         OrderRequest,
+        RejectReason,
         Side,
         StateValues,
         TimeInForce,
         WaitOrderResponse,
         UNTIL_END_OF_DATA,
     },
-    types::{BuildError, Event},
+    types::{BuildError, Event, Unsupported},
 };
 
 /// Provides asset types.
@@ -55,6 +78,15 @@ pub mod recorder;
 pub mod data;
 mod evs;
 
+mod checkpoint;
+
+mod observer;
+
+mod sweep;
+
+#[cfg(test)]
+mod fixture;
+
 /// Errors that can occur during backtesting.
 #[derive(Error, Debug)]
 pub enum BacktestError {
@@ -72,6 +104,14 @@ pub enum BacktestError {
     EndOfData,
     #[error("data error: {0:?}")]
     DataError(#[from] IoError),
+    #[error("unsupported")]
+    Unsupported,
+}
+
+impl From<Unsupported> for BacktestError {
+    fn from(_: Unsupported) -> Self {
+        BacktestError::Unsupported
+    }
 }
 
 /// Backtesting Asset
@@ -94,7 +134,7 @@ impl<L, E> Asset<L, E> {
     pub fn l2_builder<LM, AT, QM, MD, FM>() -> L2AssetBuilder<LM, AT, QM, MD, FM>
     where
         AT: AssetType + Clone + 'static,
-        MD: MarketDepth + L2MarketDepth + 'static,
+        MD: MarketDepth + L2MarketDepth + ApplySnapshot + 'static,
         QM: QueueModel<MD> + 'static,
         LM: LatencyModel + Clone + 'static,
         FM: FeeModel + Clone + 'static,
@@ -106,7 +146,7 @@ impl<L, E> Asset<L, E> {
     pub fn l3_builder<LM, AT, QM, MD, FM>() -> L3AssetBuilder<LM, AT, QM, MD, FM>
     where
         AT: AssetType + Clone + 'static,
-        MD: MarketDepth + L3MarketDepth + 'static,
+        MD: MarketDepth + L3MarketDepth + ApplySnapshot + 'static,
         QM: L3QueueModel<MD> + 'static,
         LM: LatencyModel + Clone + 'static,
         FM: FeeModel + Clone + 'static,
@@ -118,30 +158,190 @@ impl<L, E> Asset<L, E> {
 
 /// Exchange model kind.
 pub enum ExchangeKind {
-    /// Uses [NoPartialFillExchange](`NoPartialFillExchange`).
+    /// Uses [NoPartialFillExchange](`NoPartialFillExchange`), or
+    /// [L3NoPartialFillExchange](`L3NoPartialFillExchange`) for a Level-3 asset.
     NoPartialFillExchange,
-    /// Uses [PartialFillExchange](`PartialFillExchange`).
+    /// Uses [PartialFillExchange](`PartialFillExchange`), or
+    /// [L3PartialFillExchange](`L3PartialFillExchange`) for a Level-3 asset.
     PartialFillExchange,
 }
 
+/// Self-trade prevention mode, which determines what the exchange does when an order would match
+/// a resting order of the same account on the opposite side.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SelfTradePrevention {
+    /// Self-trade prevention is not applied.
+    None,
+    /// Expires the incoming order. The resting orders remain.
+    CancelNewest,
+    /// Cancels the resting orders that the incoming order would match, and then the incoming
+    /// order is matched as usual.
+    CancelOldest,
+    /// Cancels both the resting orders and the incoming order.
+    CancelBoth,
+    /// Cancels the smaller of the incoming order and each resting order it would match, and
+    /// decrements the larger by the smaller's quantity. The incoming order is expired if nothing
+    /// is left.
+    DecrementAndCancel,
+}
+
+/// Determines whether and how long the liquidity consumed by the taker fills is removed from the
+/// market depth that the exchange sees. Since the market depth is replayed from the feed, the
+/// consumed liquidity would otherwise remain available to the subsequent orders.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LiquidityImpact {
+    /// The consumed liquidity is not removed.
+    None,
+    /// The consumed liquidity is removed until the next feed update at the price level, which
+    /// then restores the quantity in the feed.
+    Resync,
+    /// The consumed liquidity is removed, and at each feed update at the price level, the
+    /// remaining consumed quantity is multiplied by the factor and subtracted from the quantity in
+    /// the feed. A factor of `0.0` is the same as [`LiquidityImpact::Resync`].
+    Decay(f64),
+}
+
+/// Limits how far a market order can sweep the market depth away from the best price on the
+/// opposite side at the time the exchange receives it, as exchanges protect market orders from
+/// excessive slippage. The remaining quantity that cannot be filled within the band expires.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PriceBand {
+    /// The market order can sweep the entire market depth.
+    None,
+    /// The band is the given percentage of the best price; for example, `5.0` means 5%.
+    Percentage(f64),
+    /// The band is the given number of ticks from the best price.
+    Ticks(i64),
+}
+
+impl PriceBand {
+    /// Returns the highest price in ticks at which a buy market order can be filled.
+    pub(crate) fn buy_limit_tick(&self, best_ask_tick: i64) -> i64 {
+        match *self {
+            PriceBand::None => INVALID_MAX,
+            PriceBand::Percentage(pct) => {
+                best_ask_tick.saturating_add((best_ask_tick as f64 * pct / 100.0).floor() as i64)
+            }
+            PriceBand::Ticks(ticks) => best_ask_tick.saturating_add(ticks),
+        }
+    }
+
+    /// Returns the lowest price in ticks at which a sell market order can be filled.
+    pub(crate) fn sell_limit_tick(&self, best_bid_tick: i64) -> i64 {
+        match *self {
+            PriceBand::None => INVALID_MIN,
+            PriceBand::Percentage(pct) => {
+                best_bid_tick.saturating_sub((best_bid_tick as f64 * pct / 100.0).floor() as i64)
+            }
+            PriceBand::Ticks(ticks) => best_bid_tick.saturating_sub(ticks),
+        }
+    }
+}
+
+/// The trading rules of an instrument that are enforced on new and modified orders, mirroring the
+/// exchange filters such as Binance's `PRICE_FILTER`, `LOT_SIZE`, `MIN_NOTIONAL` and
+/// `PERCENT_PRICE`. The orders that do not conform are rejected with the corresponding
+/// [`RejectReason`]. A rule set to `0.0` is not applied, which is the default for all rules.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct InstrumentRules {
+    /// The price of an order should be a multiple of the tick size.
+    pub tick_size: f64,
+    pub min_price: f64,
+    pub max_price: f64,
+    /// The quantity of an order should be a multiple of the lot size.
+    pub lot_size: f64,
+    pub min_qty: f64,
+    pub max_qty: f64,
+    /// The minimum value of price times quantity. A market order is valued at the mid price.
+    pub min_notional: f64,
+    /// The price of an order should not exceed the mid price multiplied by this.
+    pub multiplier_up: f64,
+    /// The price of an order should not fall below the mid price multiplied by this.
+    pub multiplier_down: f64,
+}
+
+impl InstrumentRules {
+    fn is_multiple(value: f64, step: f64) -> bool {
+        let n = value / step;
+        (n - n.round()).abs() < 1e-6
+    }
+
+    /// Checks the order's price and quantity against the rules. The percent price filter and the
+    /// market order's notional value refer to the mid price of the market depth, and are not
+    /// checked if the market depth is empty.
+    pub(crate) fn check<MD: MarketDepth>(
+        &self,
+        order_type: OrdType,
+        price: f64,
+        qty: f64,
+        depth: &MD,
+    ) -> Result<(), RejectReason> {
+        let mid_price = (depth.best_bid() + depth.best_ask()) / 2.0;
+        // Only the orders with a limit price are subject to the price filters.
+        let has_price = matches!(
+            order_type,
+            OrdType::Limit | OrdType::StopLimit | OrdType::TakeProfitLimit
+        );
+        if has_price {
+            if (self.tick_size > 0.0 && !Self::is_multiple(price, self.tick_size))
+                || (self.min_price > 0.0 && price < self.min_price)
+                || (self.max_price > 0.0 && price > self.max_price)
+            {
+                return Err(RejectReason::PriceFilter);
+            }
+            if mid_price.is_finite()
+                && ((self.multiplier_up > 0.0 && price > mid_price * self.multiplier_up)
+                    || (self.multiplier_down > 0.0 && price < mid_price * self.multiplier_down))
+            {
+                return Err(RejectReason::PercentPrice);
+            }
+        }
+
+        if (self.lot_size > 0.0 && !Self::is_multiple(qty, self.lot_size))
+            || (self.min_qty > 0.0 && qty < self.min_qty)
+            || (self.max_qty > 0.0 && qty > self.max_qty)
+        {
+            return Err(RejectReason::LotSize);
+        }
+
+        if self.min_notional > 0.0 {
+            let notional_price = if has_price { price } else { mid_price };
+            if notional_price.is_finite() && notional_price * qty < self.min_notional {
+                return Err(RejectReason::MinNotional);
+            }
+        }
+        Ok(())
+    }
+}
+
 /// A level-2 asset builder.
-pub struct L2AssetBuilder<LM, AT, QM, MD, FM> {
+pub struct L2AssetBuilder<LM, AT, QM, MD, FM, MM = NoMarginModel> {
     latency_model: Option<LM>,
     asset_type: Option<AT>,
     data: Vec<DataSource<Event>>,
     parallel_load: bool,
     latency_offset: i64,
+    local_feed_degradation: Option<LocalFeedDegradation>,
     fee_model: Option<FM>,
     exch_kind: ExchangeKind,
     last_trades_cap: usize,
     queue_model: Option<QM>,
     depth_builder: Option<Box<dyn Fn() -> MD>>,
+    observer: Option<Box<dyn BacktestObserver<MD>>>,
+    margin_model: MM,
+    rate_limit: Option<Box<dyn RateLimitModel>>,
+    self_trade_prevention: SelfTradePrevention,
+    liquidity_impact: LiquidityImpact,
+    price_band: PriceBand,
+    instrument_rules: InstrumentRules,
+    out_of_order_requests: bool,
+    fault_injector: Option<FaultInjector>,
 }
 
-impl<LM, AT, QM, MD, FM> L2AssetBuilder<LM, AT, QM, MD, FM>
+impl<LM, AT, QM, MD, FM> L2AssetBuilder<LM, AT, QM, MD, FM, NoMarginModel>
 where
     AT: AssetType + Clone + 'static,
-    MD: MarketDepth + L2MarketDepth + 'static,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot + 'static,
     QM: QueueModel<MD> + 'static,
     LM: LatencyModel + Clone + 'static,
     FM: FeeModel + Clone + 'static,
@@ -154,14 +354,34 @@ where
             data: vec![],
             parallel_load: false,
             latency_offset: 0,
+            local_feed_degradation: None,
             fee_model: None,
             exch_kind: ExchangeKind::NoPartialFillExchange,
             last_trades_cap: 0,
             queue_model: None,
             depth_builder: None,
+            observer: None,
+            margin_model: NoMarginModel,
+            rate_limit: None,
+            self_trade_prevention: SelfTradePrevention::None,
+            liquidity_impact: LiquidityImpact::None,
+            price_band: PriceBand::None,
+            instrument_rules: InstrumentRules::default(),
+            out_of_order_requests: false,
+            fault_injector: None,
         }
     }
+}
 
+impl<LM, AT, QM, MD, FM, MM> L2AssetBuilder<LM, AT, QM, MD, FM, MM>
+where
+    AT: AssetType + Clone + 'static,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot + 'static,
+    QM: QueueModel<MD> + 'static,
+    LM: LatencyModel + Clone + 'static,
+    FM: FeeModel + Clone + 'static,
+    MM: MarginModel + 'static,
+{
     /// Sets the feed data.
     pub fn data(self, data: Vec<DataSource<Event>>) -> Self {
         Self { data, ..self }
@@ -187,6 +407,15 @@ where
         }
     }
 
+    /// Sets a [`LocalFeedDegradation`] that makes the local miss, or receive late, some of the
+    /// feed events that the exchange receives. It is applied after the latency offset.
+    pub fn local_feed_degradation(self, local_feed_degradation: LocalFeedDegradation) -> Self {
+        Self {
+            local_feed_degradation: Some(local_feed_degradation),
+            ..self
+        }
+    }
+
     /// Sets a latency model.
     pub fn latency_model(self, latency_model: LM) -> Self {
         Self {
@@ -244,24 +473,155 @@ where
         }
     }
 
+    /// Sets a [`BacktestObserver`] that gets notified of the order responses and the market feed
+    /// received by the local.
+    pub fn observer<Observer>(self, observer: Observer) -> Self
+    where
+        Observer: BacktestObserver<MD> + 'static,
+    {
+        Self {
+            observer: Some(Box::new(observer)),
+            ..self
+        }
+    }
+
+    /// Sets a margin model, which the exchange uses to reject orders that exceed the available
+    /// margin and to liquidate the position when the maintenance margin is breached. The default
+    /// value is [`NoMarginModel`], which imposes no margin requirements.
+    pub fn margin_model<Margin>(
+        self,
+        margin_model: Margin,
+    ) -> L2AssetBuilder<LM, AT, QM, MD, FM, Margin>
+    where
+        Margin: MarginModel + 'static,
+    {
+        L2AssetBuilder {
+            latency_model: self.latency_model,
+            asset_type: self.asset_type,
+            data: self.data,
+            parallel_load: self.parallel_load,
+            latency_offset: self.latency_offset,
+            local_feed_degradation: self.local_feed_degradation,
+            fee_model: self.fee_model,
+            exch_kind: self.exch_kind,
+            last_trades_cap: self.last_trades_cap,
+            queue_model: self.queue_model,
+            depth_builder: self.depth_builder,
+            observer: self.observer,
+            margin_model,
+            rate_limit: self.rate_limit,
+            self_trade_prevention: self.self_trade_prevention,
+            liquidity_impact: self.liquidity_impact,
+            price_band: self.price_band,
+            instrument_rules: self.instrument_rules,
+            out_of_order_requests: self.out_of_order_requests,
+            fault_injector: self.fault_injector,
+        }
+    }
+
+    /// Sets a [`RateLimitModel`] that makes the exchange reject the requests exceeding the request
+    /// rate limits or the maximum number of open orders. By default, no limits are imposed.
+    pub fn rate_limit<RateLimit>(self, rate_limit: RateLimit) -> Self
+    where
+        RateLimit: RateLimitModel + 'static,
+    {
+        Self {
+            rate_limit: Some(Box::new(rate_limit)),
+            ..self
+        }
+    }
+
+    /// Sets the self-trade prevention mode applied by the exchange. The default value is
+    /// [`SelfTradePrevention::None`].
+    pub fn self_trade_prevention(self, self_trade_prevention: SelfTradePrevention) -> Self {
+        Self {
+            self_trade_prevention,
+            ..self
+        }
+    }
+
+    /// Sets how the liquidity consumed by the taker fills impacts the exchange-side market depth.
+    /// This is only applied by [`ExchangeKind::PartialFillExchange`]. The default value is
+    /// [`LiquidityImpact::None`].
+    pub fn liquidity_impact(self, liquidity_impact: LiquidityImpact) -> Self {
+        Self {
+            liquidity_impact,
+            ..self
+        }
+    }
+
+    /// Sets the [`PriceBand`] that limits how far a market order can sweep the market depth from
+    /// the best price. This requires [`ExchangeKind::PartialFillExchange`], as
+    /// [`ExchangeKind::NoPartialFillExchange`] fills a market order entirely at the best price, and
+    /// building the asset fails otherwise. The default value is [`PriceBand::None`].
+    pub fn price_band(self, price_band: PriceBand) -> Self {
+        Self { price_band, ..self }
+    }
+
+    /// Sets the [`InstrumentRules`] that both the local and the exchange check the orders against.
+    /// By default, no rules are applied.
+    pub fn instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
+        }
+    }
+
+    /// Sets whether the requests reach the exchange purely at the arrival timestamps given by the
+    /// order latency model, so that a request sent later can overtake the earlier ones, as REST
+    /// requests can. The local can then cancel an order while its new order request is still in
+    /// flight, and the cancel is rejected with [`RejectReason::UnknownOrder`], which is set on the
+    /// order, if it reaches the exchange before the new order does. By default, the requests reach the exchange in the
+    /// order in which they are sent.
+    pub fn out_of_order_requests(self, out_of_order_requests: bool) -> Self {
+        Self {
+            out_of_order_requests,
+            ..self
+        }
+    }
+
+    /// Sets a [`FaultInjector`] that rejects requests, drops responses and delays the order flow
+    /// between the local and the exchange. By default, no faults are injected.
+    pub fn fault_injector(self, fault_injector: FaultInjector) -> Self {
+        Self {
+            fault_injector: Some(fault_injector),
+            ..self
+        }
+    }
+
     /// Builds an `Asset`.
     pub fn build(self) -> Result<Asset<dyn LocalProcessor<MD>, dyn Processor>, BuildError> {
-        let reader = if self.latency_offset == 0 {
-            Reader::builder()
-                .parallel_load(self.parallel_load)
-                .data(self.data)
-                .build()
-                .map_err(|err| BuildError::Error(err.into()))?
-        } else {
-            Reader::builder()
-                .parallel_load(self.parallel_load)
-                .data(self.data)
-                .preprocessor(FeedLatencyAdjustment::new(self.latency_offset))
-                .build()
-                .map_err(|err| BuildError::Error(err.into()))?
+        if matches!(self.exch_kind, ExchangeKind::NoPartialFillExchange)
+            && self.price_band != PriceBand::None
+        {
+            return Err(BuildError::InvalidArgument(
+                "`price_band` requires `ExchangeKind::PartialFillExchange`",
+            ));
+        }
+
+        let reader_builder = Reader::builder()
+            .parallel_load(self.parallel_load)
+            .data(self.data);
+        let reader_builder = match (self.latency_offset, self.local_feed_degradation) {
+            (0, None) => reader_builder,
+            (0, Some(degradation)) => reader_builder.preprocessor(degradation),
+            (latency_offset, None) => {
+                reader_builder.preprocessor(FeedLatencyAdjustment::new(latency_offset))
+            }
+            (latency_offset, Some(degradation)) => reader_builder.preprocessor((
+                FeedLatencyAdjustment::new(latency_offset),
+                degradation,
+            )),
         };
+        let reader = reader_builder
+            .build()
+            .map_err(|err| BuildError::Error(err.into()))?;
 
-        let ob_local_to_exch = OrderBus::new();
+        let ob_local_to_exch = if self.out_of_order_requests {
+            OrderBus::out_of_order()
+        } else {
+            OrderBus::new()
+        };
         let ob_exch_to_local = OrderBus::new();
 
         let create_depth = self
@@ -281,7 +641,7 @@ where
             .clone()
             .ok_or(BuildError::BuilderIncomplete("fee_model"))?;
 
-        let local = Local::new(
+        let mut local = Local::new(
             reader.clone(),
             create_depth(),
             State::new(asset_type, fee_model),
@@ -289,7 +649,15 @@ where
             self.last_trades_cap,
             ob_local_to_exch.clone(),
             ob_exch_to_local.clone(),
-        );
+        )
+        .with_instrument_rules(self.instrument_rules)
+        .with_out_of_order_requests(self.out_of_order_requests);
+        if let Some(observer) = self.observer {
+            local = local.with_observer(observer);
+        }
+        if let Some(fault_injector) = self.fault_injector {
+            local = local.with_fault_injector(fault_injector);
+        }
 
         let order_latency = self
             .latency_model
@@ -309,15 +677,20 @@ where
 
         match self.exch_kind {
             ExchangeKind::NoPartialFillExchange => {
-                let exch = NoPartialFillExchange::new(
+                let mut exch = NoPartialFillExchange::new(
                     reader.clone(),
                     create_depth(),
-                    State::new(asset_type, fee_model),
+                    State::with_margin_model(asset_type, fee_model, self.margin_model),
                     order_latency,
                     queue_model,
                     ob_exch_to_local,
                     ob_local_to_exch,
-                );
+                )
+                .with_self_trade_prevention(self.self_trade_prevention)
+                .with_instrument_rules(self.instrument_rules);
+                if let Some(rate_limit) = self.rate_limit {
+                    exch = exch.with_rate_limit(rate_limit);
+                }
 
                 Ok(Asset {
                     local: Box::new(local),
@@ -325,15 +698,22 @@ where
                 })
             }
             ExchangeKind::PartialFillExchange => {
-                let exch = PartialFillExchange::new(
+                let mut exch = PartialFillExchange::new(
                     reader.clone(),
                     create_depth(),
-                    State::new(asset_type, fee_model),
+                    State::with_margin_model(asset_type, fee_model, self.margin_model),
                     order_latency,
                     queue_model,
                     ob_exch_to_local,
                     ob_local_to_exch,
-                );
+                )
+                .with_self_trade_prevention(self.self_trade_prevention)
+                .with_liquidity_impact(self.liquidity_impact)
+                .with_price_band(self.price_band)
+                .with_instrument_rules(self.instrument_rules);
+                if let Some(rate_limit) = self.rate_limit {
+                    exch = exch.with_rate_limit(rate_limit);
+                }
 
                 Ok(Asset {
                     local: Box::new(local),
@@ -344,10 +724,10 @@ where
     }
 }
 
-impl<LM, AT, QM, MD, FM> Default for L2AssetBuilder<LM, AT, QM, MD, FM>
+impl<LM, AT, QM, MD, FM> Default for L2AssetBuilder<LM, AT, QM, MD, FM, NoMarginModel>
 where
     AT: AssetType + Clone + 'static,
-    MD: MarketDepth + L2MarketDepth + 'static,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot + 'static,
     QM: QueueModel<MD> + 'static,
     LM: LatencyModel + Clone + 'static,
     FM: FeeModel + Clone + 'static,
@@ -358,23 +738,31 @@ where
 }
 
 /// A level-3 asset builder.
-pub struct L3AssetBuilder<LM, AT, QM, MD, FM> {
+pub struct L3AssetBuilder<LM, AT, QM, MD, FM, MM = NoMarginModel> {
     latency_model: Option<LM>,
     asset_type: Option<AT>,
     data: Vec<DataSource<Event>>,
     parallel_load: bool,
     latency_offset: i64,
+    local_feed_degradation: Option<LocalFeedDegradation>,
     fee_model: Option<FM>,
     exch_kind: ExchangeKind,
     last_trades_cap: usize,
     queue_model: Option<QM>,
     depth_builder: Option<Box<dyn Fn() -> MD>>,
+    observer: Option<Box<dyn BacktestObserver<MD>>>,
+    margin_model: MM,
+    rate_limit: Option<Box<dyn RateLimitModel>>,
+    price_band: PriceBand,
+    instrument_rules: InstrumentRules,
+    out_of_order_requests: bool,
+    fault_injector: Option<FaultInjector>,
 }
 
-impl<LM, AT, QM, MD, FM> L3AssetBuilder<LM, AT, QM, MD, FM>
+impl<LM, AT, QM, MD, FM> L3AssetBuilder<LM, AT, QM, MD, FM, NoMarginModel>
 where
     AT: AssetType + Clone + 'static,
-    MD: MarketDepth + L3MarketDepth + 'static,
+    MD: MarketDepth + L3MarketDepth + ApplySnapshot + 'static,
     QM: L3QueueModel<MD> + 'static,
     LM: LatencyModel + Clone + 'static,
     FM: FeeModel + Clone + 'static,
@@ -388,14 +776,33 @@ where
             data: vec![],
             parallel_load: false,
             latency_offset: 0,
+            local_feed_degradation: None,
             fee_model: None,
             exch_kind: ExchangeKind::NoPartialFillExchange,
             last_trades_cap: 0,
             queue_model: None,
             depth_builder: None,
+            observer: None,
+            margin_model: NoMarginModel,
+            rate_limit: None,
+            price_band: PriceBand::None,
+            instrument_rules: InstrumentRules::default(),
+            out_of_order_requests: false,
+            fault_injector: None,
         }
     }
+}
 
+impl<LM, AT, QM, MD, FM, MM> L3AssetBuilder<LM, AT, QM, MD, FM, MM>
+where
+    AT: AssetType + Clone + 'static,
+    MD: MarketDepth + L3MarketDepth + ApplySnapshot + 'static,
+    QM: L3QueueModel<MD> + 'static,
+    LM: LatencyModel + Clone + 'static,
+    FM: FeeModel + Clone + 'static,
+    MM: MarginModel + 'static,
+    BacktestError: From<<MD as L3MarketDepth>::Error>,
+{
     /// Sets the feed data.
     pub fn data(self, data: Vec<DataSource<Event>>) -> Self {
         Self { data, ..self }
@@ -421,6 +828,15 @@ where
         }
     }
 
+    /// Sets a [`LocalFeedDegradation`] that makes the local miss, or receive late, some of the
+    /// feed events that the exchange receives. It is applied after the latency offset.
+    pub fn local_feed_degradation(self, local_feed_degradation: LocalFeedDegradation) -> Self {
+        Self {
+            local_feed_degradation: Some(local_feed_degradation),
+            ..self
+        }
+    }
+
     /// Sets a latency model.
     pub fn latency_model(self, latency_model: LM) -> Self {
         Self {
@@ -478,24 +894,134 @@ where
         }
     }
 
+    /// Sets a [`BacktestObserver`] that gets notified of the order responses and the market feed
+    /// received by the local.
+    pub fn observer<Observer>(self, observer: Observer) -> Self
+    where
+        Observer: BacktestObserver<MD> + 'static,
+    {
+        Self {
+            observer: Some(Box::new(observer)),
+            ..self
+        }
+    }
+
+    /// Sets a margin model, which the exchange uses to reject orders that exceed the available
+    /// margin and to liquidate the position when the maintenance margin is breached. The default
+    /// value is [`NoMarginModel`], which imposes no margin requirements.
+    pub fn margin_model<Margin>(
+        self,
+        margin_model: Margin,
+    ) -> L3AssetBuilder<LM, AT, QM, MD, FM, Margin>
+    where
+        Margin: MarginModel + 'static,
+    {
+        L3AssetBuilder {
+            latency_model: self.latency_model,
+            asset_type: self.asset_type,
+            data: self.data,
+            parallel_load: self.parallel_load,
+            latency_offset: self.latency_offset,
+            local_feed_degradation: self.local_feed_degradation,
+            fee_model: self.fee_model,
+            exch_kind: self.exch_kind,
+            last_trades_cap: self.last_trades_cap,
+            queue_model: self.queue_model,
+            depth_builder: self.depth_builder,
+            observer: self.observer,
+            margin_model,
+            rate_limit: self.rate_limit,
+            price_band: self.price_band,
+            instrument_rules: self.instrument_rules,
+            out_of_order_requests: self.out_of_order_requests,
+            fault_injector: self.fault_injector,
+        }
+    }
+
+    /// Sets a [`RateLimitModel`] that makes the exchange reject the requests exceeding the request
+    /// rate limits or the maximum number of open orders. By default, no limits are imposed.
+    pub fn rate_limit<RateLimit>(self, rate_limit: RateLimit) -> Self
+    where
+        RateLimit: RateLimitModel + 'static,
+    {
+        Self {
+            rate_limit: Some(Box::new(rate_limit)),
+            ..self
+        }
+    }
+
+    /// Sets the [`PriceBand`] that limits how far a market order can sweep the order book from the
+    /// best price. This requires [`ExchangeKind::PartialFillExchange`], as
+    /// [`ExchangeKind::NoPartialFillExchange`] fills a market order entirely at the best price, and
+    /// building the asset fails otherwise. The default value is [`PriceBand::None`].
+    pub fn price_band(self, price_band: PriceBand) -> Self {
+        Self { price_band, ..self }
+    }
+
+    /// Sets the [`InstrumentRules`] that both the local and the exchange check the orders against.
+    /// By default, no rules are applied.
+    pub fn instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
+        }
+    }
+
+    /// Sets whether the requests reach the exchange purely at the arrival timestamps given by the
+    /// order latency model, so that a request sent later can overtake the earlier ones, as REST
+    /// requests can. The local can then cancel an order while its new order request is still in
+    /// flight, and the cancel is rejected with [`RejectReason::UnknownOrder`], which is set on the
+    /// order, if it reaches the exchange before the new order does. By default, the requests reach the exchange in the
+    /// order in which they are sent.
+    pub fn out_of_order_requests(self, out_of_order_requests: bool) -> Self {
+        Self {
+            out_of_order_requests,
+            ..self
+        }
+    }
+
+    /// Sets a [`FaultInjector`] that rejects requests, drops responses and delays the order flow
+    /// between the local and the exchange. By default, no faults are injected.
+    pub fn fault_injector(self, fault_injector: FaultInjector) -> Self {
+        Self {
+            fault_injector: Some(fault_injector),
+            ..self
+        }
+    }
+
     /// Builds an `Asset`.
     pub fn build(self) -> Result<Asset<dyn LocalProcessor<MD>, dyn Processor>, BuildError> {
-        let reader = if self.latency_offset == 0 {
-            Reader::builder()
-                .parallel_load(self.parallel_load)
-                .data(self.data)
-                .build()
-                .map_err(|err| BuildError::Error(err.into()))?
-        } else {
-            Reader::builder()
-                .parallel_load(self.parallel_load)
-                .data(self.data)
-                .preprocessor(FeedLatencyAdjustment::new(self.latency_offset))
-                .build()
-                .map_err(|err| BuildError::Error(err.into()))?
+        if matches!(self.exch_kind, ExchangeKind::NoPartialFillExchange)
+            && self.price_band != PriceBand::None
+        {
+            return Err(BuildError::InvalidArgument(
+                "`price_band` requires `ExchangeKind::PartialFillExchange`",
+            ));
+        }
+
+        let reader_builder = Reader::builder()
+            .parallel_load(self.parallel_load)
+            .data(self.data);
+        let reader_builder = match (self.latency_offset, self.local_feed_degradation) {
+            (0, None) => reader_builder,
+            (0, Some(degradation)) => reader_builder.preprocessor(degradation),
+            (latency_offset, None) => {
+                reader_builder.preprocessor(FeedLatencyAdjustment::new(latency_offset))
+            }
+            (latency_offset, Some(degradation)) => reader_builder.preprocessor((
+                FeedLatencyAdjustment::new(latency_offset),
+                degradation,
+            )),
         };
+        let reader = reader_builder
+            .build()
+            .map_err(|err| BuildError::Error(err.into()))?;
 
-        let ob_local_to_exch = OrderBus::new();
+        let ob_local_to_exch = if self.out_of_order_requests {
+            OrderBus::out_of_order()
+        } else {
+            OrderBus::new()
+        };
         let ob_exch_to_local = OrderBus::new();
 
         let create_depth = self
@@ -515,7 +1041,7 @@ where
             .clone()
             .ok_or(BuildError::BuilderIncomplete("fee_model"))?;
 
-        let local = L3Local::new(
+        let mut local = L3Local::new(
             reader.clone(),
             create_depth(),
             State::new(asset_type, fee_model),
@@ -523,7 +1049,15 @@ where
             self.last_trades_cap,
             ob_local_to_exch.clone(),
             ob_exch_to_local.clone(),
-        );
+        )
+        .with_instrument_rules(self.instrument_rules)
+        .with_out_of_order_requests(self.out_of_order_requests);
+        if let Some(observer) = self.observer {
+            local = local.with_observer(observer);
+        }
+        if let Some(fault_injector) = self.fault_injector {
+            local = local.with_fault_injector(fault_injector);
+        }
 
         let order_latency = self
             .latency_model
@@ -543,15 +1077,19 @@ where
 
         match self.exch_kind {
             ExchangeKind::NoPartialFillExchange => {
-                let exch = L3NoPartialFillExchange::new(
+                let mut exch = L3NoPartialFillExchange::new(
                     reader.clone(),
                     create_depth(),
-                    State::new(asset_type, fee_model),
+                    State::with_margin_model(asset_type, fee_model, self.margin_model),
                     order_latency,
                     queue_model,
                     ob_exch_to_local,
                     ob_local_to_exch,
-                );
+                )
+                .with_instrument_rules(self.instrument_rules);
+                if let Some(rate_limit) = self.rate_limit {
+                    exch = exch.with_rate_limit(rate_limit);
+                }
 
                 Ok(Asset {
                     local: Box::new(local),
@@ -559,16 +1097,34 @@ where
                 })
             }
             ExchangeKind::PartialFillExchange => {
-                unimplemented!();
+                let mut exch = L3PartialFillExchange::new(
+                    reader.clone(),
+                    create_depth(),
+                    State::with_margin_model(asset_type, fee_model, self.margin_model),
+                    order_latency,
+                    queue_model,
+                    ob_exch_to_local,
+                    ob_local_to_exch,
+                )
+                .with_price_band(self.price_band)
+                .with_instrument_rules(self.instrument_rules);
+                if let Some(rate_limit) = self.rate_limit {
+                    exch = exch.with_rate_limit(rate_limit);
+                }
+
+                Ok(Asset {
+                    local: Box::new(local),
+                    exch: Box::new(exch),
+                })
             }
         }
     }
 }
 
-impl<LM, AT, QM, MD, FM> Default for L3AssetBuilder<LM, AT, QM, MD, FM>
+impl<LM, AT, QM, MD, FM> Default for L3AssetBuilder<LM, AT, QM, MD, FM, NoMarginModel>
 where
     AT: AssetType + Clone + 'static,
-    MD: MarketDepth + L3MarketDepth + 'static,
+    MD: MarketDepth + L3MarketDepth + ApplySnapshot + 'static,
     QM: L3QueueModel<MD> + 'static,
     LM: LatencyModel + Clone + 'static,
     FM: FeeModel + Clone + 'static,
@@ -643,6 +1199,60 @@ where
         }
     }
 
+    /// Writes a checkpoint from which the backtest can be resumed by [`restore`](Self::restore).
+    ///
+    /// The checkpoint holds the current timestamp and event cursor, along with each asset's market
+    /// depth, open orders, orders in transit on the order buses, state values, queue positions, and
+    /// data position, as well as the state of the latency model, the rate limit windows, and the
+    /// fault injector. The configuration of the models is not saved, so the backtest should be
+    /// rebuilt with the same models to produce the same results.
+    pub fn checkpoint<W: Write>(&self, writer: &mut W) -> Result<(), BacktestError> {
+        let (evs_timestamp, evs_invalid) = self.evs.cursor();
+        let mut local = Vec::with_capacity(self.local.len());
+        for lp in self.local.iter() {
+            local.push(lp.checkpoint()?);
+        }
+        let mut exch = Vec::with_capacity(self.exch.len());
+        for ep in self.exch.iter() {
+            exch.push(ep.checkpoint()?);
+        }
+        let bytes = checkpoint::encode(&BacktestCheckpoint {
+            cur_ts: self.cur_ts,
+            evs_timestamp,
+            evs_invalid,
+            local,
+            exch,
+        })?;
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Restores the backtest from the checkpoint written by [`checkpoint`](Self::checkpoint).
+    ///
+    /// This backtest must be built in the same way as the one that wrote the checkpoint, with the
+    /// same assets, data, and models, and must not have been run yet. Once restored, the backtest
+    /// continues identically to the one that wrote the checkpoint.
+    pub fn restore<R: Read>(&mut self, reader: &mut R) -> Result<(), BacktestError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let state: BacktestCheckpoint = checkpoint::decode(&bytes)?;
+        if state.local.len() != self.local.len() || state.exch.len() != self.exch.len() {
+            return Err(BacktestError::DataError(IoError::new(
+                ErrorKind::InvalidData,
+                "the number of assets does not match the checkpoint",
+            )));
+        }
+        for (lp, bytes) in self.local.iter_mut().zip(state.local.iter()) {
+            lp.restore(bytes)?;
+        }
+        for (ep, bytes) in self.exch.iter_mut().zip(state.exch.iter()) {
+            ep.restore(bytes)?;
+        }
+        self.evs.set_cursor(&state.evs_timestamp, state.evs_invalid);
+        self.cur_ts = state.cur_ts;
+        Ok(())
+    }
+
     fn initialize_evs(&mut self) -> Result<(), BacktestError> {
         for (asset_no, local) in self.local.iter_mut().enumerate() {
             match local.initialize_data() {
@@ -690,9 +1300,11 @@ where
         wait_order_response: WaitOrderResponse,
     ) -> Result<bool, BacktestError> {
         let mut timestamp = timestamp;
-        for (asset_no, local) in self.local.iter().enumerate() {
+        for (asset_no, (local, exch)) in self.local.iter().zip(self.exch.iter()).enumerate() {
+            // The exchange also needs to wake up to expire its good-till-date orders, so its
+            // earliest timestamp is used rather than that of the orders sent by the local.
             self.evs
-                .update_exch_order(asset_no, local.earliest_send_order_timestamp());
+                .update_exch_order(asset_no, exch.earliest_recv_order_timestamp());
             self.evs
                 .update_local_order(asset_no, local.earliest_recv_order_timestamp());
         }
@@ -753,9 +1365,10 @@ where
                                     return Err(e);
                                 }
                             }
+                            let local = unsafe { self.local.get_unchecked(ev.asset_no) };
                             self.evs.update_local_order(
                                 ev.asset_no,
-                                exch.earliest_send_order_timestamp(),
+                                local.earliest_recv_order_timestamp(),
                             );
                         }
                         EventIntentKind::ExchOrder => {
@@ -765,6 +1378,11 @@ where
                                 ev.asset_no,
                                 exch.earliest_recv_order_timestamp(),
                             );
+                            let local = unsafe { self.local.get_unchecked(ev.asset_no) };
+                            self.evs.update_local_order(
+                                ev.asset_no,
+                                local.earliest_recv_order_timestamp(),
+                            );
                         }
                     }
                 }
@@ -899,15 +1517,7 @@ where
         wait: bool,
     ) -> Result<bool, Self::Error> {
         let local = self.local.get_mut(asset_no).unwrap();
-        local.submit_order(
-            order.order_id,
-            Side::Sell,
-            order.price,
-            order.qty,
-            order.order_type,
-            order.time_in_force,
-            self.cur_ts,
-        )?;
+        local.submit_order_request(&order, self.cur_ts)?;
 
         if wait {
             return self.goto::<false>(
@@ -940,6 +1550,27 @@ where
         Ok(true)
     }
 
+    #[inline]
+    fn modify(
+        &mut self,
+        asset_no: usize,
+        order_id: OrderId,
+        price: f64,
+        qty: f64,
+        wait: bool,
+    ) -> Result<bool, Self::Error> {
+        let local = self.local.get_mut(asset_no).unwrap();
+        local.modify(order_id, price, qty, self.cur_ts)?;
+
+        if wait {
+            return self.goto::<false>(
+                UNTIL_END_OF_DATA,
+                WaitOrderResponse::Specified { asset_no, order_id },
+            );
+        }
+        Ok(true)
+    }
+
     #[inline]
     fn clear_inactive_orders(&mut self, asset_no: Option<usize>) {
         match asset_no {
@@ -1140,9 +1771,11 @@ where
         wait_order_response: WaitOrderResponse,
     ) -> Result<bool, BacktestError> {
         let mut timestamp = timestamp;
-        for (asset_no, local) in self.local.iter().enumerate() {
+        for (asset_no, (local, exch)) in self.local.iter().zip(self.exch.iter()).enumerate() {
+            // The exchange also needs to wake up to expire its good-till-date orders, so its
+            // earliest timestamp is used rather than that of the orders sent by the local.
             self.evs
-                .update_exch_order(asset_no, local.earliest_send_order_timestamp());
+                .update_exch_order(asset_no, exch.earliest_recv_order_timestamp());
             self.evs
                 .update_local_order(asset_no, local.earliest_recv_order_timestamp());
         }
@@ -1203,9 +1836,10 @@ where
                                     return Err(e);
                                 }
                             }
+                            let local = unsafe { self.local.get_unchecked(ev.asset_no) };
                             self.evs.update_local_order(
                                 ev.asset_no,
-                                exch.earliest_send_order_timestamp(),
+                                local.earliest_recv_order_timestamp(),
                             );
                         }
                         EventIntentKind::ExchOrder => {
@@ -1215,6 +1849,11 @@ where
                                 ev.asset_no,
                                 exch.earliest_recv_order_timestamp(),
                             );
+                            let local = unsafe { self.local.get_unchecked(ev.asset_no) };
+                            self.evs.update_local_order(
+                                ev.asset_no,
+                                local.earliest_recv_order_timestamp(),
+                            );
                         }
                     }
                 }
@@ -1351,15 +1990,7 @@ where
         wait: bool,
     ) -> Result<bool, Self::Error> {
         let local = self.local.get_mut(asset_no).unwrap();
-        local.submit_order(
-            order.order_id,
-            Side::Sell,
-            order.price,
-            order.qty,
-            order.order_type,
-            order.time_in_force,
-            self.cur_ts,
-        )?;
+        local.submit_order_request(&order, self.cur_ts)?;
 
         if wait {
             return self.goto::<false>(
@@ -1392,6 +2023,27 @@ where
         Ok(true)
     }
 
+    #[inline]
+    fn modify(
+        &mut self,
+        asset_no: usize,
+        order_id: OrderId,
+        price: f64,
+        qty: f64,
+        wait: bool,
+    ) -> Result<bool, Self::Error> {
+        let local = self.local.get_mut(asset_no).unwrap();
+        local.modify(order_id, price, qty, self.cur_ts)?;
+
+        if wait {
+            return self.goto::<false>(
+                UNTIL_END_OF_DATA,
+                WaitOrderResponse::Specified { asset_no, order_id },
+            );
+        }
+        Ok(true)
+    }
+
     #[inline]
     fn clear_inactive_orders(&mut self, asset_no: Option<usize>) {
         match asset_no {
@@ -1481,3 +2133,398 @@ where
         self.local.get(asset_no).unwrap().order_latency()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use crate::{
+        backtest::{
+            checkpoint::{self, BacktestCheckpoint, ExchCheckpoint},
+            fixture::{asset_builder, asset_builder_with_depth, build_backtest, event, to_data},
+            models::FaultInjector,
+            Backtest,
+            BacktestError,
+            BacktestObserver,
+            ParamSweep,
+        },
+        depth::{
+            ApplySnapshot,
+            BTreeMarketDepth,
+            HashMapMarketDepth,
+            L2MarketDepth,
+            MarketDepth,
+            ROIVectorMarketDepth,
+        },
+        prelude::{
+            Bot,
+            Event,
+            OrdType,
+            Order,
+            Recorder,
+            Side,
+            StateValues,
+            Status,
+            TimeInForce,
+        },
+        types::{BUY_EVENT, DEPTH_EVENT, LOCAL_TRADE_EVENT, SELL_EVENT, TRADE_EVENT},
+    };
+    #[cfg(feature = "unstable_fuse")]
+    use crate::depth::FusedHashMapMarketDepth;
+
+    fn generate_events(num_steps: usize, seed: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        let mut rng = seed;
+        let mut next = || {
+            rng = rng
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            rng >> 33
+        };
+        let mut mid_tick = 1000;
+        for step in 0..num_steps {
+            let exch_ts = (step as i64 + 1) * 1_000;
+            let mut push = |ev: u64, px_tick: i64, qty: f64| {
+                events.push(event(ev, exch_ts, px_tick as f64, qty));
+            };
+            let prev_mid_tick = mid_tick;
+            mid_tick += next() as i64 % 3 - 1;
+            if mid_tick != prev_mid_tick {
+                push(BUY_EVENT | DEPTH_EVENT, prev_mid_tick - 1, 0.0);
+                push(SELL_EVENT | DEPTH_EVENT, prev_mid_tick + 1, 0.0);
+            }
+            push(
+                BUY_EVENT | DEPTH_EVENT,
+                mid_tick - 1,
+                (next() % 10 + 1) as f64,
+            );
+            push(
+                SELL_EVENT | DEPTH_EVENT,
+                mid_tick + 1,
+                (next() % 10 + 1) as f64,
+            );
+            match next() % 3 {
+                0 => push(SELL_EVENT | TRADE_EVENT, mid_tick - 1, (next() % 5 + 1) as f64),
+                1 => push(BUY_EVENT | TRADE_EVENT, mid_tick + 1, (next() % 5 + 1) as f64),
+                _ => {}
+            }
+        }
+        events
+    }
+
+    fn run<MD>(hbt: &mut Backtest<MD>, max_steps: usize) -> Vec<(i64, f64, f64, StateValues)>
+    where
+        MD: MarketDepth,
+    {
+        let mut records = Vec::new();
+        for _ in 0..max_steps {
+            if !hbt.elapse(700).unwrap() {
+                break;
+            }
+            hbt.clear_inactive_orders(Some(0));
+            let best_bid = hbt.depth(0).best_bid();
+            let best_ask = hbt.depth(0).best_ask();
+            for side in [Side::Buy, Side::Sell] {
+                let has_order = hbt
+                    .orders(0)
+                    .values()
+                    .any(|order| order.side == side && order.active());
+                if has_order || best_bid.is_nan() || best_ask.is_nan() {
+                    continue;
+                }
+                let order_id = hbt.current_timestamp() as u64 * 2;
+                if side == Side::Buy {
+                    hbt.submit_buy_order(
+                        0,
+                        order_id,
+                        best_bid,
+                        1.0,
+                        TimeInForce::GTC,
+                        OrdType::Limit,
+                        false,
+                    )
+                    .unwrap();
+                } else {
+                    hbt.submit_sell_order(
+                        0,
+                        order_id + 1,
+                        best_ask,
+                        1.0,
+                        TimeInForce::GTC,
+                        OrdType::Limit,
+                        false,
+                    )
+                    .unwrap();
+                }
+            }
+            records.push((
+                hbt.current_timestamp(),
+                best_bid,
+                best_ask,
+                hbt.state_values(0).clone(),
+            ));
+        }
+        records
+    }
+
+    #[test]
+    fn restore_from_checkpoint() {
+        // Splits the data into two so that the checkpoint is made while reading the second one.
+        let events = generate_events(600, 1);
+        let (first, second) = events.split_at(events.len() / 2);
+        let data = [to_data(first), to_data(second)];
+
+        let mut hbt = build_backtest(&data);
+        run(&mut hbt, 500);
+        let mut checkpoint = Vec::new();
+        hbt.checkpoint(&mut checkpoint).unwrap();
+        let expected = run(&mut hbt, usize::MAX);
+        assert!(expected.last().unwrap().3.num_trades > 0);
+
+        let mut restored = build_backtest(&data);
+        restored.restore(&mut checkpoint.as_slice()).unwrap();
+        let actual = run(&mut restored, usize::MAX);
+        assert_eq!(actual, expected);
+    }
+
+    fn restore_with_depth<MD>(depth: fn() -> MD)
+    where
+        MD: MarketDepth + L2MarketDepth + ApplySnapshot + 'static,
+    {
+        let events = generate_events(600, 2);
+        let (first, second) = events.split_at(events.len() / 2);
+        let data = [to_data(first), to_data(second)];
+        let build = || {
+            Backtest::builder()
+                .add_asset(asset_builder_with_depth(&data, depth).build().unwrap())
+                .build()
+                .unwrap()
+        };
+
+        let mut hbt = build();
+        run(&mut hbt, 500);
+        let mut checkpoint = Vec::new();
+        hbt.checkpoint(&mut checkpoint).unwrap();
+        let expected = run(&mut hbt, usize::MAX);
+
+        let mut restored = build();
+        restored.restore(&mut checkpoint.as_slice()).unwrap();
+        let actual = run(&mut restored, usize::MAX);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn restore_every_market_depth() {
+        restore_with_depth(|| HashMapMarketDepth::new(1.0, 1.0));
+        restore_with_depth(|| BTreeMarketDepth::new(1.0, 1.0));
+        restore_with_depth(|| ROIVectorMarketDepth::new(1.0, 1.0, 0.0, 2_000.0));
+        #[cfg(feature = "unstable_fuse")]
+        restore_with_depth(|| FusedHashMapMarketDepth::new(1.0, 1.0));
+    }
+
+    #[test]
+    fn reconcile_faulted_order_flow() {
+        let events = generate_events(600, 3);
+        let (first, second) = events.split_at(events.len() / 2);
+        let data = [to_data(first), to_data(second)];
+        let build = || {
+            Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .fault_injector(
+                            FaultInjector::new(1)
+                                .reject_prob(0.1)
+                                .drop_prob(0.3)
+                                .bursts(50_000, 2_000)
+                                .reconcile_timeout(5_000),
+                        )
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+
+        let mut hbt = build();
+        run(&mut hbt, 300);
+        let mut checkpoint = Vec::new();
+        hbt.checkpoint(&mut checkpoint).unwrap();
+        let expected = run(&mut hbt, usize::MAX);
+        assert!(expected.last().unwrap().3.num_trades > 0);
+
+        // The fills in the dropped responses are applied, so the local state ends up the same as
+        // the exchange's.
+        let mut end = Vec::new();
+        hbt.checkpoint(&mut end).unwrap();
+        let state: BacktestCheckpoint = checkpoint::decode(&end).unwrap();
+        let exch: ExchCheckpoint = checkpoint::decode(&state.exch[0]).unwrap();
+        assert_eq!(hbt.state_values(0), &exch.state_values);
+
+        // The same faults are injected after restoring from the checkpoint.
+        let mut restored = build();
+        restored.restore(&mut checkpoint.as_slice()).unwrap();
+        let actual = run(&mut restored, usize::MAX);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn sweep_over_shared_data() {
+        let events = generate_events(600, 2);
+        let data = [to_data(&events)];
+        let params = vec![100, 300, usize::MAX];
+
+        // The backtest can be moved to another thread along with the data it shares.
+        let mut expected: Vec<_> = params
+            .iter()
+            .map(|&max_steps| {
+                let mut hbt = build_backtest(&data);
+                let handle = thread::spawn(move || run(&mut hbt, max_steps));
+                (max_steps, handle.join().unwrap())
+            })
+            .collect();
+
+        let records = Mutex::new(Vec::new());
+        let results = ParamSweep::new(params.clone()).num_threads(2).run(
+            |_| Ok::<_, BacktestError>(build_backtest(&data)),
+            |hbt, recorder, &max_steps| {
+                let run_records = run(hbt, max_steps);
+                recorder.record(hbt)?;
+                records.lock().unwrap().push((max_steps, run_records));
+                Ok(())
+            },
+        );
+        assert_eq!(results.len(), params.len());
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        let mut actual = records.into_inner().unwrap();
+        actual.sort_by_key(|(max_steps, _)| *max_steps);
+        expected.sort_by_key(|(max_steps, _)| *max_steps);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn sweep_with_preprocessor() {
+        let events = generate_events(600, 2);
+        let data = [to_data(&events)];
+        let params = vec![100, 300, usize::MAX];
+        let build = || {
+            Backtest::builder()
+                .add_asset(asset_builder(&data).latency_offset(100).build().unwrap())
+                .build()
+                .unwrap()
+        };
+
+        let expected: Vec<_> = params
+            .iter()
+            .map(|&max_steps| (max_steps, run(&mut build(), max_steps)))
+            .collect();
+
+        // Each backtest applies the latency offset to its own copy of the shared data.
+        let records = Mutex::new(Vec::new());
+        let results = ParamSweep::new(params.clone()).num_threads(3).run(
+            |_| Ok::<_, BacktestError>(build()),
+            |hbt, _, &max_steps| {
+                records.lock().unwrap().push((max_steps, run(hbt, max_steps)));
+                Ok(())
+            },
+        );
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        let mut actual = records.into_inner().unwrap();
+        actual.sort_by_key(|(max_steps, _)| *max_steps);
+        assert_eq!(actual, expected);
+        for (i, event) in events.iter().enumerate() {
+            assert_eq!(data[0][i].local_ts, event.local_ts);
+        }
+    }
+
+    #[derive(Default)]
+    struct FillLog {
+        fills: Vec<(i64, f64)>,
+        num_order_updates: usize,
+        num_trades: usize,
+        mid_price: f64,
+    }
+
+    impl BacktestObserver<HashMapMarketDepth> for FillLog {
+        fn on_order_update(&mut self, _order: &Order, _timestamp: i64) {
+            self.num_order_updates += 1;
+        }
+
+        fn on_fill(&mut self, order: &Order, timestamp: i64) {
+            self.fills.push((timestamp, order.exec_qty));
+        }
+
+        fn on_depth_change(&mut self, depth: &HashMapMarketDepth, _event: &Event) {
+            self.mid_price = (depth.best_bid() + depth.best_ask()) / 2.0;
+        }
+
+        fn on_trade(&mut self, _trade: &Event) {
+            self.num_trades += 1;
+        }
+    }
+
+    #[test]
+    fn observer_is_notified() {
+        let events = generate_events(600, 3);
+        let data = [to_data(&events)];
+        let fill_log = Arc::new(Mutex::new(FillLog::default()));
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                asset_builder(&data)
+                    .observer(fill_log.clone())
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        let records = run(&mut hbt, usize::MAX);
+
+        let fill_log = fill_log.lock().unwrap();
+        let state_values = &records.last().unwrap().3;
+        assert!(state_values.num_trades > 0);
+        assert_eq!(fill_log.fills.len() as i64, state_values.num_trades);
+        assert!(fill_log.fills.windows(2).all(|fills| fills[0].0 <= fills[1].0));
+        assert_eq!(
+            fill_log.fills.iter().map(|(_, qty)| qty).sum::<f64>(),
+            state_values.trading_volume
+        );
+        assert!(fill_log.num_order_updates > fill_log.fills.len());
+        assert_eq!(
+            fill_log.num_trades,
+            events.iter().filter(|ev| ev.is(LOCAL_TRADE_EVENT)).count()
+        );
+        let depth = hbt.depth(0);
+        assert_eq!(
+            fill_log.mid_price,
+            (depth.best_bid() + depth.best_ask()) / 2.0
+        );
+    }
+
+    #[test]
+    fn receive_response_before_next_feed_event() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 100_000, 101.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_buy_order(0, 1, 95.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        // The response is received after the round-trip latency, without waiting for the next
+        // feed event.
+        assert_eq!(hbt.current_timestamp(), 2_020);
+        assert_eq!(hbt.orders(0).get(&1).unwrap().status, Status::New);
+
+        hbt.modify(0, 1, 96.0, 1.0, true).unwrap();
+        assert_eq!(hbt.current_timestamp(), 2_040);
+        let order = hbt.orders(0).get(&1).unwrap();
+        assert_eq!(order.price_tick, 96);
+        assert!(!order.pending());
+    }
+}
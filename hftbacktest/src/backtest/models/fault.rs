@@ -0,0 +1,337 @@
+use std::{
+    collections::VecDeque,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    backtest::{checkpoint, models::rng::ReplayRng, BacktestError},
+    types::{Order, OrderId},
+};
+
+/// Injects faults into the order flow between the local and the exchange, so that the error paths
+/// of a strategy can be exercised in backtesting. All faults are drawn from random number
+/// generators seeded by the given seed, so the same backtest reproduces the same faults.
+///
+/// * A fraction of the requests is rejected before reaching the exchange, in the same way as a
+///   negative entry latency from the [`LatencyModel`](super::LatencyModel), and the rejection is
+///   received after the entry latency.
+/// * A fraction of the exchange's responses is dropped, so the local keeps the order
+///   [`pending()`](crate::types::Order::pending) until a later response for the same order, such
+///   as a fill, brings it up to date, or until the order status is refreshed after the
+///   [`reconcile_timeout`](FaultInjector::reconcile_timeout). The fill in a dropped response is
+///   still applied to the position and the balance, as the account updates would carry it.
+///   Rejections made before reaching the exchange are never dropped. Whether a response is dropped
+///   is drawn from the response itself, since the responses that the exchange sends at the same
+///   time can come in a different order from run to run.
+/// * Bursts of delay occur at random intervals. During a burst, the requests reach the exchange
+///   and the responses reach the local only after the burst ends.
+///
+/// The state of the random number generator, the burst, and the scheduled refreshes is saved in
+/// the checkpoint, so a restored backtest reproduces the same faults as well.
+///
+/// **Example**
+/// ```
+/// use hftbacktest::backtest::models::FaultInjector;
+///
+/// // Rejects 1% of the requests, drops 0.5% of the responses, and stalls the order flow for 200ms
+/// // every 10 minutes on average. The status of an order whose response is dropped is refreshed
+/// // after 1s.
+/// let fault_injector = FaultInjector::new(42)
+///     .reject_prob(0.01)
+///     .drop_prob(0.005)
+///     .bursts(600_000_000_000, 200_000_000)
+///     .reconcile_timeout(1_000_000_000);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FaultInjector {
+    seed: u64,
+    rng: ReplayRng,
+    reject_prob: f64,
+    drop_prob: f64,
+    burst_interval: i64,
+    burst_duration: i64,
+    reconcile_timeout: i64,
+    // The start and the end timestamps of the current or the next burst.
+    burst: Option<(i64, i64)>,
+    // The dropped responses with the timestamps at which the order status is refreshed with them,
+    // in the order of the timestamps.
+    refreshes: VecDeque<(i64, Order)>,
+}
+
+impl FaultInjector {
+    /// Constructs a `FaultInjector` that injects no faults until they are configured.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ReplayRng::seed_from_u64(seed),
+            reject_prob: 0.0,
+            drop_prob: 0.0,
+            burst_interval: 0,
+            burst_duration: 0,
+            reconcile_timeout: 0,
+            burst: None,
+            refreshes: VecDeque::new(),
+        }
+    }
+
+    /// Sets the probability that a request is rejected.
+    pub fn reject_prob(self, reject_prob: f64) -> Self {
+        Self {
+            reject_prob,
+            ..self
+        }
+    }
+
+    /// Sets the probability that a response from the exchange is dropped.
+    pub fn drop_prob(self, drop_prob: f64) -> Self {
+        Self { drop_prob, ..self }
+    }
+
+    /// Makes bursts of delay that last `duration` occur with exponentially distributed gaps whose
+    /// mean is `mean_interval`, both in nanoseconds.
+    pub fn bursts(self, mean_interval: i64, duration: i64) -> Self {
+        Self {
+            burst_interval: mean_interval,
+            burst_duration: duration,
+            burst: None,
+            ..self
+        }
+    }
+
+    /// Refreshes the status of an order `timeout` nanoseconds after its response is dropped, as a
+    /// strategy would query the exchange once it stops hearing about the order. The status is not
+    /// refreshed if a later response for the order is received in the meantime. By default, the
+    /// status is never refreshed.
+    pub fn reconcile_timeout(self, timeout: i64) -> Self {
+        Self {
+            reconcile_timeout: timeout,
+            ..self
+        }
+    }
+
+    fn next_burst(&mut self, after: i64) -> (i64, i64) {
+        let u = 1.0 - self.rng.gen::<f64>();
+        let start = after + (-u.ln() * self.burst_interval as f64) as i64;
+        (start, start + self.burst_duration)
+    }
+
+    /// Returns the end timestamp of the burst in progress at the timestamp, if any.
+    fn burst_end(&mut self, timestamp: i64) -> Option<i64> {
+        if self.burst_interval <= 0 || self.burst_duration <= 0 {
+            return None;
+        }
+        let mut burst = match self.burst {
+            Some(burst) => burst,
+            None => self.next_burst(timestamp),
+        };
+        while burst.1 <= timestamp {
+            burst = self.next_burst(burst.1);
+        }
+        self.burst = Some(burst);
+        (burst.0 <= timestamp).then_some(burst.1)
+    }
+
+    fn is_dropped(&self, order: &Order) -> bool {
+        if self.drop_prob <= 0.0 {
+            return false;
+        }
+        let mut hasher = DefaultHasher::new();
+        (
+            self.seed,
+            order.order_id,
+            order.exch_timestamp,
+            order.status as u8,
+            order.leaves_qty.to_bits(),
+        )
+            .hash(&mut hasher);
+        StdRng::seed_from_u64(hasher.finish()).gen_bool(self.drop_prob)
+    }
+
+    /// Applies the faults to a request sent at the timestamp with the entry latency given by the
+    /// latency model, and returns the entry latency to use. As with the latency model, a negative
+    /// value means that the request is rejected.
+    pub fn request(&mut self, timestamp: i64, entry_latency: i64) -> i64 {
+        if entry_latency < 0 {
+            return entry_latency;
+        }
+        let rejected = self.rng.gen_bool(self.reject_prob);
+        let entry_latency = match self.burst_end(timestamp) {
+            Some(burst_end) => entry_latency + burst_end - timestamp,
+            None => entry_latency,
+        };
+        if rejected {
+            -entry_latency.max(1)
+        } else {
+            entry_latency
+        }
+    }
+
+    /// Applies the faults to a response from the exchange that the local receives at the
+    /// timestamp, and returns the timestamp at which the response should be received instead, or
+    /// `None` if the response is dropped. The refresh of the order status is scheduled for the
+    /// dropped response.
+    pub fn response(&mut self, timestamp: i64, order: &Order) -> Option<i64> {
+        if let Some(burst_end) = self.burst_end(timestamp) {
+            // The held response is tested for dropping once it is received after the burst.
+            return Some(burst_end);
+        }
+        if self.is_dropped(order) {
+            if self.reconcile_timeout > 0 {
+                self.refreshes
+                    .push_back((timestamp + self.reconcile_timeout, order.clone()));
+            }
+            None
+        } else {
+            // The received response supersedes the dropped ones for the same order.
+            self.cancel_refreshes(order.order_id);
+            Some(timestamp)
+        }
+    }
+
+    fn cancel_refreshes(&mut self, order_id: OrderId) {
+        self.refreshes
+            .retain(|(_, order)| order.order_id != order_id);
+    }
+
+    /// Returns the earliest timestamp at which an order status is refreshed, if any.
+    pub fn earliest_refresh_timestamp(&self) -> Option<i64> {
+        self.refreshes.front().map(|(timestamp, _)| *timestamp)
+    }
+
+    /// Removes and returns the dropped response with which the order status is refreshed at or
+    /// before the timestamp, if any.
+    pub fn pop_refresh(&mut self, timestamp: i64) -> Option<Order> {
+        while self.earliest_refresh_timestamp()? <= timestamp {
+            let (_, order) = self.refreshes.pop_front().unwrap();
+            // A later dropped response for the same order refreshes the status instead.
+            if !self
+                .refreshes
+                .iter()
+                .any(|(_, later)| later.order_id == order.order_id)
+            {
+                return Some(order);
+            }
+        }
+        None
+    }
+
+    /// Returns the state of the injector as bytes so that it can be saved in a checkpoint.
+    pub fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let refreshes: Vec<(i64, Order)> = self.refreshes.iter().cloned().collect();
+        checkpoint::encode(&(self.rng.state(), self.burst, refreshes))
+    }
+
+    /// Restores the state saved by [`checkpoint`](FaultInjector::checkpoint).
+    pub fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let (rng, burst, refreshes): (_, _, Vec<(i64, Order)>) = checkpoint::decode(checkpoint)?;
+        self.rng.restore(rng);
+        self.burst = burst;
+        self.refreshes = refreshes.into();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{asset_builder, event, to_data},
+            models::FaultInjector,
+            Backtest,
+        },
+        prelude::{Bot, OrdType, Order, Side, Status, TimeInForce},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn delay_order_flow_during_bursts() {
+        let order = Order::new(1, 100, 1.0, 1.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        let mut fault_injector = FaultInjector::new(7).bursts(1_000_000, 10_000);
+        let mut replayed = fault_injector.clone();
+
+        // Finds the first burst, which is the same for the injectors with the same seed.
+        let mut timestamp = 0;
+        let burst_end = loop {
+            let deliver_timestamp = fault_injector.response(timestamp, &order).unwrap();
+            assert_eq!(replayed.response(timestamp, &order), Some(deliver_timestamp));
+            if deliver_timestamp > timestamp {
+                break deliver_timestamp;
+            }
+            timestamp += 1_000;
+        };
+        assert!(burst_end - timestamp <= 10_000);
+
+        // A request sent during the burst reaches the exchange only after the burst ends.
+        assert_eq!(fault_injector.request(timestamp, 10), burst_end - timestamp + 10);
+        assert_eq!(fault_injector.request(burst_end, 10), 10);
+        assert_eq!(fault_injector.response(burst_end, &order), Some(burst_end));
+
+        // The restored injector continues in the same way.
+        let mut restored = FaultInjector::new(8).bursts(1_000_000, 10_000);
+        restored
+            .restore(&fault_injector.checkpoint().unwrap())
+            .unwrap();
+        for timestamp in (burst_end..burst_end + 10_000_000).step_by(1_000) {
+            assert_eq!(
+                restored.response(timestamp, &order),
+                fault_injector.response(timestamp, &order)
+            );
+        }
+    }
+
+    #[test]
+    fn inject_order_flow_faults() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+        let build = |fault_injector: FaultInjector| {
+            Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .fault_injector(fault_injector)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+
+        // The rejected request never reaches the exchange.
+        let mut hbt = build(FaultInjector::new(1).reject_prob(1.0));
+        hbt.elapse(1_000).unwrap();
+        let timestamp = hbt.current_timestamp();
+        hbt.submit_buy_order(0, 1, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.current_timestamp(), timestamp + 10);
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.exch_timestamp, 0);
+
+        // The order is accepted by the exchange, but the local never hears about it.
+        let mut hbt = build(FaultInjector::new(1).drop_prob(1.0));
+        hbt.elapse(1_000).unwrap();
+        let timestamp = hbt.current_timestamp();
+        hbt.submit_buy_order(0, 1, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, false)
+            .unwrap();
+        hbt.wait_order_response(0, 1, 1_000).unwrap();
+        assert_eq!(hbt.current_timestamp(), timestamp + 1_000);
+        assert!(hbt.orders(0)[&1].pending());
+
+        // The status of the order is refreshed after the reconcile timeout.
+        let mut hbt = build(FaultInjector::new(1).drop_prob(1.0).reconcile_timeout(500));
+        hbt.elapse(1_000).unwrap();
+        let timestamp = hbt.current_timestamp();
+        hbt.submit_buy_order(0, 1, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, false)
+            .unwrap();
+        hbt.wait_order_response(0, 1, 1_000).unwrap();
+        // The exchange accepts the order after 10, and the response is dropped after another 10.
+        assert_eq!(hbt.current_timestamp(), timestamp + 520);
+        let order = &hbt.orders(0)[&1];
+        assert!(!order.pending());
+        assert_eq!(order.status, Status::New);
+    }
+}
@@ -44,7 +44,7 @@ impl DirectionalFees {
 }
 
 /// Provides the fee.
-pub trait FeeModel {
+pub trait FeeModel: Send {
     /// Calculates the fee amount.
     fn amount(&self, order: &Order, amount: f64) -> f64;
 }
@@ -1,22 +1,56 @@
-use std::{io::Error as IoError, mem};
+use std::{
+    collections::VecDeque,
+    f64::consts::PI,
+    io::{Error as IoError, ErrorKind},
+    mem,
+};
 
 use hftbacktest_derive::NpyDTyped;
+use rand::Rng;
 
 use crate::{
     backtest::{
-        data::{Data, DataPreprocess, DataSource, Reader, POD},
+        checkpoint,
+        data::{read_npy_file, read_npz_file, Data, DataPreprocess, DataSource, Reader, POD},
+        models::rng::ReplayRng,
         BacktestError,
     },
-    types::Order,
+    types::{Event, Order, Status, TRADE_EVENT},
 };
 
 /// Provides the order entry latency and the order response latency.
-pub trait LatencyModel {
+pub trait LatencyModel: Send {
     /// Returns the order entry latency for the given timestamp and order.
     fn entry(&mut self, timestamp: i64, order: &Order) -> i64;
 
     /// Returns the order response latency for the given timestamp and order.
     fn response(&mut self, timestamp: i64, order: &Order) -> i64;
+
+    /// Notifies the model of the market feed event that the processor has just processed, so that
+    /// the latency can depend on the market activity. The local notifies the model at the local
+    /// timestamp, and the exchange at the exchange timestamp. The default implementation does
+    /// nothing.
+    fn on_feed(&mut self, _timestamp: i64, _event: &Event) {}
+
+    /// Notifies the model of the type of the request that the exchange starts to process, which
+    /// is [`Status::New`], [`Status::Canceled`], or [`Status::Replaced`], and then of
+    /// [`Status::None`] once it finishes processing the request. The responses in between are
+    /// the responses to the request. The default implementation does nothing.
+    fn on_request(&mut self, _req: Status) {}
+
+    /// Returns the state of the model that changes as the backtest runs, such as the state of a
+    /// random number generator, as bytes so that it can be saved in a checkpoint. The default
+    /// implementation returns no bytes, which suits a model whose latency depends only on the
+    /// given timestamp and order.
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        Ok(Vec::new())
+    }
+
+    /// Restores the state saved by [`checkpoint`](LatencyModel::checkpoint). The default
+    /// implementation does nothing.
+    fn restore(&mut self, _checkpoint: &[u8]) -> Result<(), BacktestError> {
+        Ok(())
+    }
 }
 
 /// Provides constant order latency.
@@ -293,3 +327,748 @@ impl DataPreprocess<OrderLatencyRow> for OrderLatencyAdjustment {
         Ok(())
     }
 }
+
+/// A distribution from which [`StochasticLatency`] samples latency.
+pub trait LatencyDistribution {
+    /// Samples a latency value.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> i64;
+}
+
+/// A log-normal latency distribution, which reproduces the right-skewed shape of the observed
+/// latency.
+#[derive(Clone, Debug)]
+pub struct LogNormal {
+    mu: f64,
+    sigma: f64,
+}
+
+impl LogNormal {
+    /// Constructs an instance of `LogNormal`.
+    ///
+    /// `mu` and `sigma` are the mean and the standard deviation of the natural logarithm of the
+    /// latency, so the median latency is `exp(mu)`.
+    pub fn new(mu: f64, sigma: f64) -> Self {
+        Self { mu, sigma }
+    }
+}
+
+impl LatencyDistribution for LogNormal {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        // Box-Muller transform. `gen` samples from [0, 1), so `u1` is in (0, 1].
+        let u1 = 1.0 - rng.gen::<f64>();
+        let u2 = rng.gen::<f64>();
+        let z = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+        (self.mu + self.sigma * z).exp().round() as i64
+    }
+}
+
+/// A bin of the empirical latency histogram.
+#[repr(C)]
+#[derive(Clone, Debug, NpyDTyped)]
+pub struct LatencyHistogramRow {
+    /// The lower bound of the bin, inclusive.
+    pub lower: i64,
+    /// The upper bound of the bin, exclusive. If it is not greater than `lower`, the bin
+    /// represents the single value `lower`.
+    pub upper: i64,
+    /// The weight of the bin, such as the number of observations that fall into it.
+    pub weight: f64,
+}
+
+unsafe impl POD for LatencyHistogramRow {}
+
+/// An empirical latency distribution given by a histogram of the observed latency. A bin is
+/// chosen in proportion to its weight, and the latency is sampled uniformly within the bin.
+#[derive(Clone, Debug)]
+pub struct EmpiricalHistogram {
+    bins: Vec<(i64, i64)>,
+    cum_weights: Vec<f64>,
+}
+
+impl EmpiricalHistogram {
+    /// Constructs an `EmpiricalHistogram` from the histogram data. A file can be either a `npy`
+    /// file or a `npz` file that stores the histogram under the name `data`.
+    pub fn build(data: DataSource<LatencyHistogramRow>) -> Result<Self, BacktestError> {
+        let data = match data {
+            DataSource::File(filepath) if filepath.ends_with(".npz") => {
+                read_npz_file(&filepath, "data")?
+            }
+            DataSource::File(filepath) => read_npy_file(&filepath)?,
+            DataSource::Data(data) => data,
+        };
+        let mut bins = Vec::with_capacity(data.len());
+        let mut cum_weights = Vec::with_capacity(data.len());
+        let mut total = 0.0;
+        for i in 0..data.len() {
+            let row = &data[i];
+            if row.weight.is_nan() || row.weight < 0.0 {
+                return Err(BacktestError::DataError(IoError::new(
+                    ErrorKind::InvalidData,
+                    "the weight of a histogram bin is negative",
+                )));
+            }
+            total += row.weight;
+            bins.push((row.lower, row.upper));
+            cum_weights.push(total);
+        }
+        if total <= 0.0 {
+            return Err(BacktestError::DataError(IoError::new(
+                ErrorKind::InvalidData,
+                "the histogram has no weight",
+            )));
+        }
+        Ok(Self { bins, cum_weights })
+    }
+
+    /// Constructs an `EmpiricalHistogram` from the histogram data.
+    pub fn new(data: DataSource<LatencyHistogramRow>) -> Self {
+        Self::build(data).unwrap()
+    }
+}
+
+impl LatencyDistribution for EmpiricalHistogram {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        let total = self.cum_weights[self.cum_weights.len() - 1];
+        let x = rng.gen::<f64>() * total;
+        let i = self
+            .cum_weights
+            .partition_point(|&cum_weight| cum_weight <= x)
+            .min(self.bins.len() - 1);
+        let (lower, upper) = self.bins[i];
+        if upper > lower {
+            rng.gen_range(lower..upper)
+        } else {
+            lower
+        }
+    }
+}
+
+/// A mixture of a base latency distribution and a rare spike component, which models the fat tail
+/// of the latency such as the occasional delays during congestion.
+#[derive(Clone, Debug)]
+pub struct SpikeMixture<B, S> {
+    base: B,
+    spike: S,
+    spike_prob: f64,
+}
+
+impl<B, S> SpikeMixture<B, S> {
+    /// Constructs an instance of `SpikeMixture`, which samples from `spike` with the probability
+    /// `spike_prob` and from `base` otherwise.
+    pub fn new(base: B, spike: S, spike_prob: f64) -> Self {
+        Self {
+            base,
+            spike,
+            spike_prob,
+        }
+    }
+}
+
+impl<B, S> LatencyDistribution for SpikeMixture<B, S>
+where
+    B: LatencyDistribution,
+    S: LatencyDistribution,
+{
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        if rng.gen::<f64>() < self.spike_prob {
+            self.spike.sample(rng)
+        } else {
+            self.base.sample(rng)
+        }
+    }
+}
+
+/// Provides order latency sampled from the given [`LatencyDistribution`]s.
+///
+/// The samples are drawn from random number generators seeded with the given seed, so that a
+/// backtest with the same seed reproduces the same latency. The entry latency and the response
+/// latency are drawn from separate generators, which keeps them independent of each other even
+/// though the local and the exchange each use their own copy of the model. The state of the
+/// generators is saved in the checkpoint, so a restored backtest draws the same samples as well.
+///
+/// As with [`ConstantLatency`], a negative latency sampled from the distribution indicates an
+/// order rejection by the exchange.
+///
+/// **Example**
+/// ```
+/// use hftbacktest::backtest::models::{LogNormal, SpikeMixture, StochasticLatency};
+///
+/// // The median latency is about 1ms, and 0.1% of requests are delayed by about 50ms.
+/// let latency_model = StochasticLatency::new(
+///     SpikeMixture::new(LogNormal::new(13.8, 0.3), LogNormal::new(17.7, 0.5), 0.001),
+///     LogNormal::new(13.8, 0.3),
+///     42,
+/// );
+/// ```
+#[derive(Clone)]
+pub struct StochasticLatency<E, R> {
+    entry: E,
+    response: R,
+    entry_rng: ReplayRng,
+    response_rng: ReplayRng,
+}
+
+impl<E, R> StochasticLatency<E, R>
+where
+    E: LatencyDistribution,
+    R: LatencyDistribution,
+{
+    /// Constructs an instance of `StochasticLatency` with the entry and response latency
+    /// distributions and the seed of the random number generators.
+    pub fn new(entry: E, response: R, seed: u64) -> Self {
+        Self {
+            entry,
+            response,
+            entry_rng: ReplayRng::seed_from_u64(seed),
+            response_rng: ReplayRng::seed_from_u64(seed.wrapping_add(1)),
+        }
+    }
+}
+
+impl<E, R> LatencyModel for StochasticLatency<E, R>
+where
+    E: LatencyDistribution + Send,
+    R: LatencyDistribution + Send,
+{
+    fn entry(&mut self, _timestamp: i64, _order: &Order) -> i64 {
+        self.entry.sample(&mut self.entry_rng)
+    }
+
+    fn response(&mut self, _timestamp: i64, _order: &Order) -> i64 {
+        self.response.sample(&mut self.response_rng)
+    }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        checkpoint::encode(&(self.entry_rng.state(), self.response_rng.state()))
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let (entry_rng, response_rng) = checkpoint::decode(checkpoint)?;
+        self.entry_rng.restore(entry_rng);
+        self.response_rng.restore(response_rng);
+        Ok(())
+    }
+}
+
+/// The measure of the market activity that [`ActivityLatency`] maps to latency.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Activity {
+    /// The number of the feed messages.
+    MessageCount,
+    /// The traded quantity.
+    TradeVolume,
+}
+
+/// Provides order latency that rises with the market activity, as the exchange slows down when
+/// the market is busy.
+///
+/// The activity is measured over the trailing window of the market feed that the processor has
+/// processed, and is mapped to latency through a curve given by points of `(activity, latency)`,
+/// such as points sampled from a curve fitted to the historical latency. The latency between two
+/// points is linearly interpolated, and the latency outside the points is that of the nearest
+/// point.
+///
+/// **Example**
+/// ```
+/// use hftbacktest::backtest::models::{Activity, ActivityLatency};
+///
+/// // The latency rises from 1ms to 20ms as the number of messages within 100ms grows to 5,000.
+/// let latency_model = ActivityLatency::new(
+///     Activity::MessageCount,
+///     100_000_000,
+///     vec![(0.0, 1_000_000), (1_000.0, 2_000_000), (5_000.0, 20_000_000)],
+///     vec![(0.0, 1_000_000), (1_000.0, 2_000_000), (5_000.0, 20_000_000)],
+/// );
+/// ```
+#[derive(Clone)]
+pub struct ActivityLatency {
+    activity: Activity,
+    window: i64,
+    entry_curve: Vec<(f64, i64)>,
+    response_curve: Vec<(f64, i64)>,
+    // The timestamps and the amounts of the activity within the window.
+    events: VecDeque<(i64, f64)>,
+    amount: f64,
+}
+
+impl ActivityLatency {
+    /// Constructs an instance of `ActivityLatency`.
+    ///
+    /// `window` should match the time unit of the data's timestamps. The curves should have at
+    /// least one point each.
+    pub fn new(
+        activity: Activity,
+        window: i64,
+        mut entry_curve: Vec<(f64, i64)>,
+        mut response_curve: Vec<(f64, i64)>,
+    ) -> Self {
+        assert!(!entry_curve.is_empty() && !response_curve.is_empty());
+        entry_curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+        response_curve.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self {
+            activity,
+            window,
+            entry_curve,
+            response_curve,
+            events: Default::default(),
+            amount: 0.0,
+        }
+    }
+
+    /// Returns the market activity within the window ending at the given timestamp.
+    pub fn activity(&mut self, timestamp: i64) -> f64 {
+        while let Some(&(event_timestamp, amount)) = self.events.front() {
+            if event_timestamp > timestamp - self.window {
+                break;
+            }
+            self.events.pop_front();
+            self.amount -= amount;
+        }
+        if self.events.is_empty() {
+            // Prevents the rounding errors from accumulating.
+            self.amount = 0.0;
+        }
+        self.amount
+    }
+
+    fn latency(curve: &[(f64, i64)], activity: f64) -> i64 {
+        let i = curve.partition_point(|&(x, _)| x <= activity);
+        if i == 0 {
+            return curve[0].1;
+        }
+        if i == curve.len() {
+            return curve[curve.len() - 1].1;
+        }
+        let (x1, y1) = curve[i - 1];
+        let (x2, y2) = curve[i];
+        y1 + ((y2 - y1) as f64 * (activity - x1) / (x2 - x1)) as i64
+    }
+}
+
+impl LatencyModel for ActivityLatency {
+    fn entry(&mut self, timestamp: i64, _order: &Order) -> i64 {
+        let activity = self.activity(timestamp);
+        Self::latency(&self.entry_curve, activity)
+    }
+
+    fn response(&mut self, timestamp: i64, _order: &Order) -> i64 {
+        let activity = self.activity(timestamp);
+        Self::latency(&self.response_curve, activity)
+    }
+
+    fn on_feed(&mut self, timestamp: i64, event: &Event) {
+        let amount = match self.activity {
+            Activity::MessageCount => 1.0,
+            Activity::TradeVolume if event.is(TRADE_EVENT) => event.qty,
+            Activity::TradeVolume => return,
+        };
+        self.events.push_back((timestamp, amount));
+        self.amount += amount;
+    }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let events: Vec<(i64, f64)> = self.events.iter().copied().collect();
+        checkpoint::encode(&(events, self.amount))
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let (events, amount): (Vec<(i64, f64)>, f64) = checkpoint::decode(checkpoint)?;
+        self.events = events.into();
+        self.amount = amount;
+        Ok(())
+    }
+}
+
+/// Provides order latency that differs by the request type, by using a separate latency model for
+/// each of the new, cancel, and modify requests. For example, the cancel requests can travel a
+/// faster path than the new orders, which matters when a cancel races against a fill.
+///
+/// The entry latency is chosen by the request type of the order, and the response latency by the
+/// type of the request that the exchange is processing. The response latency of the fills and
+/// expiries that are not caused by a request comes from the model for the new orders. If no model
+/// is set for the cancel or modify requests, the model for the new orders is used instead.
+///
+/// **Example**
+/// ```no_run
+/// use hftbacktest::backtest::{
+///     models::{IntpOrderLatency, RequestTypeLatency},
+///     DataSource,
+/// };
+///
+/// let latency_model = RequestTypeLatency::new(IntpOrderLatency::new(
+///     vec![DataSource::File("latency_20240215.npz".to_string())],
+///     0,
+/// ))
+/// .with_cancel(IntpOrderLatency::new(
+///     vec![DataSource::File("cancel_latency_20240215.npz".to_string())],
+///     0,
+/// ));
+/// ```
+#[derive(Clone)]
+pub struct RequestTypeLatency<N, C = N, M = N> {
+    new: N,
+    cancel: Option<C>,
+    modify: Option<M>,
+    req: Status,
+}
+
+impl<N> RequestTypeLatency<N>
+where
+    N: LatencyModel,
+{
+    /// Constructs an instance of `RequestTypeLatency` with the latency model for the new orders,
+    /// which is used for all requests until the other models are set.
+    pub fn new(new: N) -> Self {
+        Self {
+            new,
+            cancel: None,
+            modify: None,
+            req: Status::None,
+        }
+    }
+}
+
+impl<N, C, M> RequestTypeLatency<N, C, M>
+where
+    N: LatencyModel,
+    C: LatencyModel,
+    M: LatencyModel,
+{
+    /// Sets the latency model for the cancel requests.
+    pub fn with_cancel<Cancel>(self, cancel: Cancel) -> RequestTypeLatency<N, Cancel, M>
+    where
+        Cancel: LatencyModel,
+    {
+        RequestTypeLatency {
+            new: self.new,
+            cancel: Some(cancel),
+            modify: self.modify,
+            req: self.req,
+        }
+    }
+
+    /// Sets the latency model for the modify requests.
+    pub fn with_modify<Modify>(self, modify: Modify) -> RequestTypeLatency<N, C, Modify>
+    where
+        Modify: LatencyModel,
+    {
+        RequestTypeLatency {
+            new: self.new,
+            cancel: self.cancel,
+            modify: Some(modify),
+            req: self.req,
+        }
+    }
+
+    fn model(&mut self, req: Status) -> &mut dyn LatencyModel {
+        match (req, &mut self.cancel, &mut self.modify) {
+            (Status::Canceled, Some(cancel), _) => cancel,
+            (Status::Replaced, _, Some(modify)) => modify,
+            _ => &mut self.new,
+        }
+    }
+}
+
+impl<N, C, M> LatencyModel for RequestTypeLatency<N, C, M>
+where
+    N: LatencyModel,
+    C: LatencyModel,
+    M: LatencyModel,
+{
+    fn entry(&mut self, timestamp: i64, order: &Order) -> i64 {
+        self.model(order.req).entry(timestamp, order)
+    }
+
+    fn response(&mut self, timestamp: i64, order: &Order) -> i64 {
+        self.model(self.req).response(timestamp, order)
+    }
+
+    fn on_feed(&mut self, timestamp: i64, event: &Event) {
+        self.new.on_feed(timestamp, event);
+        if let Some(cancel) = &mut self.cancel {
+            cancel.on_feed(timestamp, event);
+        }
+        if let Some(modify) = &mut self.modify {
+            modify.on_feed(timestamp, event);
+        }
+    }
+
+    fn on_request(&mut self, req: Status) {
+        self.req = req;
+    }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let cancel = match &self.cancel {
+            Some(cancel) => Some(cancel.checkpoint()?),
+            None => None,
+        };
+        let modify = match &self.modify {
+            Some(modify) => Some(modify.checkpoint()?),
+            None => None,
+        };
+        checkpoint::encode(&(self.new.checkpoint()?, cancel, modify))
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let (new, cancel, modify): (Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>) =
+            checkpoint::decode(checkpoint)?;
+        self.new.restore(&new)?;
+        if let (Some(model), Some(cancel)) = (&mut self.cancel, cancel) {
+            model.restore(&cancel)?;
+        }
+        if let (Some(model), Some(modify)) = (&mut self.modify, modify) {
+            model.restore(&modify)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of_val;
+
+    use crate::{
+        backtest::{
+            assettype::LinearAsset,
+            data::{Data, DataPtr, DataSource},
+            fixture::{event, to_data},
+            models::{
+                Activity,
+                ActivityLatency,
+                CommonFees,
+                ConstantLatency,
+                EmpiricalHistogram,
+                LatencyHistogramRow,
+                LatencyModel,
+                LogNormal,
+                RequestTypeLatency,
+                RiskAdverseQueueModel,
+                SpikeMixture,
+                StochasticLatency,
+                TradingValueFeeModel,
+            },
+            Backtest,
+            L2AssetBuilder,
+        },
+        depth::HashMapMarketDepth,
+        prelude::Bot,
+        types::{
+            Event,
+            OrdType,
+            Order,
+            Side,
+            Status,
+            TimeInForce,
+            BUY_EVENT,
+            DEPTH_EVENT,
+            EXCH_EVENT,
+            SELL_EVENT,
+            TRADE_EVENT,
+        },
+    };
+
+    fn histogram(rows: &[LatencyHistogramRow]) -> DataSource<LatencyHistogramRow> {
+        let mut data = unsafe { Data::from_data_ptr(DataPtr::new(size_of_val(rows)), 0) };
+        for (i, row) in rows.iter().enumerate() {
+            data[i] = row.clone();
+        }
+        DataSource::Data(data)
+    }
+
+    #[test]
+    fn stochastic_latency_is_reproducible() {
+        let order = Order::new(1, 100, 1.0, 1.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        let sample = |seed: u64| {
+            let mut latency_model = StochasticLatency::new(
+                SpikeMixture::new(LogNormal::new(13.8, 0.3), LogNormal::new(17.7, 0.5), 0.01),
+                LogNormal::new(13.8, 0.3),
+                seed,
+            );
+            (0..1_000)
+                .map(|i| {
+                    (
+                        latency_model.entry(i, &order),
+                        latency_model.response(i, &order),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let samples = sample(1);
+        assert_eq!(samples, sample(1));
+        assert_ne!(samples, sample(2));
+        assert!(samples.iter().all(|&(entry, response)| entry > 0 && response > 0));
+        // The spikes appear only in the entry latency.
+        assert!(samples.iter().any(|&(entry, _)| entry > 10_000_000));
+        assert!(samples.iter().all(|&(_, response)| response < 10_000_000));
+
+        // The restored model continues drawing the same samples, whatever its seed was.
+        let mut latency_model =
+            StochasticLatency::new(LogNormal::new(13.8, 0.3), LogNormal::new(13.8, 0.3), 1);
+        for i in 0..100 {
+            latency_model.entry(i, &order);
+        }
+        let checkpoint = latency_model.checkpoint().unwrap();
+        let mut restored =
+            StochasticLatency::new(LogNormal::new(13.8, 0.3), LogNormal::new(13.8, 0.3), 2);
+        restored.restore(&checkpoint).unwrap();
+        for i in 100..200 {
+            assert_eq!(restored.entry(i, &order), latency_model.entry(i, &order));
+            assert_eq!(restored.response(i, &order), latency_model.response(i, &order));
+        }
+    }
+
+    #[test]
+    fn sample_from_empirical_histogram() {
+        let histogram = EmpiricalHistogram::new(histogram(&[
+            LatencyHistogramRow {
+                lower: 100,
+                upper: 200,
+                weight: 3.0,
+            },
+            LatencyHistogramRow {
+                lower: 200,
+                upper: 300,
+                weight: 0.0,
+            },
+            LatencyHistogramRow {
+                lower: 1_000,
+                upper: 1_000,
+                weight: 1.0,
+            },
+        ]));
+        let order = Order::new(1, 100, 1.0, 1.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        let mut latency_model = StochasticLatency::new(histogram.clone(), histogram, 0);
+
+        let samples: Vec<_> = (0..1_000).map(|i| latency_model.entry(i, &order)).collect();
+        assert!(samples
+            .iter()
+            .all(|&latency| (100..200).contains(&latency) || latency == 1_000));
+        let num_spikes = samples.iter().filter(|&&latency| latency == 1_000).count();
+        assert!((150..350).contains(&num_spikes));
+
+        assert!(EmpiricalHistogram::build(DataSource::Data(Data::empty())).is_err());
+    }
+
+    #[test]
+    fn activity_dependent_latency() {
+        let feed = |ev: u64, qty: f64| Event {
+            ev: EXCH_EVENT | BUY_EVENT | ev,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty,
+            order_id: 0,
+            ival: 0,
+            fval: 0.0,
+        };
+        let order = Order::new(1, 100, 1.0, 1.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        let mut latency_model = ActivityLatency::new(
+            Activity::TradeVolume,
+            100,
+            vec![(0.0, 10), (10.0, 20), (20.0, 100)],
+            vec![(0.0, 5)],
+        );
+
+        assert_eq!(latency_model.entry(0, &order), 10);
+        latency_model.on_feed(10, &feed(DEPTH_EVENT, 50.0));
+        latency_model.on_feed(20, &feed(TRADE_EVENT, 5.0));
+        latency_model.on_feed(30, &feed(TRADE_EVENT, 10.0));
+        assert_eq!(latency_model.entry(50, &order), 60);
+        assert_eq!(latency_model.response(50, &order), 5);
+        // The first trade falls out of the window.
+        assert_eq!(latency_model.entry(120, &order), 20);
+
+        // The activity within the window is restored from the checkpoint.
+        let checkpoint = latency_model.checkpoint().unwrap();
+        let mut latency_model = ActivityLatency::new(
+            Activity::TradeVolume,
+            100,
+            vec![(0.0, 10), (10.0, 20), (20.0, 100)],
+            vec![(0.0, 5)],
+        );
+        latency_model.restore(&checkpoint).unwrap();
+        assert_eq!(latency_model.entry(120, &order), 20);
+        latency_model.on_feed(125, &feed(TRADE_EVENT, 30.0));
+        assert_eq!(latency_model.entry(125, &order), 100);
+        assert_eq!(latency_model.entry(300, &order), 10);
+    }
+
+    #[test]
+    fn latency_by_request_type() {
+        let mut order = Order::new(1, 100, 1.0, 1.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        let mut latency_model = RequestTypeLatency::new(ConstantLatency::new(10, 20))
+            .with_cancel(ConstantLatency::new(1, 2));
+
+        order.req = Status::New;
+        assert_eq!(latency_model.entry(0, &order), 10);
+        order.req = Status::Canceled;
+        assert_eq!(latency_model.entry(0, &order), 1);
+        // Falls back to the model for the new orders.
+        order.req = Status::Replaced;
+        assert_eq!(latency_model.entry(0, &order), 10);
+
+        order.req = Status::None;
+        latency_model.on_request(Status::Canceled);
+        assert_eq!(latency_model.response(0, &order), 2);
+        latency_model.on_request(Status::None);
+        assert_eq!(latency_model.response(0, &order), 20);
+
+        // The state of each model is restored from the checkpoint.
+        let stochastic = |seed| {
+            StochasticLatency::new(LogNormal::new(13.8, 0.3), LogNormal::new(13.8, 0.3), seed)
+        };
+        let mut latency_model = RequestTypeLatency::new(stochastic(1)).with_cancel(stochastic(2));
+        order.req = Status::Canceled;
+        latency_model.entry(0, &order);
+        let checkpoint = latency_model.checkpoint().unwrap();
+        let mut restored = RequestTypeLatency::new(stochastic(1)).with_cancel(stochastic(2));
+        restored.restore(&checkpoint).unwrap();
+        for req in [Status::New, Status::Canceled] {
+            order.req = req;
+            assert_eq!(restored.entry(0, &order), latency_model.entry(0, &order));
+        }
+    }
+
+    #[test]
+    fn cancel_with_faster_latency() {
+        let data = to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ]);
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                L2AssetBuilder::new()
+                    .data(vec![DataSource::Data(data)])
+                    .latency_model(
+                        RequestTypeLatency::new(ConstantLatency::new(10, 10))
+                            .with_cancel(ConstantLatency::new(1, 2)),
+                    )
+                    .asset_type(LinearAsset::new(1.0))
+                    .fee_model(TradingValueFeeModel::new(CommonFees::new(-0.0001, 0.0005)))
+                    .queue_model(RiskAdverseQueueModel::new())
+                    .depth(|| HashMapMarketDepth::new(1.0, 1.0))
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        hbt.elapse(1_000).unwrap();
+        let timestamp = hbt.current_timestamp();
+        hbt.submit_buy_order(0, 1, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.current_timestamp(), timestamp + 20);
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.exch_timestamp, timestamp + 10);
+
+        // The cancel request and its response travel the faster path.
+        let timestamp = hbt.current_timestamp();
+        hbt.cancel(0, 1, true).unwrap();
+        assert_eq!(hbt.current_timestamp(), timestamp + 3);
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Canceled);
+        assert_eq!(order.exch_timestamp, timestamp + 1);
+    }
+}
@@ -0,0 +1,114 @@
+/// Provides the margin requirements of a position, which the exchange model enforces by rejecting
+/// orders that cannot be covered and by liquidating positions that can no longer be maintained.
+pub trait MarginModel: Send {
+    /// Returns the collateral deposited in the account, in the same unit as the balance. It is
+    /// added to the equity when checking the margin requirements.
+    fn collateral(&self) -> f64;
+
+    /// Returns the initial margin required to open a position of the given notional value. An
+    /// initial margin of zero never rejects orders.
+    fn initial_margin(&self, notional: f64) -> f64;
+
+    /// Returns the maintenance margin required to keep a position of the given notional value. The
+    /// position is liquidated once the collateral plus the equity falls below it, after all open
+    /// orders are canceled. A maintenance margin of zero never triggers liquidation.
+    fn maintenance_margin(&self, notional: f64) -> f64;
+}
+
+/// Imposes no margin requirements. Orders are never rejected for margin and positions are never
+/// liquidated, which is the default.
+#[derive(Clone, Default)]
+pub struct NoMarginModel;
+
+impl MarginModel for NoMarginModel {
+    fn collateral(&self) -> f64 {
+        0.0
+    }
+
+    fn initial_margin(&self, _notional: f64) -> f64 {
+        0.0
+    }
+
+    fn maintenance_margin(&self, _notional: f64) -> f64 {
+        0.0
+    }
+}
+
+/// A leverage tier, also known as a risk limit or a leverage bracket, which applies to positions
+/// with a notional value up to `notional_cap`.
+#[derive(Clone, Debug)]
+pub struct LeverageTier {
+    /// The upper bound of the notional value of the positions that the tier applies to.
+    pub notional_cap: f64,
+    /// The maximum leverage allowed in the tier.
+    pub max_leverage: f64,
+    /// The maintenance margin rate of the tier.
+    pub maintenance_margin_rate: f64,
+}
+
+/// Margin requirements based on leverage tiers, as used by most crypto derivatives exchanges. The
+/// larger the position is, the lower the maximum leverage and the higher the maintenance margin
+/// rate become.
+///
+/// The maintenance margin is calculated in the same way as Binance Futures, deducting the
+/// maintenance amount of the tier so that the maintenance margin is continuous across the tiers.
+#[derive(Clone)]
+pub struct TieredMarginModel {
+    collateral: f64,
+    leverage: f64,
+    // The tiers, each with its maintenance amount.
+    tiers: Vec<(LeverageTier, f64)>,
+}
+
+impl TieredMarginModel {
+    /// Constructs `TieredMarginModel`.
+    ///
+    /// * `collateral` - The collateral deposited in the account.
+    /// * `leverage` - The leverage set for the position. It is capped by the tier's maximum leverage.
+    /// * `tiers` - The leverage tiers in ascending order of the notional cap. Positions exceeding the
+    ///   cap of the last tier are subject to the last tier.
+    pub fn new(collateral: f64, leverage: f64, tiers: Vec<LeverageTier>) -> Self {
+        if tiers.is_empty() {
+            panic!("`tiers` cannot be empty.");
+        }
+        let mut maintenance_amount = 0.0;
+        let mut prev: Option<&LeverageTier> = None;
+        let mut tiers_ = Vec::with_capacity(tiers.len());
+        for tier in &tiers {
+            if let Some(prev) = prev {
+                maintenance_amount += prev.notional_cap
+                    * (tier.maintenance_margin_rate - prev.maintenance_margin_rate);
+            }
+            tiers_.push((tier.clone(), maintenance_amount));
+            prev = Some(tier);
+        }
+        Self {
+            collateral,
+            leverage,
+            tiers: tiers_,
+        }
+    }
+
+    fn tier(&self, notional: f64) -> &(LeverageTier, f64) {
+        self.tiers
+            .iter()
+            .find(|(tier, _)| notional <= tier.notional_cap)
+            .unwrap_or_else(|| self.tiers.last().unwrap())
+    }
+}
+
+impl MarginModel for TieredMarginModel {
+    fn collateral(&self) -> f64 {
+        self.collateral
+    }
+
+    fn initial_margin(&self, notional: f64) -> f64 {
+        let (tier, _) = self.tier(notional);
+        notional / self.leverage.min(tier.max_leverage)
+    }
+
+    fn maintenance_margin(&self, notional: f64) -> f64 {
+        let (tier, maintenance_amount) = self.tier(notional);
+        notional * tier.maintenance_margin_rate - maintenance_amount
+    }
+}
@@ -3,10 +3,15 @@
 //! Please find more details in the documents below.
 //! * [Latency Models](https://hftbacktest.readthedocs.io/en/latest/latency_models.html)
 //! * [Order Fill](https://hftbacktest.readthedocs.io/en/latest/order_fill.html)
+mod fault;
 mod fee;
 mod latency;
+mod margin;
 mod queue;
+mod ratelimit;
+mod rng;
 
+pub use fault::FaultInjector;
 pub use fee::{
     CommonFees,
     DirectionalFees,
@@ -15,7 +20,22 @@ pub use fee::{
     TradingQtyFeeModel,
     TradingValueFeeModel,
 };
-pub use latency::{ConstantLatency, IntpOrderLatency, LatencyModel, OrderLatencyRow};
+pub use latency::{
+    Activity,
+    ActivityLatency,
+    ConstantLatency,
+    EmpiricalHistogram,
+    IntpOrderLatency,
+    LatencyDistribution,
+    LatencyHistogramRow,
+    LatencyModel,
+    LogNormal,
+    OrderLatencyRow,
+    RequestTypeLatency,
+    SpikeMixture,
+    StochasticLatency,
+};
+pub use margin::{LeverageTier, MarginModel, NoMarginModel, TieredMarginModel};
 pub use queue::{
     L3FIFOQueueModel,
     L3QueueModel,
@@ -30,3 +50,4 @@ pub use queue::{
     QueuePos,
     RiskAdverseQueueModel,
 };
+pub use ratelimit::{RateLimit, RateLimitModel, SlidingWindowRateLimit};
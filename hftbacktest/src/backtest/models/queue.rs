@@ -4,8 +4,10 @@ use std::{
     marker::PhantomData,
 };
 
+use bincode::{Decode, Encode};
+
 use crate::{
-    backtest::BacktestError,
+    backtest::{checkpoint, BacktestError},
     depth::{MarketDepth, INVALID_MAX, INVALID_MIN},
     types::{
         AnyClone,
@@ -13,16 +15,20 @@ use crate::{
         OrdType,
         Order,
         OrderId,
+        PositionSide,
+        RejectReason,
         Side,
         Status,
         TimeInForce,
+        TrailingUnit,
+        TriggerSource,
         BUY_EVENT,
         SELL_EVENT,
     },
 };
 
 /// Provides an estimation of the order's queue position.
-pub trait QueueModel<MD>
+pub trait QueueModel<MD>: Send
 where
     MD: MarketDepth,
 {
@@ -37,6 +43,32 @@ where
     fn depth(&self, order: &mut Order, prev_qty: f64, new_qty: f64, depth: &MD);
 
     fn is_filled(&self, order: &Order, depth: &MD) -> f64;
+
+    /// Encodes the estimation values stored in [`Order::q`] so that they can be saved in a
+    /// checkpoint.
+    fn encode_q(&self, order: &Order) -> Result<Vec<u8>, BacktestError>;
+
+    /// Decodes the estimation values encoded by [`encode_q`](QueueModel::encode_q) into
+    /// [`Order::q`].
+    fn decode_q(&self, order: &mut Order, bytes: &[u8]) -> Result<(), BacktestError>;
+
+    /// Adjusts the estimation values when the exchange model accepts a modification of the order.
+    /// `order` already holds the modified price and quantity. By default, the order loses its queue
+    /// priority if the price changes or the leaves quantity increases, and keeps it if only the
+    /// quantity decreases.
+    fn amend(&self, order: &mut Order, prev_price_tick: i64, prev_leaves_qty: f64, depth: &MD) {
+        if order.price_tick != prev_price_tick || order.leaves_qty > prev_leaves_qty {
+            self.new_order(order, depth);
+        }
+    }
+
+    /// Adjusts the estimation values when the iceberg order displays its next slice after the
+    /// current one is fully executed, and when the market depth changes at the price of the hidden
+    /// order. By default, the order joins the back of the queue, behind all the quantity displayed
+    /// at the price.
+    fn refresh(&self, order: &mut Order, depth: &MD) {
+        self.new_order(order, depth);
+    }
 }
 
 /// Provides a conservative queue position model, where your order's queue position advances only
@@ -91,10 +123,21 @@ where
             0.0
         }
     }
+
+    fn encode_q(&self, order: &Order) -> Result<Vec<u8>, BacktestError> {
+        let front_q_qty = order.q.as_any().downcast_ref::<f64>().unwrap();
+        checkpoint::encode(front_q_qty)
+    }
+
+    fn decode_q(&self, order: &mut Order, bytes: &[u8]) -> Result<(), BacktestError> {
+        let front_q_qty: f64 = checkpoint::decode(bytes)?;
+        order.q = Box::new(front_q_qty);
+        Ok(())
+    }
 }
 
 /// Stores the values needed for queue position estimation and adjustment for [`ProbQueueModel`].
-#[derive(Clone)]
+#[derive(Clone, Decode, Encode)]
 pub struct QueuePos {
     front_q_qty: f64,
     cum_trade_qty: f64,
@@ -120,7 +163,7 @@ impl Default for QueuePos {
 }
 
 /// Provides the probability of a decrease behind the order's queue position.
-pub trait Probability {
+pub trait Probability: Send {
     /// Returns the probability based on the quantity ahead and behind the order.
     fn prob(&self, front: f64, back: f64) -> f64;
 }
@@ -210,6 +253,17 @@ where
             0.0
         }
     }
+
+    fn encode_q(&self, order: &Order) -> Result<Vec<u8>, BacktestError> {
+        let q = order.q.as_any().downcast_ref::<QueuePos>().unwrap();
+        checkpoint::encode(q)
+    }
+
+    fn decode_q(&self, order: &mut Order, bytes: &[u8]) -> Result<(), BacktestError> {
+        let q: QueuePos = checkpoint::decode(bytes)?;
+        order.q = Box::new(q);
+        Ok(())
+    }
 }
 
 /// This probability model uses a power function `f(x) = x ** n` to adjust the probability which is
@@ -327,7 +381,7 @@ impl Probability for PowerProbQueueFunc3 {
 
 /// Represents the order source for the Level 3 Market-By-Order queue model, which is stored in
 /// [`order.q`](crate::types::Order::q)
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Decode, Encode)]
 enum L3OrderSource {
     /// Represents an order originating from the market feed.
     MarketFeed,
@@ -373,12 +427,33 @@ impl L3Order for Order {
     }
 }
 
+/// Appends the order to the queue. The hidden backtest orders yield their priority to the displayed
+/// orders, so they always remain at the back of the queue.
+fn enqueue(queue: &mut VecDeque<Order>, order: Order) {
+    let mut i = queue.len();
+    if !order.is_hidden() {
+        while i > 0 && queue[i - 1].is_backtest_order() && queue[i - 1].is_hidden() {
+            i -= 1;
+        }
+    }
+    queue.insert(i, order);
+}
+
 /// Provides a model to determine whether the backtest order is filled, accounting for the queue
 /// position based on L3 Market-By-Order data.
-pub trait L3QueueModel<MD> {
+pub trait L3QueueModel<MD>: Send {
     /// Returns `true` if the queue contains a backtest order for the order ID.
     fn contains_backtest_order(&self, order_id: OrderId) -> bool;
 
+    /// Returns the backtest order for the order ID, if the queue contains it.
+    fn backtest_order(&self, order_id: OrderId) -> Option<&Order>;
+
+    /// Returns the number of backtest orders in the queue.
+    fn num_backtest_orders(&self) -> usize;
+
+    /// Returns all backtest orders in the queue.
+    fn backtest_orders(&self) -> Vec<&Order>;
+
     /// Invoked when the best bid is updated.
     /// Returns the ask backtest orders that are filled by crossing the best bid.
     fn on_best_bid_update(
@@ -448,6 +523,21 @@ pub trait L3QueueModel<MD> {
         depth: &MD,
     ) -> Result<Vec<Order>, BacktestError>;
 
+    /// Invoked when an order is filled from the market feed, in the exchange model that simulates
+    /// partial fills.
+    ///
+    /// Unlike [`fill_market_feed_order`](L3QueueModel::fill_market_feed_order), the backtest orders
+    /// placed before the filled market-feed order at the same price are executed only up to the
+    /// fill quantity, since it represents the liquidity-taking quantity that reaches the price
+    /// level. Returns the executed backtest orders along with their executed quantities. The
+    /// backtest orders that are partially executed remain in the queue.
+    fn partially_fill_market_feed_order<const DELETE: bool>(
+        &mut self,
+        order_id: OrderId,
+        order: &Event,
+        depth: &MD,
+    ) -> Result<Vec<(Order, f64)>, BacktestError>;
+
     /// Invoked when a clear order message is received. Returns the expired orders due to the clear
     /// message.
     ///
@@ -464,6 +554,13 @@ pub trait L3QueueModel<MD> {
     /// Due to these challenges, HftBacktest opts to clear all backtest orders upon receiving a
     /// clear message, even though this may differ from the exchange's actual behavior.
     fn clear_orders(&mut self, side: Side) -> Vec<Order>;
+
+    /// Returns the state of the queues, including both the backtest orders and the market-feed
+    /// orders, as bytes so that it can be saved in a checkpoint.
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError>;
+
+    /// Restores the state of the queues saved by [`checkpoint`](L3QueueModel::checkpoint).
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError>;
 }
 
 /// This provides a Level 3 Market-By-Order queue model for backtesting in a FIFO manner. This means
@@ -593,6 +690,29 @@ where
         self.backtest_orders.contains_key(&order_id)
     }
 
+    fn num_backtest_orders(&self) -> usize {
+        self.backtest_orders.len()
+    }
+
+    fn backtest_orders(&self) -> Vec<&Order> {
+        self.backtest_orders
+            .keys()
+            .filter_map(|order_id| L3QueueModel::<MD>::backtest_order(self, *order_id))
+            .collect()
+    }
+
+    fn backtest_order(&self, order_id: OrderId) -> Option<&Order> {
+        let (side, order_price_tick) = self.backtest_orders.get(&order_id)?;
+        let queue = match side {
+            Side::Buy => self.bid_queue.get(order_price_tick)?,
+            Side::Sell => self.ask_queue.get(order_price_tick)?,
+            Side::None | Side::Unsupported => unreachable!(),
+        };
+        queue
+            .iter()
+            .find(|order| order.is_backtest_order() && order.order_id == order_id)
+    }
+
     fn on_best_bid_update(
         &mut self,
         prev_best_tick: i64,
@@ -630,7 +750,7 @@ where
             Side::None | Side::Unsupported => unreachable!(),
         };
 
-        queue.push_back(order);
+        enqueue(queue, order);
 
         match self.backtest_orders.entry(order_id) {
             Entry::Occupied(_) => Err(BacktestError::OrderIdExist),
@@ -657,7 +777,7 @@ where
             unreachable!()
         };
 
-        queue.push_back(Order {
+        let feed_order = Order {
             qty: order.qty,
             leaves_qty: order.qty,
             price_tick: order_price_tick,
@@ -675,7 +795,18 @@ where
             req: Status::None,
             status: Status::None,
             time_in_force: TimeInForce::GTC,
-        });
+            reject_reason: RejectReason::None,
+            trigger_price_tick: 0,
+            trailing_offset: 0.0,
+            trailing_unit: TrailingUnit::Ticks,
+            trigger_source: TriggerSource::LastPrice,
+            triggered: false,
+            expire_timestamp: 0,
+            display_qty: f64::INFINITY,
+            reduce_only: false,
+            position_side: PositionSide::Both,
+        };
+        enqueue(queue, feed_order);
 
         match self.mkt_feed_orders.entry(order_id) {
             Entry::Occupied(_) => Err(BacktestError::OrderIdExist),
@@ -801,9 +932,9 @@ where
                                 *order_price_tick = order.price_tick;
                                 let queue_ =
                                     self.bid_queue.entry(prev_order.price_tick).or_default();
-                                queue_.push_back(prev_order);
+                                enqueue(queue_, prev_order);
                             } else {
-                                queue.push_back(prev_order);
+                                enqueue(queue, prev_order);
                             }
                         } else {
                             order_in_q.leaves_qty = order.leaves_qty;
@@ -837,9 +968,9 @@ where
                                 *order_price_tick = order.price_tick;
                                 let queue_ =
                                     self.ask_queue.entry(prev_order.price_tick).or_default();
-                                queue_.push_back(prev_order);
+                                enqueue(queue_, prev_order);
                             } else {
-                                queue.push_back(prev_order);
+                                enqueue(queue, prev_order);
                             }
                         } else {
                             order_in_q.leaves_qty = order.leaves_qty;
@@ -895,9 +1026,9 @@ where
                                 *order_price_tick = new_price_tick;
 
                                 let queue_ = self.bid_queue.entry(*order_price_tick).or_default();
-                                queue_.push_back(prev_order);
+                                enqueue(queue_, prev_order);
                             } else {
-                                queue.push_back(prev_order);
+                                enqueue(queue, prev_order);
                             }
                         } else {
                             order_in_q.leaves_qty = order.qty;
@@ -934,9 +1065,9 @@ where
                                 *order_price_tick = new_price_tick;
 
                                 let queue_ = self.ask_queue.entry(*order_price_tick).or_default();
-                                queue_.push_back(prev_order);
+                                enqueue(queue_, prev_order);
                             } else {
-                                queue.push_back(prev_order);
+                                enqueue(queue, prev_order);
                             }
                         } else {
                             order_in_q.leaves_qty = order.qty;
@@ -1062,6 +1193,104 @@ where
         }
     }
 
+    fn partially_fill_market_feed_order<const DELETE: bool>(
+        &mut self,
+        order_id: OrderId,
+        order: &Event,
+        depth: &MD,
+    ) -> Result<Vec<(Order, f64)>, BacktestError> {
+        let (side, order_price_tick) = if DELETE {
+            self.mkt_feed_orders
+                .remove(&order_id)
+                .ok_or(BacktestError::OrderNotFound)?
+        } else {
+            *self
+                .mkt_feed_orders
+                .get(&order_id)
+                .ok_or(BacktestError::OrderNotFound)?
+        };
+        let exec_price_tick = (order.px / depth.tick_size()).round() as i64;
+        let lot_size = depth.lot_size();
+
+        let mut filled = Vec::new();
+        let queue = match side {
+            Side::Buy => {
+                // The backtest bid orders above the price of the filled market-feed bid order are
+                // fully filled.
+                if exec_price_tick < depth.best_bid_tick() {
+                    let f =
+                        self.fill_bid_between::<false>(depth.best_bid_tick(), exec_price_tick + 1);
+                    filled.extend(f.into_iter().map(|order| {
+                        let exec_qty = order.leaves_qty;
+                        (order, exec_qty)
+                    }));
+                }
+                self.bid_queue.get_mut(&order_price_tick).unwrap()
+            }
+            Side::Sell => {
+                // The backtest ask orders below the price of the filled market-feed ask order are
+                // fully filled.
+                if exec_price_tick > depth.best_ask_tick() {
+                    let f =
+                        self.fill_ask_between::<false>(depth.best_ask_tick(), exec_price_tick - 1);
+                    filled.extend(f.into_iter().map(|order| {
+                        let exec_qty = order.leaves_qty;
+                        (order, exec_qty)
+                    }));
+                }
+                self.ask_queue.get_mut(&order_price_tick).unwrap()
+            }
+            Side::None | Side::Unsupported => unreachable!(),
+        };
+
+        // The backtest orders in the queue, placed before the filled market-feed order, are filled
+        // in FIFO order until the fill quantity is exhausted.
+        let mut remaining_qty = order.qty;
+        let mut i = 0;
+        while i < queue.len() {
+            let order_in_q = queue.get_mut(i).unwrap();
+            match order_in_q.order_source() {
+                L3OrderSource::MarketFeed if order_in_q.order_id == order_id => {
+                    if DELETE {
+                        queue.remove(i);
+                    }
+                    break;
+                }
+                L3OrderSource::MarketFeed => {
+                    i += 1;
+                }
+                L3OrderSource::Backtest => {
+                    // An iceberg order executes only up to its displayed slice.
+                    let slice_qty = order_in_q.slice_qty(lot_size);
+                    let exec_qty = slice_qty.min(remaining_qty);
+                    if (exec_qty / lot_size).round() <= 0.0 {
+                        i += 1;
+                        continue;
+                    }
+                    remaining_qty -= exec_qty;
+
+                    if ((order_in_q.leaves_qty - exec_qty) / lot_size).round() > 0.0 {
+                        filled.push((order_in_q.clone(), exec_qty));
+                        order_in_q.leaves_qty -= exec_qty;
+                        order_in_q.status = Status::PartiallyFilled;
+                        if ((slice_qty - exec_qty) / lot_size).round() <= 0.0 {
+                            // The next slice is displayed at the back of the queue.
+                            let order = queue.remove(i).unwrap();
+                            enqueue(queue, order);
+                        } else {
+                            i += 1;
+                        }
+                    } else {
+                        let order = queue.remove(i).unwrap();
+                        self.backtest_orders.remove(&order.order_id);
+                        filled.push((order, exec_qty));
+                    }
+                }
+            }
+        }
+        Ok(filled)
+    }
+
     fn clear_orders(&mut self, side: Side) -> Vec<Order> {
         match side {
             Side::Buy => {
@@ -1115,6 +1344,122 @@ where
             }
         }
     }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        checkpoint::encode(&L3FIFOQueueCheckpoint {
+            bid_queue: Self::save_queue(&self.bid_queue),
+            ask_queue: Self::save_queue(&self.ask_queue),
+        })
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let checkpoint: L3FIFOQueueCheckpoint = checkpoint::decode(checkpoint)?;
+        self.backtest_orders.clear();
+        self.mkt_feed_orders.clear();
+        self.bid_queue = self.restore_queue(Side::Buy, checkpoint.bid_queue);
+        self.ask_queue = self.restore_queue(Side::Sell, checkpoint.ask_queue);
+        Ok(())
+    }
+}
+
+/// The state of [`L3FIFOQueueModel`] saved in a checkpoint, which consists of the orders in each
+/// queue along with their order sources, keyed by the price in ticks.
+#[derive(Decode, Encode)]
+struct L3FIFOQueueCheckpoint {
+    bid_queue: Vec<(i64, Vec<(Order, L3OrderSource)>)>,
+    ask_queue: Vec<(i64, Vec<(Order, L3OrderSource)>)>,
+}
+
+impl L3FIFOQueueModel {
+    fn save_queue(
+        queue: &HashMap<i64, VecDeque<Order>>,
+    ) -> Vec<(i64, Vec<(Order, L3OrderSource)>)> {
+        let mut saved: Vec<_> = queue
+            .iter()
+            .map(|(&price_tick, q)| {
+                (
+                    price_tick,
+                    q.iter()
+                        .map(|order| (order.clone(), order.order_source()))
+                        .collect(),
+                )
+            })
+            .collect();
+        saved.sort_by_key(|(price_tick, _)| *price_tick);
+        saved
+    }
+
+    fn restore_queue(
+        &mut self,
+        side: Side,
+        saved: Vec<(i64, Vec<(Order, L3OrderSource)>)>,
+    ) -> HashMap<i64, VecDeque<Order>> {
+        let mut queue = HashMap::new();
+        for (price_tick, orders) in saved {
+            let mut q = VecDeque::with_capacity(orders.len());
+            for (mut order, source) in orders {
+                match source {
+                    L3OrderSource::MarketFeed => {
+                        self.mkt_feed_orders
+                            .insert(order.order_id, (side, price_tick));
+                    }
+                    L3OrderSource::Backtest => {
+                        self.backtest_orders
+                            .insert(order.order_id, (side, price_tick));
+                    }
+                }
+                order.q = Box::new(source);
+                q.push_back(order);
+            }
+            queue.insert(price_tick, q);
+        }
+        queue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::models::{QueueModel, RiskAdverseQueueModel},
+        prelude::{HashMapMarketDepth, L2MarketDepth, OrdType, Order, Side, TimeInForce},
+    };
+
+    fn front_q_qty(order: &Order) -> f64 {
+        *order.q.as_any().downcast_ref::<f64>().unwrap()
+    }
+
+    #[test]
+    fn amend_keeps_priority_on_qty_decrease() {
+        let mut depth = HashMapMarketDepth::new(1.0, 1.0);
+        depth.update_bid_depth(100.0, 10.0, 0);
+        depth.update_bid_depth(99.0, 20.0, 0);
+        let qm = RiskAdverseQueueModel::new();
+
+        let mut order = Order::new(1, 100, 1.0, 5.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        qm.new_order(&mut order, &depth);
+        qm.trade(&mut order, 4.0, &depth);
+        assert_eq!(front_q_qty(&order), 6.0);
+
+        // Decreasing the quantity keeps the queue position.
+        order.qty = 3.0;
+        order.leaves_qty = 3.0;
+        qm.amend(&mut order, 100, 5.0, &depth);
+        assert_eq!(front_q_qty(&order), 6.0);
+
+        // Increasing the quantity sends the order to the back of the queue.
+        order.qty = 4.0;
+        order.leaves_qty = 4.0;
+        qm.amend(&mut order, 100, 3.0, &depth);
+        assert_eq!(front_q_qty(&order), 10.0);
+
+        // Changing the price sends the order to the back of the queue at the new price.
+        qm.trade(&mut order, 4.0, &depth);
+        order.price_tick = 99;
+        order.qty = 2.0;
+        order.leaves_qty = 2.0;
+        qm.amend(&mut order, 100, 4.0, &depth);
+        assert_eq!(front_q_qty(&order), 20.0);
+    }
 }
 
 #[cfg(test)]
@@ -1127,9 +1472,13 @@ mod l3_tests {
             L3MarketDepth,
             OrdType,
             Order,
+            PositionSide,
+            RejectReason,
             Side,
             Status,
             TimeInForce,
+            TrailingUnit,
+            TriggerSource,
         },
         types::{ADD_ORDER_EVENT, BUY_EVENT, EXCH_EVENT, FILL_EVENT, SELL_EVENT},
     };
@@ -1189,6 +1538,16 @@ mod l3_tests {
                 status: Status::None,
                 side: Side::Buy,
                 time_in_force: TimeInForce::GTC,
+                reject_reason: RejectReason::None,
+                trigger_price_tick: 0,
+                trailing_offset: 0.0,
+                trailing_unit: TrailingUnit::Ticks,
+                trigger_source: TriggerSource::LastPrice,
+                triggered: false,
+                expire_timestamp: 0,
+                display_qty: f64::INFINITY,
+                reduce_only: false,
+                position_side: PositionSide::Both,
             },
             &depth,
         )
@@ -1223,6 +1582,16 @@ mod l3_tests {
                 status: Status::None,
                 side: Side::Sell,
                 time_in_force: TimeInForce::GTC,
+                reject_reason: RejectReason::None,
+                trigger_price_tick: 0,
+                trailing_offset: 0.0,
+                trailing_unit: TrailingUnit::Ticks,
+                trigger_source: TriggerSource::LastPrice,
+                triggered: false,
+                expire_timestamp: 0,
+                display_qty: f64::INFINITY,
+                reduce_only: false,
+                position_side: PositionSide::Both,
             },
             &depth,
         )
@@ -1279,6 +1648,16 @@ mod l3_tests {
                 status: Status::None,
                 side: Side::Buy,
                 time_in_force: TimeInForce::GTC,
+                reject_reason: RejectReason::None,
+                trigger_price_tick: 0,
+                trailing_offset: 0.0,
+                trailing_unit: TrailingUnit::Ticks,
+                trigger_source: TriggerSource::LastPrice,
+                triggered: false,
+                expire_timestamp: 0,
+                display_qty: f64::INFINITY,
+                reduce_only: false,
+                position_side: PositionSide::Both,
             },
             &depth,
         )
@@ -1353,4 +1732,265 @@ mod l3_tests {
             )
         );
     }
+
+    #[test]
+    fn partially_fill_in_queue() {
+        let mut depth = HashMapMarketDepth::new(1.0, 1.0);
+        let mut qm = L3FIFOQueueModel::new();
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | ADD_ORDER_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty: 1.0,
+            order_id: 1,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        depth
+            .add_buy_order(ev.order_id, ev.px, ev.qty, ev.exch_ts)
+            .unwrap();
+        qm.add_market_feed_order(&ev, &depth).unwrap();
+
+        qm.add_backtest_order(
+            Order {
+                qty: 5.0,
+                leaves_qty: 5.0,
+                exec_qty: 0.0,
+                exec_price_tick: 0,
+                price_tick: 100,
+                tick_size: 1.0,
+                exch_timestamp: 0,
+                local_timestamp: 0,
+                order_id: 1,
+                q: Box::new(()),
+                maker: false,
+                order_type: OrdType::Limit,
+                req: Status::None,
+                status: Status::New,
+                side: Side::Buy,
+                time_in_force: TimeInForce::GTC,
+                reject_reason: RejectReason::None,
+                trigger_price_tick: 0,
+                trailing_offset: 0.0,
+                trailing_unit: TrailingUnit::Ticks,
+                trigger_source: TriggerSource::LastPrice,
+                triggered: false,
+                expire_timestamp: 0,
+                display_qty: f64::INFINITY,
+                reduce_only: false,
+                position_side: PositionSide::Both,
+            },
+            &depth,
+        )
+        .unwrap();
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | ADD_ORDER_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty: 10.0,
+            order_id: 2,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        depth
+            .add_buy_order(ev.order_id, ev.px, ev.qty, ev.exch_ts)
+            .unwrap();
+        qm.add_market_feed_order(&ev, &depth).unwrap();
+
+        // The market-feed order ahead of the backtest order is filled.
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | FILL_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty: 1.0,
+            order_id: 1,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        let filled = qm
+            .partially_fill_market_feed_order::<true>(1, &ev, &depth)
+            .unwrap();
+        assert_eq!(filled.len(), 0);
+
+        // The market-feed order behind the backtest order is filled by the quantity less than the
+        // backtest order's quantity.
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | FILL_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty: 2.0,
+            order_id: 2,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        let filled = qm
+            .partially_fill_market_feed_order::<false>(2, &ev, &depth)
+            .unwrap();
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].0.order_id, 1);
+        assert_eq!(filled[0].1, 2.0);
+        assert!(
+            <L3FIFOQueueModel as L3QueueModel<HashMapMarketDepth>>::contains_backtest_order(&qm, 1)
+        );
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | FILL_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty: 8.0,
+            order_id: 2,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        let filled = qm
+            .partially_fill_market_feed_order::<false>(2, &ev, &depth)
+            .unwrap();
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].1, 3.0);
+        assert!(
+            !<L3FIFOQueueModel as L3QueueModel<HashMapMarketDepth>>::contains_backtest_order(
+                &qm, 1
+            )
+        );
+    }
+
+    #[test]
+    fn modify_reevaluates_queue_position() {
+        let mut depth = HashMapMarketDepth::new(1.0, 1.0);
+        let mut qm = L3FIFOQueueModel::new();
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | ADD_ORDER_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 100.0,
+            qty: 1.0,
+            order_id: 1,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        depth
+            .add_buy_order(ev.order_id, ev.px, ev.qty, ev.exch_ts)
+            .unwrap();
+        qm.add_market_feed_order(&ev, &depth).unwrap();
+
+        let mut order = Order {
+            qty: 5.0,
+            leaves_qty: 5.0,
+            exec_qty: 0.0,
+            exec_price_tick: 0,
+            price_tick: 100,
+            tick_size: 1.0,
+            exch_timestamp: 0,
+            local_timestamp: 0,
+            order_id: 1,
+            q: Box::new(()),
+            maker: false,
+            order_type: OrdType::Limit,
+            req: Status::None,
+            status: Status::New,
+            side: Side::Buy,
+            time_in_force: TimeInForce::GTC,
+            reject_reason: RejectReason::None,
+            trigger_price_tick: 0,
+            trailing_offset: 0.0,
+            trailing_unit: TrailingUnit::Ticks,
+            trigger_source: TriggerSource::LastPrice,
+            triggered: false,
+            expire_timestamp: 0,
+            display_qty: f64::INFINITY,
+            reduce_only: false,
+            position_side: PositionSide::Both,
+        };
+        qm.add_backtest_order(order.clone(), &depth).unwrap();
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | ADD_ORDER_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 99.0,
+            qty: 1.0,
+            order_id: 2,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        depth
+            .add_buy_order(ev.order_id, ev.px, ev.qty, ev.exch_ts)
+            .unwrap();
+        qm.add_market_feed_order(&ev, &depth).unwrap();
+
+        // The backtest order moves to the back of the queue at the new price.
+        order.price_tick = 99;
+        qm.modify_backtest_order(1, order.clone(), &depth).unwrap();
+        assert_eq!(
+            <L3FIFOQueueModel as L3QueueModel<HashMapMarketDepth>>::backtest_order(&qm, 1)
+                .unwrap()
+                .price_tick,
+            99
+        );
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | ADD_ORDER_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 99.0,
+            qty: 1.0,
+            order_id: 3,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        depth
+            .add_buy_order(ev.order_id, ev.px, ev.qty, ev.exch_ts)
+            .unwrap();
+        qm.add_market_feed_order(&ev, &depth).unwrap();
+
+        // Decreasing the quantity keeps the queue position ahead of the market-feed order 3.
+        order.qty = 2.0;
+        order.leaves_qty = 2.0;
+        qm.modify_backtest_order(1, order.clone(), &depth).unwrap();
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | FILL_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 99.0,
+            qty: 1.0,
+            order_id: 2,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        let filled = qm.fill_market_feed_order::<false>(2, &ev, &depth).unwrap();
+        assert_eq!(filled.len(), 0);
+
+        let ev = Event {
+            ev: EXCH_EVENT | BUY_EVENT | FILL_EVENT,
+            exch_ts: 0,
+            local_ts: 0,
+            px: 99.0,
+            qty: 1.0,
+            order_id: 3,
+            ival: 0,
+            fval: 0.0,
+        };
+
+        let filled = qm.fill_market_feed_order::<false>(3, &ev, &depth).unwrap();
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].leaves_qty, 2.0);
+    }
 }
@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+
+use crate::{
+    backtest::{checkpoint, BacktestError},
+    types::{Order, RejectReason, Status},
+};
+
+/// Provides the request rate limits and the order-count limits that the exchange enforces by
+/// rejecting the requests that exceed them.
+pub trait RateLimitModel: Send {
+    /// Checks the request when the exchange receives it, and returns the reason if the request is
+    /// rejected. The request that passes the check is counted against the limits.
+    ///
+    /// * `order` - The order with the request in `order.req`, which is one of [`Status::New`],
+    ///   [`Status::Canceled`] and [`Status::Replaced`].
+    /// * `timestamp` - The time at which the exchange receives the request.
+    /// * `num_open_orders` - The number of open orders in the exchange.
+    fn check(
+        &mut self,
+        order: &Order,
+        timestamp: i64,
+        num_open_orders: usize,
+    ) -> Result<(), RejectReason>;
+
+    /// Returns the state of the limits, such as the requests counted within the windows, as bytes
+    /// so that it can be saved in a checkpoint.
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError>;
+
+    /// Restores the state of the limits saved by [`checkpoint`](RateLimitModel::checkpoint).
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError>;
+}
+
+/// A request rate limit over a sliding window.
+#[derive(Clone, Debug)]
+pub struct RateLimit {
+    /// The length of the window in nanoseconds.
+    pub window: i64,
+    /// The maximum total weight of the requests within the window.
+    pub limit: u64,
+    /// The weight of a new order request.
+    pub new_weight: u64,
+    /// The weight of a cancel request.
+    pub cancel_weight: u64,
+    /// The weight of a modify request.
+    pub modify_weight: u64,
+}
+
+impl RateLimit {
+    fn weight(&self, req: Status) -> u64 {
+        match req {
+            Status::New => self.new_weight,
+            Status::Canceled => self.cancel_weight,
+            Status::Replaced => self.modify_weight,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Window {
+    limit: RateLimit,
+    // (timestamp, weight) of the requests within the window.
+    requests: VecDeque<(i64, u64)>,
+    used: u64,
+}
+
+impl Window {
+    fn expire(&mut self, timestamp: i64) {
+        while let Some(&(req_timestamp, weight)) = self.requests.front() {
+            if req_timestamp > timestamp - self.limit.window {
+                break;
+            }
+            self.requests.pop_front();
+            self.used -= weight;
+        }
+    }
+}
+
+/// Limits the requests with sliding windows and the number of open orders, as crypto exchanges
+/// do. For example, Binance Futures limits both the request weight per minute and the number of
+/// new orders per 10 seconds, which can be expressed as two [`RateLimit`]s, the latter with zero
+/// cancel and modify weights.
+///
+/// A request rejected for the rate limit isn't counted against the limits, whereas a new order
+/// rejected for the maximum number of open orders is.
+#[derive(Clone)]
+pub struct SlidingWindowRateLimit {
+    windows: Vec<Window>,
+    max_open_orders: usize,
+}
+
+impl SlidingWindowRateLimit {
+    /// Constructs `SlidingWindowRateLimit`.
+    ///
+    /// * `limits` - The request rate limits, all of which must be satisfied.
+    /// * `max_open_orders` - The maximum number of open orders. A new order is rejected once the
+    ///   number of open orders reaches it.
+    pub fn new(limits: Vec<RateLimit>, max_open_orders: usize) -> Self {
+        Self {
+            windows: limits
+                .into_iter()
+                .map(|limit| Window {
+                    limit,
+                    requests: VecDeque::new(),
+                    used: 0,
+                })
+                .collect(),
+            max_open_orders,
+        }
+    }
+}
+
+impl RateLimitModel for SlidingWindowRateLimit {
+    fn check(
+        &mut self,
+        order: &Order,
+        timestamp: i64,
+        num_open_orders: usize,
+    ) -> Result<(), RejectReason> {
+        for window in self.windows.iter_mut() {
+            window.expire(timestamp);
+        }
+
+        if self
+            .windows
+            .iter()
+            .any(|window| window.used + window.limit.weight(order.req) > window.limit.limit)
+        {
+            return Err(RejectReason::RateLimited);
+        }
+        for window in self.windows.iter_mut() {
+            let weight = window.limit.weight(order.req);
+            if weight > 0 {
+                window.requests.push_back((timestamp, weight));
+                window.used += weight;
+            }
+        }
+
+        if order.req == Status::New && num_open_orders >= self.max_open_orders {
+            return Err(RejectReason::MaxOpenOrders);
+        }
+        Ok(())
+    }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let requests: Vec<Vec<(i64, u64)>> = self
+            .windows
+            .iter()
+            .map(|window| window.requests.iter().copied().collect())
+            .collect();
+        checkpoint::encode(&requests)
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let requests: Vec<Vec<(i64, u64)>> = checkpoint::decode(checkpoint)?;
+        for (window, requests) in self.windows.iter_mut().zip(requests) {
+            window.used = requests.iter().map(|(_, weight)| weight).sum();
+            window.requests = requests.into();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{asset_builder, event, to_data},
+            models::{RateLimit, SlidingWindowRateLimit},
+            Backtest,
+        },
+        prelude::{Bot, OrdType, RejectReason, Status, TimeInForce},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn reject_requests_over_rate_limit() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 10_000, 101.0, 4.0),
+        ])];
+        let build = || {
+            let rate_limit = SlidingWindowRateLimit::new(
+                vec![RateLimit {
+                    window: 1_000,
+                    limit: 2,
+                    new_weight: 1,
+                    cancel_weight: 1,
+                    modify_weight: 1,
+                }],
+                1,
+            );
+            Backtest::builder()
+                .add_asset(asset_builder(&data).rate_limit(rate_limit).build().unwrap())
+                .build()
+                .unwrap()
+        };
+        let mut hbt = build();
+
+        hbt.elapse(2_000).unwrap();
+        hbt.submit_buy_order(0, 1, 95.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.orders(0).get(&1).unwrap().status, Status::New);
+
+        hbt.submit_buy_order(0, 2, 94.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        let order = hbt.orders(0).get(&2).unwrap();
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.reject_reason, RejectReason::MaxOpenOrders);
+
+        // Both requests above are within the window.
+        hbt.cancel(0, 1, true).unwrap();
+        let order = hbt.orders(0).get(&1).unwrap();
+        assert_eq!(order.status, Status::New);
+        assert_eq!(order.reject_reason, RejectReason::RateLimited);
+
+        // The requests within the window are restored from the checkpoint.
+        let mut checkpoint = Vec::new();
+        hbt.checkpoint(&mut checkpoint).unwrap();
+        let mut restored = build();
+        restored.restore(&mut checkpoint.as_slice()).unwrap();
+        restored.cancel(0, 1, true).unwrap();
+        let order = restored.orders(0).get(&1).unwrap();
+        assert_eq!(order.status, Status::New);
+        assert_eq!(order.reject_reason, RejectReason::RateLimited);
+
+        hbt.elapse(1_000).unwrap();
+        hbt.cancel(0, 1, true).unwrap();
+        let order = hbt.orders(0).get(&1).unwrap();
+        assert_eq!(order.status, Status::Canceled);
+        assert_eq!(order.reject_reason, RejectReason::None);
+    }
+}
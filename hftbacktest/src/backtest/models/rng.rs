@@ -0,0 +1,59 @@
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+
+/// A seeded random number generator whose state can be saved in a checkpoint.
+///
+/// [`StdRng`] cannot be serialized, so the generator counts the values drawn from its seed, and a
+/// restored generator is reseeded and skips the values that have already been drawn. All values
+/// are drawn as `u64` so that the count alone determines the position in the sequence.
+#[derive(Clone, Debug)]
+pub(crate) struct ReplayRng {
+    seed: u64,
+    draws: u64,
+    rng: StdRng,
+}
+
+impl ReplayRng {
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Self {
+            seed,
+            draws: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Returns the seed and the number of values drawn from it.
+    pub fn state(&self) -> (u64, u64) {
+        (self.seed, self.draws)
+    }
+
+    /// Restores the state returned by [`state`](ReplayRng::state).
+    pub fn restore(&mut self, (seed, draws): (u64, u64)) {
+        *self = Self::seed_from_u64(seed);
+        for _ in 0..draws {
+            self.next_u64();
+        }
+    }
+}
+
+impl RngCore for ReplayRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 1;
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use std::sync::{Arc, Mutex};
+
+use crate::types::{Event, Order};
+
+/// Gets notified of the order responses and the market feed as the local processor receives them
+/// during backtesting, which allows building fill logs, markout analytics and custom recorders
+/// without polling the orders after every elapse.
+///
+/// All methods do nothing by default, so only the needed ones have to be implemented. An observer
+/// is registered per asset through the asset builder. To access the observer after the backtest,
+/// register it as `Arc<Mutex<_>>` and keep a clone of it.
+pub trait BacktestObserver<MD>: Send {
+    /// Called when an order response is received, including the rejection of a request, which is
+    /// indicated by [`Status::Rejected`](crate::types::Status::Rejected) in `order.req`.
+    ///
+    /// * `order` - The order response, not yet applied to the local orders.
+    /// * `timestamp` - The timestamp at which the local receives the response.
+    fn on_order_update(&mut self, _order: &Order, _timestamp: i64) {}
+
+    /// Called when an order response reports a fill, before
+    /// [`on_order_update`](Self::on_order_update) is called for the same response. The fill is
+    /// described by `exec_qty`, `exec_price_tick` and `maker` of the order.
+    fn on_fill(&mut self, _order: &Order, _timestamp: i64) {}
+
+    /// Called after the local market depth is updated by the feed event.
+    fn on_depth_change(&mut self, _depth: &MD, _event: &Event) {}
+
+    /// Called when the local receives a market trade.
+    fn on_trade(&mut self, _trade: &Event) {}
+}
+
+impl<MD, O> BacktestObserver<MD> for Arc<Mutex<O>>
+where
+    O: BacktestObserver<MD>,
+{
+    fn on_order_update(&mut self, order: &Order, timestamp: i64) {
+        self.lock().unwrap().on_order_update(order, timestamp);
+    }
+
+    fn on_fill(&mut self, order: &Order, timestamp: i64) {
+        self.lock().unwrap().on_fill(order, timestamp);
+    }
+
+    fn on_depth_change(&mut self, depth: &MD, event: &Event) {
+        self.lock().unwrap().on_depth_change(depth, event);
+    }
+
+    fn on_trade(&mut self, trade: &Event) {
+        self.lock().unwrap().on_trade(trade);
+    }
+}
@@ -1,12 +1,18 @@
-use std::{cell::UnsafeCell, collections::VecDeque, rc::Rc};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
 
 use crate::types::Order;
 
 /// Provides a bus for transporting backtesting orders between the exchange and the local model
 /// based on the given timestamp.
+///
+/// The clones of an `OrderBus` share the same bus, and they can be moved to other threads.
 #[derive(Clone, Debug, Default)]
 pub struct OrderBus {
-    order_list: Rc<UnsafeCell<VecDeque<(Order, i64)>>>,
+    order_list: Arc<Mutex<VecDeque<(Order, i64)>>>,
+    out_of_order: bool,
 }
 
 impl OrderBus {
@@ -15,9 +21,22 @@ impl OrderBus {
         Default::default()
     }
 
+    /// Constructs an instance of ``OrderBus`` that delivers the orders in the order of their
+    /// timestamps rather than the order in which they are appended, allowing an order appended
+    /// later to overtake the earlier ones. Orders with the same timestamp are delivered in the
+    /// order in which they are appended.
+    pub fn out_of_order() -> Self {
+        Self {
+            order_list: Default::default(),
+            out_of_order: true,
+        }
+    }
+
     /// Returns the timestamp of the earliest order in the bus.
     pub fn earliest_timestamp(&self) -> Option<i64> {
-        unsafe { &*self.order_list.get() }
+        self.order_list
+            .lock()
+            .unwrap()
             .front()
             .map(|(_order, ts)| *ts)
     }
@@ -30,10 +49,16 @@ impl OrderBus {
     /// In crypto exchanges that use REST APIs, it may be still possible for order requests sent
     /// later to reach the matching engine before order requests sent earlier. However, for the
     /// purpose of simplifying the backtesting process, all requests and responses are assumed to be
-    /// in order.
+    /// in order. The bus constructed by [`out_of_order`](OrderBus::out_of_order) does not enforce
+    /// this and instead inserts the order according to its timestamp.
     pub fn append(&mut self, order: Order, timestamp: i64) {
+        let mut order_list = self.order_list.lock().unwrap();
+        if self.out_of_order {
+            let i = order_list.partition_point(|(_, ts)| *ts <= timestamp);
+            order_list.insert(i, (order, timestamp));
+            return;
+        }
         let latest_timestamp = {
-            let order_list = unsafe { &*self.order_list.get() };
             let len = order_list.len();
             if len > 0 {
                 let (_, timestamp) = order_list.get(len - 1).unwrap();
@@ -43,26 +68,31 @@ impl OrderBus {
             }
         };
         let timestamp = timestamp.max(latest_timestamp);
-        unsafe { &mut *self.order_list.get() }.push_back((order, timestamp));
+        order_list.push_back((order, timestamp));
     }
 
     /// Resets this to clear it.
     pub fn reset(&mut self) {
-        unsafe { &mut *self.order_list.get() }.clear();
+        self.order_list.lock().unwrap().clear();
     }
 
     /// Returns the number of orders in the bus.
     pub fn len(&self) -> usize {
-        unsafe { &*self.order_list.get() }.len()
+        self.order_list.lock().unwrap().len()
     }
 
     /// Returns ``true`` if the ``OrderBus`` is empty.
     pub fn is_empty(&self) -> bool {
-        unsafe { &*self.order_list.get() }.is_empty()
+        self.order_list.lock().unwrap().is_empty()
+    }
+
+    /// Returns a copy of the orders and their timestamps in the bus.
+    pub fn to_vec(&self) -> Vec<(Order, i64)> {
+        self.order_list.lock().unwrap().iter().cloned().collect()
     }
 
     /// Removes the first order and its timestamp and returns it, or ``None`` if the bus is empty.
     pub fn pop_front(&mut self) -> Option<(Order, i64)> {
-        unsafe { &mut *self.order_list.get() }.pop_front()
+        self.order_list.lock().unwrap().pop_front()
     }
 }
@@ -0,0 +1,266 @@
+use bincode::{Decode, Encode};
+
+use crate::types::{
+    OrdType,
+    Order,
+    OrderId,
+    RejectReason,
+    Side,
+    Status,
+    TrailingUnit,
+    TriggerSource,
+};
+
+/// Holds the conditional orders that the exchange has accepted until their trigger conditions are
+/// met. Once triggered, an order is converted into a market or limit order and is handed over to
+/// the matching engine as if it were newly submitted.
+#[derive(Default, Encode, Decode)]
+pub struct ConditionalOrders {
+    // The untriggered orders in the order of arrival, each with the best price reached since the
+    // order was placed, which the trailing stop orders trail.
+    orders: Vec<(Order, i64)>,
+    last_price_tick: Option<i64>,
+    mark_price_tick: Option<i64>,
+}
+
+impl ConditionalOrders {
+    fn price_tick(&self, source: TriggerSource) -> Option<i64> {
+        match source {
+            TriggerSource::LastPrice => self.last_price_tick,
+            TriggerSource::MarkPrice => self.mark_price_tick,
+        }
+    }
+
+    fn is_triggered(order: &Order, price_tick: i64) -> bool {
+        match (order.order_type, order.side) {
+            (
+                OrdType::StopMarket | OrdType::StopLimit | OrdType::TrailingStopMarket,
+                Side::Buy,
+            )
+            | (OrdType::TakeProfitMarket | OrdType::TakeProfitLimit, Side::Sell) => {
+                price_tick >= order.trigger_price_tick
+            }
+            (
+                OrdType::StopMarket | OrdType::StopLimit | OrdType::TrailingStopMarket,
+                Side::Sell,
+            )
+            | (OrdType::TakeProfitMarket | OrdType::TakeProfitLimit, Side::Buy) => {
+                price_tick <= order.trigger_price_tick
+            }
+            _ => false,
+        }
+    }
+
+    fn trailing_trigger_tick(order: &Order, best_price_tick: i64) -> i64 {
+        let offset_tick = match order.trailing_unit {
+            TrailingUnit::Ticks => order.trailing_offset.round() as i64,
+            TrailingUnit::Percent => {
+                (best_price_tick as f64 * order.trailing_offset / 100.0).floor() as i64
+            }
+        };
+        match order.side {
+            Side::Buy => best_price_tick + offset_tick,
+            _ => best_price_tick - offset_tick,
+        }
+    }
+
+    /// Returns `true` if there is an untriggered order with the given order ID.
+    pub fn contains(&self, order_id: OrderId) -> bool {
+        self.orders
+            .iter()
+            .any(|(order, _)| order.order_id == order_id)
+    }
+
+    /// Returns the untriggered order with the given order ID.
+    pub fn get(&self, order_id: OrderId) -> Option<&Order> {
+        self.orders
+            .iter()
+            .find(|(order, _)| order.order_id == order_id)
+            .map(|(order, _)| order)
+    }
+
+    /// Returns the untriggered orders in the order of arrival.
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.orders.iter().map(|(order, _)| order)
+    }
+
+    /// Accepts the conditional order and sets its status to [`Status::New`]. The order is rejected
+    /// if it would trigger immediately, or if it is a trailing stop order without a valid offset
+    /// or without a reference price to trail.
+    pub fn add(&mut self, order: &mut Order) -> Result<(), RejectReason> {
+        let price_tick = self.price_tick(order.trigger_source);
+        let best_price_tick = if order.order_type == OrdType::TrailingStopMarket {
+            let best_price_tick = price_tick.ok_or(RejectReason::InvalidTrigger)?;
+            if order.trailing_offset <= 0.0 {
+                return Err(RejectReason::InvalidTrigger);
+            }
+            order.trigger_price_tick = Self::trailing_trigger_tick(order, best_price_tick);
+            best_price_tick
+        } else {
+            if let Some(price_tick) = price_tick {
+                if Self::is_triggered(order, price_tick) {
+                    return Err(RejectReason::InvalidTrigger);
+                }
+            }
+            0
+        };
+        order.status = Status::New;
+        self.orders.push((order.clone(), best_price_tick));
+        Ok(())
+    }
+
+    /// Removes the untriggered order.
+    pub fn cancel(&mut self, order_id: OrderId) -> Option<Order> {
+        let i = self
+            .orders
+            .iter()
+            .position(|(order, _)| order.order_id == order_id)?;
+        Some(self.orders.remove(i).0)
+    }
+
+    /// Modifies the price and quantity of the untriggered order. The trigger condition remains
+    /// unchanged.
+    pub fn modify(&mut self, order: &Order) -> Option<Order> {
+        let (exch_order, _) = self
+            .orders
+            .iter_mut()
+            .find(|(exch_order, _)| exch_order.order_id == order.order_id)?;
+        exch_order.price_tick = order.price_tick;
+        exch_order.qty = order.qty;
+        exch_order.leaves_qty = order.qty;
+        Some(exch_order.clone())
+    }
+
+    /// Updates the reference price of the trigger source, and returns the orders triggered by it
+    /// in the order of arrival. The triggered orders are converted into [`OrdType::Market`] or
+    /// [`OrdType::Limit`] orders.
+    pub fn update(&mut self, source: TriggerSource, price_tick: i64) -> Vec<Order> {
+        match source {
+            TriggerSource::LastPrice => self.last_price_tick = Some(price_tick),
+            TriggerSource::MarkPrice => self.mark_price_tick = Some(price_tick),
+        }
+
+        let mut triggered = Vec::new();
+        self.orders.retain_mut(|(order, best_price_tick)| {
+            if order.trigger_source != source {
+                return true;
+            }
+            if order.order_type == OrdType::TrailingStopMarket {
+                let improved = match order.side {
+                    Side::Buy => price_tick < *best_price_tick,
+                    _ => price_tick > *best_price_tick,
+                };
+                if improved {
+                    *best_price_tick = price_tick;
+                    order.trigger_price_tick = Self::trailing_trigger_tick(order, price_tick);
+                }
+            }
+            if !Self::is_triggered(order, price_tick) {
+                return true;
+            }
+            let mut order = order.clone();
+            order.order_type = match order.order_type {
+                OrdType::StopLimit | OrdType::TakeProfitLimit => OrdType::Limit,
+                _ => OrdType::Market,
+            };
+            order.triggered = true;
+            triggered.push(order);
+            false
+        });
+        triggered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::fixture::{build_backtest, event, limit_order, to_data},
+        prelude::{
+            Bot,
+            OrdType,
+            OrderRequest,
+            RejectReason,
+            Side,
+            Status,
+            TrailingUnit,
+            Trigger,
+            TriggerSource,
+        },
+        types::{BUY_EVENT, DEPTH_EVENT, MARK_PRICE_EVENT, SELL_EVENT, TRADE_EVENT},
+    };
+
+    #[test]
+    fn trigger_conditional_orders() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | TRADE_EVENT, 1_000, 100.0, 1.0),
+            event(BUY_EVENT | TRADE_EVENT, 3_000, 103.0, 1.0),
+            event(BUY_EVENT | TRADE_EVENT, 5_000, 105.0, 1.0),
+            event(SELL_EVENT | TRADE_EVENT, 7_000, 102.0, 1.0),
+            event(MARK_PRICE_EVENT, 9_000, 94.0, 0.0),
+            event(BUY_EVENT | DEPTH_EVENT, 11_000, 99.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        let requests = [
+            (1, Side::Buy, OrdType::StopMarket, Trigger::new(102.0, TriggerSource::LastPrice)),
+            (
+                2,
+                Side::Sell,
+                OrdType::TakeProfitMarket,
+                Trigger::new(104.0, TriggerSource::LastPrice),
+            ),
+            (
+                3,
+                Side::Sell,
+                OrdType::TrailingStopMarket,
+                Trigger::trailing(3.0, TrailingUnit::Ticks, TriggerSource::LastPrice),
+            ),
+            // This would be triggered immediately by the last price.
+            (4, Side::Buy, OrdType::StopMarket, Trigger::new(99.0, TriggerSource::LastPrice)),
+            (5, Side::Sell, OrdType::StopMarket, Trigger::new(95.0, TriggerSource::MarkPrice)),
+        ];
+        for (order_id, side, order_type, trigger) in requests {
+            let request = OrderRequest {
+                order_type,
+                trigger: Some(trigger),
+                ..limit_order(order_id, side, trigger.price, 1.0)
+            };
+            hbt.submit_order(0, request, true).unwrap();
+        }
+        assert_eq!(hbt.orders(0)[&1].status, Status::New);
+        assert_eq!(hbt.orders(0)[&3].trigger_price_tick, 97);
+        assert_eq!(hbt.orders(0)[&4].status, Status::Expired);
+        assert_eq!(hbt.orders(0)[&4].reject_reason, RejectReason::InvalidTrigger);
+
+        // The buy stop order is triggered by the buy trade and takes the best ask.
+        hbt.elapse(2_000).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.order_type, OrdType::Market);
+        assert!(order.triggered);
+        assert_eq!(order.exec_price_tick, 101);
+        assert_eq!(hbt.position(0), 1.0);
+        assert_eq!(hbt.orders(0)[&2].status, Status::New);
+        assert_eq!(hbt.orders(0)[&3].status, Status::New);
+
+        hbt.elapse(2_000).unwrap();
+        assert_eq!(hbt.orders(0)[&2].status, Status::Filled);
+        assert_eq!(hbt.position(0), 0.0);
+
+        // The trailing stop trails the highest trade price of 105.
+        hbt.elapse(2_000).unwrap();
+        let order = &hbt.orders(0)[&3];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.trigger_price_tick, 102);
+        assert_eq!(hbt.position(0), -1.0);
+        assert_eq!(hbt.orders(0)[&5].status, Status::New);
+
+        // Only the mark price triggers the order that uses it as the trigger source.
+        hbt.elapse(2_000).unwrap();
+        assert_eq!(hbt.orders(0)[&5].status, Status::Filled);
+        assert_eq!(hbt.position(0), -2.0);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeSet;
+
+use bincode::{Decode, Encode};
+
+use crate::types::{Order, OrderId, TimeInForce};
+
+/// Schedules the expiry of the [`TimeInForce::GTD`] orders that the exchange holds.
+///
+/// Filled or canceled orders are not removed from the schedule, so the exchange should check with
+/// [`has_expired`](OrderExpiry::has_expired) that the order it still holds is the one scheduled
+/// before expiring it.
+#[derive(Default, Encode, Decode)]
+pub struct OrderExpiry {
+    // The expire timestamps and the order IDs in the order of expiry.
+    schedule: BTreeSet<(i64, OrderId)>,
+}
+
+impl OrderExpiry {
+    /// Schedules the expiry of the order if its time-in-force is [`TimeInForce::GTD`].
+    pub fn schedule(&mut self, order: &Order) {
+        if order.time_in_force == TimeInForce::GTD {
+            self.schedule.insert((order.expire_timestamp, order.order_id));
+        }
+    }
+
+    /// Returns the earliest scheduled expire timestamp.
+    pub fn earliest_timestamp(&self) -> Option<i64> {
+        self.schedule.first().map(|(timestamp, _)| *timestamp)
+    }
+
+    /// Removes and returns the IDs of the orders scheduled to expire at or before the timestamp.
+    pub fn pop_expired(&mut self, timestamp: i64) -> Vec<OrderId> {
+        let mut expired = Vec::new();
+        while let Some(&(expire_timestamp, order_id)) = self.schedule.first() {
+            if expire_timestamp > timestamp {
+                break;
+            }
+            self.schedule.pop_first();
+            expired.push(order_id);
+        }
+        expired
+    }
+
+    /// Returns `true` if the order is a [`TimeInForce::GTD`] order that has reached its expire
+    /// timestamp.
+    pub fn has_expired(order: &Order, timestamp: i64) -> bool {
+        order.time_in_force == TimeInForce::GTD && order.expire_timestamp <= timestamp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{build_backtest, event, limit_order, to_data},
+            BacktestError,
+        },
+        prelude::{Bot, OrdType, OrderRequest, Side, Status, TimeInForce, Trigger, TriggerSource},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn expire_good_till_date_orders() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        let requests = [
+            (1, 98.0, TimeInForce::GTD, OrdType::Limit, None, 4_000),
+            // This has already expired by the time the exchange receives it.
+            (2, 98.0, TimeInForce::GTD, OrdType::Limit, None, 1_500),
+            (3, 97.0, TimeInForce::GTC, OrdType::Limit, None, 0),
+            (
+                4,
+                110.0,
+                TimeInForce::GTD,
+                OrdType::StopMarket,
+                Some(Trigger::new(110.0, TriggerSource::LastPrice)),
+                4_500,
+            ),
+        ];
+        for (order_id, price, time_in_force, order_type, trigger, expire_timestamp) in requests {
+            let request = OrderRequest {
+                time_in_force,
+                order_type,
+                trigger,
+                expire_timestamp,
+                ..limit_order(order_id, Side::Buy, price, 1.0)
+            };
+            hbt.submit_order(0, request, true).unwrap();
+        }
+        assert_eq!(hbt.orders(0)[&1].status, Status::New);
+        assert_eq!(hbt.orders(0)[&2].status, Status::Expired);
+        assert_eq!(hbt.orders(0)[&4].status, Status::New);
+
+        // A good-till-date order requires its expire timestamp.
+        assert!(matches!(
+            hbt.submit_buy_order(0, 5, 98.0, 1.0, TimeInForce::GTD, OrdType::Limit, false),
+            Err(BacktestError::InvalidOrderRequest)
+        ));
+
+        // The orders expire at their expire timestamps even though there is no market data.
+        hbt.elapse(5_000).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.exch_timestamp, 4_000);
+        let order = &hbt.orders(0)[&4];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.exch_timestamp, 4_500);
+        assert_eq!(hbt.orders(0)[&3].status, Status::New);
+    }
+}
@@ -3,22 +3,31 @@ use std::mem;
 use crate::{
     backtest::{
         assettype::AssetType,
+        checkpoint::{self, DepthCheckpoint, ExchCheckpoint},
         data::{Data, Reader},
-        models::{FeeModel, L3QueueModel, LatencyModel},
+        models::{FeeModel, L3QueueModel, LatencyModel, MarginModel, NoMarginModel, RateLimitModel},
         order::OrderBus,
-        proc::Processor,
+        proc::{
+            conditional::ConditionalOrders,
+            expiry::OrderExpiry,
+            reduce_only::ReduceOnlyOrders,
+            Processor,
+        },
         state::State,
         BacktestError,
+        InstrumentRules,
     },
-    depth::L3MarketDepth,
+    depth::{ApplySnapshot, L3MarketDepth},
     prelude::OrdType,
     types::{
         Event,
         Order,
         OrderId,
+        RejectReason,
         Side,
         Status,
         TimeInForce,
+        TriggerSource,
         BUY_EVENT,
         EXCH_ASK_ADD_ORDER_EVENT,
         EXCH_ASK_DEPTH_CLEAR_EVENT,
@@ -28,6 +37,8 @@ use crate::{
         EXCH_DEPTH_CLEAR_EVENT,
         EXCH_EVENT,
         EXCH_FILL_EVENT,
+        EXCH_FUNDING_EVENT,
+        EXCH_MARK_PRICE_EVENT,
         EXCH_MODIFY_ORDER_EVENT,
         SELL_EVENT,
     },
@@ -36,7 +47,7 @@ use crate::{
 /// The exchange model without partial fills.
 ///
 /// Support order types: [OrdType::Limit](crate::types::OrdType::Limit)
-/// Support time-in-force: [`TimeInForce::GTC`], [`TimeInForce::GTX`]
+/// Support time-in-force: [`TimeInForce::GTC`], [`TimeInForce::GTX`], [`TimeInForce::GTD`]
 ///
 /// **Conditions for Full Execution**
 ///
@@ -58,13 +69,14 @@ use crate::{
 /// best. Be aware that this may cause unrealistic fill simulations if you attempt to execute a
 /// large quantity.
 ///
-pub struct L3NoPartialFillExchange<AT, LM, QM, MD, FM>
+pub struct L3NoPartialFillExchange<AT, LM, QM, MD, FM, MM = NoMarginModel>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: L3QueueModel<MD>,
     MD: L3MarketDepth,
     FM: FeeModel,
+    MM: MarginModel,
 {
     reader: Reader<Event>,
     data: Data<Event>,
@@ -73,25 +85,31 @@ where
     orders_from: OrderBus,
 
     depth: MD,
-    state: State<AT, FM>,
+    state: State<AT, FM, MM>,
     order_latency: LM,
     queue_model: QM,
+    rate_limit: Option<Box<dyn RateLimitModel>>,
+    conditional_orders: ConditionalOrders,
+    order_expiry: OrderExpiry,
+    reduce_only_orders: ReduceOnlyOrders,
+    instrument_rules: InstrumentRules,
 }
 
-impl<AT, LM, QM, MD, FM> L3NoPartialFillExchange<AT, LM, QM, MD, FM>
+impl<AT, LM, QM, MD, FM, MM> L3NoPartialFillExchange<AT, LM, QM, MD, FM, MM>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: L3QueueModel<MD>,
     MD: L3MarketDepth,
     FM: FeeModel,
+    MM: MarginModel,
     BacktestError: From<<MD as L3MarketDepth>::Error>,
 {
     /// Constructs an instance of `NoPartialFillExchange`.
     pub fn new(
         reader: Reader<Event>,
         depth: MD,
-        state: State<AT, FM>,
+        state: State<AT, FM, MM>,
         order_latency: LM,
         queue_model: QM,
         orders_to: OrderBus,
@@ -107,6 +125,28 @@ where
             state,
             order_latency,
             queue_model,
+            rate_limit: None,
+            conditional_orders: Default::default(),
+            order_expiry: Default::default(),
+            reduce_only_orders: Default::default(),
+            instrument_rules: Default::default(),
+        }
+    }
+
+    /// Sets a [`RateLimitModel`] that rejects the requests exceeding the limits.
+    pub fn with_rate_limit(self, rate_limit: Box<dyn RateLimitModel>) -> Self {
+        Self {
+            rate_limit: Some(rate_limit),
+            ..self
+        }
+    }
+
+    /// Sets the [`InstrumentRules`] that the new and modified orders are checked against. By
+    /// default, no rules are applied.
+    pub fn with_instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
         }
     }
 
@@ -115,6 +155,20 @@ where
         mut order: Order,
         recv_timestamp: i64,
     ) -> Result<(), BacktestError> {
+        if let Some(rate_limit) = &mut self.rate_limit {
+            let num_open_orders = self.queue_model.num_backtest_orders();
+            if let Err(reason) = rate_limit.check(&order, recv_timestamp, num_open_orders) {
+                // The request is rejected before it reaches the matching engine, so it has no
+                // exchange timestamp.
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                let local_recv_timestamp =
+                    recv_timestamp + self.order_latency.response(recv_timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
         // Processes a new order.
         if order.req == Status::New {
             order.req = Status::None;
@@ -124,6 +178,11 @@ where
         else if order.req == Status::Canceled {
             order.req = Status::None;
             self.ack_cancel(order, recv_timestamp)?;
+        }
+        // Processes a modify order.
+        else if order.req == Status::Replaced {
+            order.req = Status::None;
+            self.ack_modify(order, recv_timestamp)?;
         } else {
             return Err(BacktestError::InvalidOrderRequest);
         }
@@ -175,6 +234,28 @@ where
         Ok(())
     }
 
+    fn fill_slice(&mut self, order: &mut Order, timestamp: i64) -> Result<(), BacktestError> {
+        if order.status == Status::Expired
+            || order.status == Status::Canceled
+            || order.status == Status::Filled
+        {
+            return Err(BacktestError::InvalidOrderStatus);
+        }
+
+        order.maker = true;
+        order.exec_price_tick = order.price_tick;
+        order.exec_qty = order.slice_qty(self.depth.lot_size());
+        order.leaves_qty -= order.exec_qty;
+        order.status = Status::PartiallyFilled;
+        order.exch_timestamp = timestamp;
+        let local_recv_timestamp =
+            order.exch_timestamp + self.order_latency.response(timestamp, order);
+
+        self.state.apply_fill(order);
+        self.orders_to.append(order.clone(), local_recv_timestamp);
+        Ok(())
+    }
+
     fn fill_ask_orders_by_crossing(
         &mut self,
         prev_best_tick: i64,
@@ -207,11 +288,209 @@ where
         Ok(())
     }
 
+    fn check_liquidation(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let fills = self.state.liquidation_fills(&self.depth);
+        if fills.is_empty() {
+            return Ok(());
+        }
+        // The open orders are canceled first so that they cannot reopen the position.
+        self.cancel_all_orders(timestamp)?;
+        for mut order in fills {
+            self.state.apply_fill(&order);
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+        Ok(())
+    }
+
+    /// Cancels all open orders, including the untriggered conditional orders, in the order of their
+    /// order IDs.
+    fn cancel_all_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let mut open_orders: Vec<_> = self
+            .queue_model
+            .backtest_orders()
+            .into_iter()
+            .chain(self.conditional_orders.orders())
+            .cloned()
+            .collect();
+        open_orders.sort_by_key(|order| order.order_id);
+        for order in open_orders {
+            self.ack_cancel(order, timestamp)?;
+        }
+        Ok(())
+    }
+
+    fn trigger_conditional_orders(
+        &mut self,
+        source: TriggerSource,
+        price_tick: i64,
+        timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        for mut order in self.conditional_orders.update(source, price_tick) {
+            if !self.state.has_margin_for(&order, &self.depth) {
+                // The order has already been accepted, so it expires instead of being rejected.
+                order.status = Status::Expired;
+                order.reject_reason = RejectReason::InsufficientMargin;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+            } else {
+                self.ack_new(order, timestamp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expire_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        for order_id in self.order_expiry.pop_expired(timestamp) {
+            let exch_order = if self
+                .conditional_orders
+                .get(order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                self.conditional_orders.cancel(order_id)
+            } else if self
+                .queue_model
+                .backtest_order(order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                Some(self.queue_model.cancel_backtest_order(order_id, &self.depth)?)
+            } else {
+                // The order has already been filled or canceled.
+                None
+            };
+            if let Some(mut exch_order) = exch_order {
+                exch_order.status = Status::Expired;
+                exch_order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &exch_order);
+                self.orders_to.append(exch_order, local_recv_timestamp);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cancels the resting reduce-only orders that would increase the position, which the fills
+    /// have changed.
+    fn expire_reduce_only_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let lot_size = self.depth.lot_size();
+        for order_id in self
+            .reduce_only_orders
+            .pop_position_increasing(self.state.values(), lot_size)
+        {
+            if !self
+                .queue_model
+                .backtest_order(order_id)
+                .is_some_and(ReduceOnlyOrders::is_reduce_only)
+            {
+                // The order has already been filled or canceled.
+                continue;
+            }
+            let mut order = self.queue_model.cancel_backtest_order(order_id, &self.depth)?;
+            order.status = Status::Expired;
+            order.reject_reason = RejectReason::ReduceOnly;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+        Ok(())
+    }
+
     fn ack_new(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
         if self.queue_model.contains_backtest_order(order.order_id) {
             return Err(BacktestError::OrderIdExist);
         }
+        if self.conditional_orders.contains(order.order_id) {
+            return Err(BacktestError::OrderIdExist);
+        }
 
+        // The triggered order has already been checked against the rules when it was placed.
+        if !order.triggered {
+            let rule_check = self.instrument_rules.check(
+                order.order_type,
+                order.price(),
+                order.qty,
+                &self.depth,
+            );
+            if let Err(reason) = rule_check {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
+        // A good-till-date order whose expire timestamp has already passed is not accepted.
+        if OrderExpiry::has_expired(&order, timestamp) {
+            order.status = Status::Expired;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+        self.order_expiry.schedule(&order);
+
+        // The conditional order is held without being matched until it is triggered.
+        if order.order_type.is_conditional() {
+            order.exch_timestamp = timestamp;
+            if let Err(reason) = self.conditional_orders.add(&mut order) {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+            }
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if ReduceOnlyOrders::is_reduce_only(&order) {
+            // The reduce-only order is clipped to the position, and is not accepted if it would
+            // only increase the position.
+            let reducible_qty = ReduceOnlyOrders::reducible_qty(
+                order.side,
+                order.position_side,
+                self.state.values(),
+            );
+            if (reducible_qty / self.depth.lot_size()).round() <= 0.0 {
+                if order.triggered {
+                    // The triggered order has already been accepted, so it expires instead.
+                    order.status = Status::Expired;
+                } else {
+                    order.req = Status::Rejected;
+                }
+                order.reject_reason = RejectReason::ReduceOnly;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+            if order.qty > reducible_qty {
+                order.qty = reducible_qty;
+                order.leaves_qty = reducible_qty;
+            }
+            self.reduce_only_orders.add(&order);
+        }
+
+        if !self.state.has_margin_for(&order, &self.depth) {
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::InsufficientMargin;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        self.match_order(order, timestamp)
+    }
+
+    /// Matches the order that has passed the admission checks against the order book, and either
+    /// rests it in the queue or executes it according to its order type and time-in-force.
+    fn match_order(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
         if order.side == Side::Buy {
             match order.order_type {
                 OrdType::Limit => {
@@ -227,7 +506,10 @@ where
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
                                 Ok(())
                             }
-                            TimeInForce::GTC | TimeInForce::FOK | TimeInForce::IOC => {
+                            TimeInForce::GTC
+                            | TimeInForce::GTD
+                            | TimeInForce::FOK
+                            | TimeInForce::IOC => {
                                 // Since this always fills the full quantity, both FOK and IOC
                                 // orders are also fully filled at the best price.
                                 // Takes the market.
@@ -237,7 +519,7 @@ where
                         }
                     } else {
                         match order.time_in_force {
-                            TimeInForce::GTC | TimeInForce::GTX => {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
                                 // Initializes the order's queue position.
                                 order.status = Status::New;
                                 order.exch_timestamp = timestamp;
@@ -267,7 +549,7 @@ where
                     // Takes the market.
                     self.fill(&mut order, timestamp, false, self.depth.best_ask_tick())
                 }
-                OrdType::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                _ => Err(BacktestError::InvalidOrderRequest),
             }
         } else {
             match order.order_type {
@@ -284,7 +566,10 @@ where
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
                                 Ok(())
                             }
-                            TimeInForce::GTC | TimeInForce::FOK | TimeInForce::IOC => {
+                            TimeInForce::GTC
+                            | TimeInForce::GTD
+                            | TimeInForce::FOK
+                            | TimeInForce::IOC => {
                                 // Since this always fills the full quantity, both FOK and IOC
                                 // orders are also fully filled at the best price.
                                 // Takes the market.
@@ -294,7 +579,7 @@ where
                         }
                     } else {
                         match order.time_in_force {
-                            TimeInForce::GTC | TimeInForce::GTX => {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
                                 // Initializes the order's queue position.
                                 order.status = Status::New;
                                 order.exch_timestamp = timestamp;
@@ -324,12 +609,21 @@ where
                     // Takes the market.
                     self.fill(&mut order, timestamp, false, self.depth.best_bid_tick())
                 }
-                OrdType::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                _ => Err(BacktestError::InvalidOrderRequest),
             }
         }
     }
 
     fn ack_cancel(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if let Some(mut exch_order) = self.conditional_orders.cancel(order.order_id) {
+            exch_order.status = Status::Canceled;
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
         match self
             .queue_model
             .cancel_backtest_order(order.order_id, &self.depth)
@@ -346,6 +640,7 @@ where
             }
             Err(BacktestError::OrderNotFound) => {
                 order.req = Status::Rejected;
+                order.reject_reason = RejectReason::UnknownOrder;
                 order.exch_timestamp = timestamp;
                 let local_recv_timestamp =
                     timestamp + self.order_latency.response(timestamp, &order);
@@ -357,17 +652,81 @@ where
     }
 
     fn ack_modify(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
-        todo!()
+        let rule_check = self.instrument_rules.check(
+            order.order_type,
+            order.price(),
+            order.qty,
+            &self.depth,
+        );
+        if let Err(reason) = rule_check {
+            order.req = Status::Rejected;
+            order.reject_reason = reason;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if let Some(mut exch_order) = self.conditional_orders.modify(&order) {
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        let mut exch_order = match self.queue_model.backtest_order(order.order_id) {
+            Some(exch_order) => exch_order.clone(),
+            None => {
+                // The order can be already deleted due to fill or expiration.
+                order.req = Status::Rejected;
+                order.reject_reason = RejectReason::UnknownOrder;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        };
+
+        exch_order.price_tick = order.price_tick;
+        exch_order.qty = order.qty;
+        exch_order.leaves_qty = order.qty;
+        exch_order.exch_timestamp = timestamp;
+
+        let cross = match exch_order.side {
+            // Checks if the buy order price is greater than or equal to the current best ask.
+            Side::Buy => exch_order.price_tick >= self.depth.best_ask_tick(),
+            // Checks if the sell order price is less than or equal to the current best bid.
+            Side::Sell => exch_order.price_tick <= self.depth.best_bid_tick(),
+            Side::None | Side::Unsupported => unreachable!(),
+        };
+        if cross {
+            // The order is matched in the same way as a new order that crosses the book, without
+            // going through the admission checks again.
+            self.queue_model
+                .cancel_backtest_order(exch_order.order_id, &self.depth)?;
+            return self.match_order(exch_order, timestamp);
+        }
+
+        // The queue model re-evaluates the order's queue position against the market-feed orders.
+        self.queue_model
+            .modify_backtest_order(exch_order.order_id, exch_order.clone(), &self.depth)?;
+
+        let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &exch_order);
+        self.orders_to.append(exch_order, local_recv_timestamp);
+        Ok(())
     }
 }
 
-impl<AT, LM, QM, MD, FM> Processor for L3NoPartialFillExchange<AT, LM, QM, MD, FM>
+impl<AT, LM, QM, MD, FM, MM> Processor for L3NoPartialFillExchange<AT, LM, QM, MD, FM, MM>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: L3QueueModel<MD>,
-    MD: L3MarketDepth,
+    MD: L3MarketDepth + ApplySnapshot,
     FM: FeeModel,
+    MM: MarginModel,
     BacktestError: From<<MD as L3MarketDepth>::Error>,
 {
     fn initialize_data(&mut self) -> Result<i64, BacktestError> {
@@ -475,12 +834,42 @@ where
                     &self.depth,
                 )?;
                 let timestamp = self.data[row_num].exch_ts;
+                let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
                 for mut order in filled {
-                    let price_tick = order.price_tick;
-                    self.fill(&mut order, timestamp, true, price_tick)?;
+                    if order.price_tick == price_tick
+                        && order.slice_qty(self.depth.lot_size()) < order.leaves_qty
+                    {
+                        // The iceberg order displays its next slice at the back of the queue.
+                        self.fill_slice(&mut order, timestamp)?;
+                        self.queue_model.add_backtest_order(order, &self.depth)?;
+                    } else {
+                        let order_price_tick = order.price_tick;
+                        self.fill(&mut order, timestamp, true, order_price_tick)?;
+                    }
                 }
+                self.trigger_conditional_orders(TriggerSource::LastPrice, price_tick, timestamp)?;
             }
+        } else if self.data[row_num].is(EXCH_FUNDING_EVENT) {
+            self.state
+                .apply_funding(self.data[row_num].px, self.data[row_num].fval);
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        } else if self.data[row_num].is(EXCH_MARK_PRICE_EVENT) {
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
         }
+        self.check_liquidation(self.data[row_num].exch_ts)?;
+        self.expire_reduce_only_orders(self.data[row_num].exch_ts)?;
+        self.order_latency
+            .on_feed(self.data[row_num].exch_ts, &self.data[row_num]);
 
         // Checks
         let mut next_ts = 0;
@@ -508,25 +897,142 @@ where
         timestamp: i64,
         _wait_resp_order_id: Option<OrderId>,
     ) -> Result<bool, BacktestError> {
+        self.expire_orders(timestamp)?;
+
         // Processes the order part.
         while !self.orders_from.is_empty() {
             let recv_timestamp = self.orders_from.earliest_timestamp().unwrap();
             if timestamp == recv_timestamp {
                 let (order, _) = self.orders_from.pop_front().unwrap();
+                self.order_latency.on_request(order.req);
                 self.process_recv_order_(order, recv_timestamp)?;
+                self.order_latency.on_request(Status::None);
             } else {
                 assert!(recv_timestamp > timestamp);
                 break;
             }
         }
+        self.expire_reduce_only_orders(timestamp)?;
         Ok(false)
     }
 
     fn earliest_recv_order_timestamp(&self) -> i64 {
-        self.orders_from.earliest_timestamp().unwrap_or(i64::MAX)
+        // Wakes up at the expire timestamp of the good-till-date order as well.
+        self.orders_from
+            .earliest_timestamp()
+            .unwrap_or(i64::MAX)
+            .min(self.order_expiry.earliest_timestamp().unwrap_or(i64::MAX))
     }
 
     fn earliest_send_order_timestamp(&self) -> i64 {
         self.orders_to.earliest_timestamp().unwrap_or(i64::MAX)
     }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        checkpoint::encode(&ExchCheckpoint {
+            data_num: self.reader.data_num(),
+            row_num: self.row_num,
+            depth: DepthCheckpoint::l3(&self.depth),
+            state_values: self.state.state_values.clone(),
+            orders: Vec::new(),
+            queue: self.queue_model.checkpoint()?,
+            conditional_orders: checkpoint::encode(&self.conditional_orders)?,
+            order_expiry: checkpoint::encode(&self.order_expiry)?,
+            reduce_only_orders: checkpoint::encode(&self.reduce_only_orders)?,
+            rate_limit: match &self.rate_limit {
+                Some(rate_limit) => rate_limit.checkpoint()?,
+                None => Vec::new(),
+            },
+            consumed_bid: Vec::new(),
+            consumed_ask: Vec::new(),
+            order_latency: self.order_latency.checkpoint()?,
+        })
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let state: ExchCheckpoint = checkpoint::decode(checkpoint)?;
+        checkpoint::restore_data(&mut self.reader, &mut self.data, state.data_num)?;
+        self.row_num = state.row_num;
+        state.depth.restore_l3(&mut self.depth)?;
+        self.state.state_values = state.state_values;
+        self.conditional_orders = checkpoint::decode(&state.conditional_orders)?;
+        self.order_expiry = checkpoint::decode(&state.order_expiry)?;
+        self.reduce_only_orders = checkpoint::decode(&state.reduce_only_orders)?;
+        if let Some(rate_limit) = &mut self.rate_limit {
+            rate_limit.restore(&state.rate_limit)?;
+        }
+        self.order_latency.restore(&state.order_latency)?;
+        self.queue_model.restore(&state.queue)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{l3_asset_builder, l3_event, to_data},
+            Backtest,
+            BacktestError,
+        },
+        prelude::{Bot, OrdType, RejectReason, Status, TimeInForce},
+        types::{ADD_ORDER_EVENT, BUY_EVENT, FILL_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn modify_order() {
+        let data = [to_data(&[
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 1_000, 1, 99.0, 2.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 2, 101.0, 3.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 3_000, 3, 99.0, 5.0),
+            // The fill of the market-feed order fills the backtest order ahead of it.
+            l3_event(BUY_EVENT | FILL_EVENT, 5_000, 3, 99.0, 1.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 20_000, 4, 98.0, 1.0),
+        ])];
+        let mut hbt = Backtest::builder()
+            .add_asset(l3_asset_builder(&data).build().unwrap())
+            .build()
+            .unwrap();
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_buy_order(0, 1, 99.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        hbt.submit_buy_order(0, 2, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+
+        // The local order takes the modified price and quantity once the response arrives.
+        hbt.modify(0, 2, 97.0, 2.0, false).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.req, Status::Replaced);
+        assert_eq!((order.price_tick, order.qty), (98, 1.0));
+        hbt.elapse(100).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.req, Status::None);
+        assert_eq!(order.status, Status::New);
+        assert_eq!((order.price_tick, order.qty, order.leaves_qty), (97, 2.0, 2.0));
+
+        hbt.elapse(3_500).unwrap();
+        assert_eq!(hbt.orders(0)[&1].status, Status::Filled);
+        assert_eq!(hbt.position(0), 1.0);
+
+        // The exchange no longer holds the filled order.
+        hbt.modify(0, 1, 99.0, 2.0, true).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.qty, 1.0);
+        assert_eq!(order.reject_reason, RejectReason::UnknownOrder);
+        assert_eq!(hbt.position(0), 1.0);
+
+        assert!(matches!(
+            hbt.modify(0, 3, 99.0, 1.0, true),
+            Err(BacktestError::OrderNotFound)
+        ));
+
+        // The modified order that crosses the order book takes the market.
+        hbt.modify(0, 2, 101.0, 2.0, true).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_price_tick, 101);
+        assert_eq!(hbt.position(0), 3.0);
+    }
 }
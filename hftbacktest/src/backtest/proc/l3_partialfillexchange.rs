@@ -0,0 +1,1219 @@
+use std::mem;
+
+use crate::{
+    backtest::{
+        assettype::AssetType,
+        checkpoint::{self, DepthCheckpoint, ExchCheckpoint},
+        data::{Data, Reader},
+        models::{FeeModel, L3QueueModel, LatencyModel, MarginModel, NoMarginModel, RateLimitModel},
+        order::OrderBus,
+        proc::{
+            conditional::ConditionalOrders,
+            expiry::OrderExpiry,
+            reduce_only::ReduceOnlyOrders,
+            Processor,
+        },
+        state::State,
+        BacktestError,
+        InstrumentRules,
+        PriceBand,
+    },
+    depth::{ApplySnapshot, L3MarketDepth, INVALID_MAX, INVALID_MIN},
+    prelude::OrdType,
+    types::{
+        Event,
+        Order,
+        OrderId,
+        RejectReason,
+        Side,
+        Status,
+        TimeInForce,
+        TriggerSource,
+        BUY_EVENT,
+        EXCH_ASK_ADD_ORDER_EVENT,
+        EXCH_ASK_DEPTH_CLEAR_EVENT,
+        EXCH_BID_ADD_ORDER_EVENT,
+        EXCH_BID_DEPTH_CLEAR_EVENT,
+        EXCH_CANCEL_ORDER_EVENT,
+        EXCH_DEPTH_CLEAR_EVENT,
+        EXCH_EVENT,
+        EXCH_FILL_EVENT,
+        EXCH_FUNDING_EVENT,
+        EXCH_MARK_PRICE_EVENT,
+        EXCH_MODIFY_ORDER_EVENT,
+        SELL_EVENT,
+    },
+};
+
+/// The Level3 Market-By-Order exchange model with partial fills.
+///
+/// * Support order types: [OrdType::Limit](crate::types::OrdType::Limit),
+///   [OrdType::Market](crate::types::OrdType::Market)
+/// * Support time-in-force: [`TimeInForce::GTC`], [`TimeInForce::FOK`], [`TimeInForce::IOC`],
+///   [`TimeInForce::GTX`], [`TimeInForce::GTD`]
+///
+/// **Conditions for Full Execution**
+///
+/// Buy order in the order book
+///
+/// - Your order price >= the best ask price
+/// - Your order price > the price of the filled market-feed sell order
+///
+/// Sell order in the order book
+///
+/// - Your order price <= the best bid price
+/// - Your order price < the price of the filled market-feed buy order
+///
+/// **Conditions for Partial Execution**
+///
+/// When a market-feed order at the same price is filled, your orders placed before it in the
+/// queue are executed in FIFO order up to the fill quantity. The remaining quantity keeps its
+/// queue position.
+///
+/// **Liquidity-Taking Order**
+///
+/// Liquidity-taking orders walk the order book from the best price and are executed based on the
+/// quantity at each price level, even though the market depth does not change due to your
+/// execution. Be aware that this may cause unrealistic fill simulations if you attempt to execute
+/// a large quantity.
+///
+pub struct L3PartialFillExchange<AT, LM, QM, MD, FM, MM = NoMarginModel>
+where
+    AT: AssetType,
+    LM: LatencyModel,
+    QM: L3QueueModel<MD>,
+    MD: L3MarketDepth,
+    FM: FeeModel,
+    MM: MarginModel,
+{
+    reader: Reader<Event>,
+    data: Data<Event>,
+    row_num: usize,
+    orders_to: OrderBus,
+    orders_from: OrderBus,
+
+    depth: MD,
+    state: State<AT, FM, MM>,
+    order_latency: LM,
+    queue_model: QM,
+    rate_limit: Option<Box<dyn RateLimitModel>>,
+    conditional_orders: ConditionalOrders,
+    order_expiry: OrderExpiry,
+    reduce_only_orders: ReduceOnlyOrders,
+    instrument_rules: InstrumentRules,
+    price_band: PriceBand,
+}
+
+impl<AT, LM, QM, MD, FM, MM> L3PartialFillExchange<AT, LM, QM, MD, FM, MM>
+where
+    AT: AssetType,
+    LM: LatencyModel,
+    QM: L3QueueModel<MD>,
+    MD: L3MarketDepth,
+    FM: FeeModel,
+    MM: MarginModel,
+    BacktestError: From<<MD as L3MarketDepth>::Error>,
+{
+    /// Constructs an instance of `L3PartialFillExchange`.
+    pub fn new(
+        reader: Reader<Event>,
+        depth: MD,
+        state: State<AT, FM, MM>,
+        order_latency: LM,
+        queue_model: QM,
+        orders_to: OrderBus,
+        orders_from: OrderBus,
+    ) -> Self {
+        Self {
+            reader,
+            data: Data::empty(),
+            row_num: 0,
+            orders_to,
+            orders_from,
+            depth,
+            state,
+            order_latency,
+            queue_model,
+            rate_limit: None,
+            conditional_orders: Default::default(),
+            order_expiry: Default::default(),
+            reduce_only_orders: Default::default(),
+            instrument_rules: Default::default(),
+            price_band: PriceBand::None,
+        }
+    }
+
+    /// Sets a [`RateLimitModel`] that rejects the requests exceeding the limits.
+    pub fn with_rate_limit(self, rate_limit: Box<dyn RateLimitModel>) -> Self {
+        Self {
+            rate_limit: Some(rate_limit),
+            ..self
+        }
+    }
+
+    /// Sets the [`InstrumentRules`] that the new and modified orders are checked against. By
+    /// default, no rules are applied.
+    pub fn with_instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
+        }
+    }
+
+    /// Sets the [`PriceBand`] that limits how far a market order can sweep the order book. The
+    /// default value is [`PriceBand::None`].
+    pub fn with_price_band(self, price_band: PriceBand) -> Self {
+        Self { price_band, ..self }
+    }
+
+    fn process_recv_order_(
+        &mut self,
+        mut order: Order,
+        recv_timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        if let Some(rate_limit) = &mut self.rate_limit {
+            let num_open_orders = self.queue_model.num_backtest_orders();
+            if let Err(reason) = rate_limit.check(&order, recv_timestamp, num_open_orders) {
+                // The request is rejected before it reaches the matching engine, so it has no
+                // exchange timestamp.
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                let local_recv_timestamp =
+                    recv_timestamp + self.order_latency.response(recv_timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
+        // Processes a new order.
+        if order.req == Status::New {
+            order.req = Status::None;
+            self.ack_new(order, recv_timestamp)?;
+        }
+        // Processes a cancel order.
+        else if order.req == Status::Canceled {
+            order.req = Status::None;
+            self.ack_cancel(order, recv_timestamp)?;
+        }
+        // Processes a modify order.
+        else if order.req == Status::Replaced {
+            order.req = Status::None;
+            self.ack_modify(order, recv_timestamp)?;
+        } else {
+            return Err(BacktestError::InvalidOrderRequest);
+        }
+        Ok(())
+    }
+
+    fn expired(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        order.exec_qty = 0.0;
+        order.leaves_qty = 0.0;
+        order.status = Status::Expired;
+        order.exch_timestamp = timestamp;
+        let local_recv_timestamp =
+            order.exch_timestamp + self.order_latency.response(timestamp, &order);
+
+        self.orders_to.append(order, local_recv_timestamp);
+        Ok(())
+    }
+
+    fn fill(
+        &mut self,
+        order: &mut Order,
+        timestamp: i64,
+        maker: bool,
+        exec_price_tick: i64,
+        exec_qty: f64,
+    ) -> Result<(), BacktestError> {
+        if order.status == Status::Expired
+            || order.status == Status::Canceled
+            || order.status == Status::Filled
+        {
+            return Err(BacktestError::InvalidOrderStatus);
+        }
+
+        order.maker = maker;
+        if maker {
+            order.exec_price_tick = order.price_tick;
+        } else {
+            order.exec_price_tick = exec_price_tick;
+        }
+
+        order.exec_qty = exec_qty;
+        order.leaves_qty -= exec_qty;
+        if (order.leaves_qty / self.depth.lot_size()).round() > 0f64 {
+            order.status = Status::PartiallyFilled;
+        } else {
+            order.status = Status::Filled;
+        }
+        order.exch_timestamp = timestamp;
+        let local_recv_timestamp =
+            order.exch_timestamp + self.order_latency.response(timestamp, order);
+
+        self.state.apply_fill(order);
+        self.orders_to.append(order.clone(), local_recv_timestamp);
+        Ok(())
+    }
+
+    /// Executes the buy order against the ask side of the order book from the best ask up to the
+    /// given price. Returns `true` if the order is fully filled.
+    fn take_asks(
+        &mut self,
+        order: &mut Order,
+        to_tick: i64,
+        timestamp: i64,
+    ) -> Result<bool, BacktestError> {
+        let mut t = self.depth.best_ask_tick();
+        while t != INVALID_MAX && t <= to_tick {
+            let exec_qty = self.depth.ask_qty_at_tick(t).min(order.leaves_qty);
+            self.fill(order, timestamp, false, t, exec_qty)?;
+            if order.status == Status::Filled {
+                return Ok(true);
+            }
+            t = self.depth.next_ask_tick(t);
+        }
+        Ok(false)
+    }
+
+    /// Executes the sell order against the bid side of the order book from the best bid down to
+    /// the given price. Returns `true` if the order is fully filled.
+    fn take_bids(
+        &mut self,
+        order: &mut Order,
+        to_tick: i64,
+        timestamp: i64,
+    ) -> Result<bool, BacktestError> {
+        let mut t = self.depth.best_bid_tick();
+        while t != INVALID_MIN && t >= to_tick {
+            let exec_qty = self.depth.bid_qty_at_tick(t).min(order.leaves_qty);
+            self.fill(order, timestamp, false, t, exec_qty)?;
+            if order.status == Status::Filled {
+                return Ok(true);
+            }
+            t = self.depth.next_bid_tick(t);
+        }
+        Ok(false)
+    }
+
+    /// Sends the response for the order that expires without being fully executed.
+    fn expire_unfilled(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        order.status = Status::Expired;
+
+        order.exch_timestamp = timestamp;
+        let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+        self.orders_to.append(order, local_recv_timestamp);
+        Ok(())
+    }
+
+    fn fill_ask_orders_by_crossing(
+        &mut self,
+        prev_best_tick: i64,
+        new_best_tick: i64,
+        timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        let filled = self
+            .queue_model
+            .on_best_bid_update(prev_best_tick, new_best_tick)?;
+        for mut order in filled {
+            let (price_tick, leaves_qty) = (order.price_tick, order.leaves_qty);
+            self.fill(&mut order, timestamp, true, price_tick, leaves_qty)?;
+        }
+        Ok(())
+    }
+
+    fn fill_bid_orders_by_crossing(
+        &mut self,
+        prev_best_tick: i64,
+        new_best_tick: i64,
+        timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        let filled = self
+            .queue_model
+            .on_best_ask_update(prev_best_tick, new_best_tick)?;
+        for mut order in filled {
+            let (price_tick, leaves_qty) = (order.price_tick, order.leaves_qty);
+            self.fill(&mut order, timestamp, true, price_tick, leaves_qty)?;
+        }
+        Ok(())
+    }
+
+    fn check_liquidation(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let fills = self.state.liquidation_fills(&self.depth);
+        if fills.is_empty() {
+            return Ok(());
+        }
+        // The open orders are canceled first so that they cannot reopen the position.
+        self.cancel_all_orders(timestamp)?;
+        for mut order in fills {
+            self.state.apply_fill(&order);
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+        Ok(())
+    }
+
+    /// Cancels all open orders, including the untriggered conditional orders, in the order of their
+    /// order IDs.
+    fn cancel_all_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let mut open_orders: Vec<_> = self
+            .queue_model
+            .backtest_orders()
+            .into_iter()
+            .chain(self.conditional_orders.orders())
+            .cloned()
+            .collect();
+        open_orders.sort_by_key(|order| order.order_id);
+        for order in open_orders {
+            self.ack_cancel(order, timestamp)?;
+        }
+        Ok(())
+    }
+
+    fn trigger_conditional_orders(
+        &mut self,
+        source: TriggerSource,
+        price_tick: i64,
+        timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        for mut order in self.conditional_orders.update(source, price_tick) {
+            if !self.state.has_margin_for(&order, &self.depth) {
+                // The order has already been accepted, so it expires instead of being rejected.
+                order.status = Status::Expired;
+                order.reject_reason = RejectReason::InsufficientMargin;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+            } else {
+                self.ack_new(order, timestamp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expire_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        for order_id in self.order_expiry.pop_expired(timestamp) {
+            let exch_order = if self
+                .conditional_orders
+                .get(order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                self.conditional_orders.cancel(order_id)
+            } else if self
+                .queue_model
+                .backtest_order(order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                Some(self.queue_model.cancel_backtest_order(order_id, &self.depth)?)
+            } else {
+                // The order has already been filled or canceled.
+                None
+            };
+            if let Some(mut exch_order) = exch_order {
+                exch_order.status = Status::Expired;
+                exch_order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &exch_order);
+                self.orders_to.append(exch_order, local_recv_timestamp);
+            }
+        }
+        Ok(())
+    }
+
+    /// Cancels the resting reduce-only orders that would increase the position, which the fills
+    /// have changed.
+    fn expire_reduce_only_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let lot_size = self.depth.lot_size();
+        for order_id in self
+            .reduce_only_orders
+            .pop_position_increasing(self.state.values(), lot_size)
+        {
+            if !self
+                .queue_model
+                .backtest_order(order_id)
+                .is_some_and(ReduceOnlyOrders::is_reduce_only)
+            {
+                // The order has already been filled or canceled.
+                continue;
+            }
+            let mut order = self.queue_model.cancel_backtest_order(order_id, &self.depth)?;
+            order.status = Status::Expired;
+            order.reject_reason = RejectReason::ReduceOnly;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+        Ok(())
+    }
+
+    fn ack_new(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if self.queue_model.contains_backtest_order(order.order_id) {
+            return Err(BacktestError::OrderIdExist);
+        }
+        if self.conditional_orders.contains(order.order_id) {
+            return Err(BacktestError::OrderIdExist);
+        }
+
+        // The triggered order has already been checked against the rules when it was placed.
+        if !order.triggered {
+            let rule_check = self.instrument_rules.check(
+                order.order_type,
+                order.price(),
+                order.qty,
+                &self.depth,
+            );
+            if let Err(reason) = rule_check {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
+        // A good-till-date order whose expire timestamp has already passed is not accepted.
+        if OrderExpiry::has_expired(&order, timestamp) {
+            order.status = Status::Expired;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+        self.order_expiry.schedule(&order);
+
+        // The conditional order is held without being matched until it is triggered.
+        if order.order_type.is_conditional() {
+            order.exch_timestamp = timestamp;
+            if let Err(reason) = self.conditional_orders.add(&mut order) {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+            }
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if ReduceOnlyOrders::is_reduce_only(&order) {
+            // The reduce-only order is clipped to the position, and is not accepted if it would
+            // only increase the position.
+            let reducible_qty = ReduceOnlyOrders::reducible_qty(
+                order.side,
+                order.position_side,
+                self.state.values(),
+            );
+            if (reducible_qty / self.depth.lot_size()).round() <= 0.0 {
+                if order.triggered {
+                    // The triggered order has already been accepted, so it expires instead.
+                    order.status = Status::Expired;
+                } else {
+                    order.req = Status::Rejected;
+                }
+                order.reject_reason = RejectReason::ReduceOnly;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+            if order.qty > reducible_qty {
+                order.qty = reducible_qty;
+                order.leaves_qty = reducible_qty;
+            }
+            self.reduce_only_orders.add(&order);
+        }
+
+        if !self.state.has_margin_for(&order, &self.depth) {
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::InsufficientMargin;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        self.match_order(order, timestamp)
+    }
+
+    /// Matches the order that has passed the admission checks against the order book, and either
+    /// rests it in the queue or executes it according to its order type and time-in-force.
+    fn match_order(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if order.side == Side::Buy {
+            match order.order_type {
+                OrdType::Limit => {
+                    // Checks if the buy order price is greater than or equal to the current best ask.
+                    if order.price_tick >= self.depth.best_ask_tick() {
+                        match order.time_in_force {
+                            TimeInForce::GTX => self.expire_unfilled(order, timestamp),
+                            TimeInForce::FOK => {
+                                // The order must be executed immediately in its entirety; otherwise,
+                                // the entire order will be cancelled.
+                                let mut cum_qty = 0f64;
+                                for t in self.depth.best_ask_tick()..=order.price_tick {
+                                    cum_qty += self.depth.ask_qty_at_tick(t);
+                                }
+                                if (cum_qty / self.depth.lot_size()).round()
+                                    >= (order.qty / self.depth.lot_size()).round()
+                                {
+                                    let price_tick = order.price_tick;
+                                    self.take_asks(&mut order, price_tick, timestamp)?;
+                                    Ok(())
+                                } else {
+                                    self.expire_unfilled(order, timestamp)
+                                }
+                            }
+                            TimeInForce::IOC => {
+                                // The order must be executed immediately.
+                                let price_tick = order.price_tick;
+                                if self.take_asks(&mut order, price_tick, timestamp)? {
+                                    return Ok(());
+                                }
+                                self.expire_unfilled(order, timestamp)
+                            }
+                            TimeInForce::GTC | TimeInForce::GTD => {
+                                // Takes the market.
+                                let price_tick = order.price_tick;
+                                if self.take_asks(&mut order, price_tick - 1, timestamp)? {
+                                    return Ok(());
+                                }
+
+                                // The buy order cannot remain in the ask book, as it cannot affect
+                                // the market depth during backtesting based on market-data replay.
+                                // So, the remaining quantity is executed at the order price.
+                                let leaves_qty = order.leaves_qty;
+                                self.fill(&mut order, timestamp, false, price_tick, leaves_qty)
+                            }
+                            TimeInForce::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                        }
+                    } else {
+                        match order.time_in_force {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
+                                // Initializes the order's queue position.
+                                order.status = Status::New;
+                                order.exch_timestamp = timestamp;
+
+                                self.queue_model
+                                    .add_backtest_order(order.clone(), &self.depth)?;
+
+                                let local_recv_timestamp =
+                                    timestamp + self.order_latency.response(timestamp, &order);
+                                self.orders_to.append(order, local_recv_timestamp);
+                                Ok(())
+                            }
+                            TimeInForce::FOK | TimeInForce::IOC => {
+                                self.expire_unfilled(order, timestamp)
+                            }
+                            TimeInForce::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                        }
+                    }
+                }
+                OrdType::Market => {
+                    // Takes the market up to the price band.
+                    let limit_tick = self.price_band.buy_limit_tick(self.depth.best_ask_tick());
+                    if self.take_asks(&mut order, limit_tick, timestamp)? {
+                        return Ok(());
+                    }
+                    self.expire_unfilled(order, timestamp)
+                }
+                _ => Err(BacktestError::InvalidOrderRequest),
+            }
+        } else {
+            match order.order_type {
+                OrdType::Limit => {
+                    // Checks if the sell order price is less than or equal to the current best bid.
+                    if order.price_tick <= self.depth.best_bid_tick() {
+                        match order.time_in_force {
+                            TimeInForce::GTX => self.expire_unfilled(order, timestamp),
+                            TimeInForce::FOK => {
+                                // The order must be executed immediately in its entirety; otherwise,
+                                // the entire order will be cancelled.
+                                let mut cum_qty = 0f64;
+                                for t in order.price_tick..=self.depth.best_bid_tick() {
+                                    cum_qty += self.depth.bid_qty_at_tick(t);
+                                }
+                                if (cum_qty / self.depth.lot_size()).round()
+                                    >= (order.qty / self.depth.lot_size()).round()
+                                {
+                                    let price_tick = order.price_tick;
+                                    self.take_bids(&mut order, price_tick, timestamp)?;
+                                    Ok(())
+                                } else {
+                                    self.expire_unfilled(order, timestamp)
+                                }
+                            }
+                            TimeInForce::IOC => {
+                                // The order must be executed immediately.
+                                let price_tick = order.price_tick;
+                                if self.take_bids(&mut order, price_tick, timestamp)? {
+                                    return Ok(());
+                                }
+                                self.expire_unfilled(order, timestamp)
+                            }
+                            TimeInForce::GTC | TimeInForce::GTD => {
+                                // Takes the market.
+                                let price_tick = order.price_tick;
+                                if self.take_bids(&mut order, price_tick + 1, timestamp)? {
+                                    return Ok(());
+                                }
+
+                                // The sell order cannot remain in the bid book, as it cannot affect
+                                // the market depth during backtesting based on market-data replay.
+                                // So, the remaining quantity is executed at the order price.
+                                let leaves_qty = order.leaves_qty;
+                                self.fill(&mut order, timestamp, false, price_tick, leaves_qty)
+                            }
+                            TimeInForce::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                        }
+                    } else {
+                        match order.time_in_force {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
+                                // Initializes the order's queue position.
+                                order.status = Status::New;
+                                order.exch_timestamp = timestamp;
+
+                                self.queue_model
+                                    .add_backtest_order(order.clone(), &self.depth)?;
+
+                                let local_recv_timestamp =
+                                    timestamp + self.order_latency.response(timestamp, &order);
+                                self.orders_to.append(order, local_recv_timestamp);
+                                Ok(())
+                            }
+                            TimeInForce::FOK | TimeInForce::IOC => {
+                                self.expire_unfilled(order, timestamp)
+                            }
+                            TimeInForce::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                        }
+                    }
+                }
+                OrdType::Market => {
+                    // Takes the market down to the price band.
+                    let limit_tick = self.price_band.sell_limit_tick(self.depth.best_bid_tick());
+                    if self.take_bids(&mut order, limit_tick, timestamp)? {
+                        return Ok(());
+                    }
+                    self.expire_unfilled(order, timestamp)
+                }
+                _ => Err(BacktestError::InvalidOrderRequest),
+            }
+        }
+    }
+
+    fn ack_cancel(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if let Some(mut exch_order) = self.conditional_orders.cancel(order.order_id) {
+            exch_order.status = Status::Canceled;
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        match self
+            .queue_model
+            .cancel_backtest_order(order.order_id, &self.depth)
+        {
+            Ok(mut exch_order) => {
+                // Makes the response.
+                exch_order.status = Status::Canceled;
+                exch_order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &exch_order);
+                self.orders_to
+                    .append(exch_order.clone(), local_recv_timestamp);
+                Ok(())
+            }
+            Err(BacktestError::OrderNotFound) => {
+                order.req = Status::Rejected;
+                order.reject_reason = RejectReason::UnknownOrder;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn ack_modify(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        let rule_check = self.instrument_rules.check(
+            order.order_type,
+            order.price(),
+            order.qty,
+            &self.depth,
+        );
+        if let Err(reason) = rule_check {
+            order.req = Status::Rejected;
+            order.reject_reason = reason;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if let Some(mut exch_order) = self.conditional_orders.modify(&order) {
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        let mut exch_order = match self.queue_model.backtest_order(order.order_id) {
+            Some(exch_order) => exch_order.clone(),
+            None => {
+                // The order can be already deleted due to fill or expiration.
+                order.req = Status::Rejected;
+                order.reject_reason = RejectReason::UnknownOrder;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        };
+
+        // The modified quantity is the total order quantity, including the quantity already
+        // executed. The modification is rejected if it does not exceed the executed quantity.
+        let filled_qty = exch_order.qty - exch_order.leaves_qty;
+        let leaves_qty = order.qty - filled_qty;
+        if (leaves_qty / self.depth.lot_size()).round() <= 0.0 {
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::BelowFilledQty;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        exch_order.price_tick = order.price_tick;
+        exch_order.qty = order.qty;
+        exch_order.leaves_qty = leaves_qty;
+        // The response to the modification is not a fill, even if the order has already been
+        // partially filled.
+        exch_order.exec_qty = 0.0;
+        exch_order.exch_timestamp = timestamp;
+
+        let cross = match exch_order.side {
+            // Checks if the buy order price is greater than or equal to the current best ask.
+            Side::Buy => exch_order.price_tick >= self.depth.best_ask_tick(),
+            // Checks if the sell order price is less than or equal to the current best bid.
+            Side::Sell => exch_order.price_tick <= self.depth.best_bid_tick(),
+            Side::None | Side::Unsupported => unreachable!(),
+        };
+        if cross {
+            // The order is matched in the same way as a new order that crosses the book, without
+            // going through the admission checks again.
+            self.queue_model
+                .cancel_backtest_order(exch_order.order_id, &self.depth)?;
+            return self.match_order(exch_order, timestamp);
+        }
+
+        // The queue model re-evaluates the order's queue position against the market-feed orders.
+        self.queue_model
+            .modify_backtest_order(exch_order.order_id, exch_order.clone(), &self.depth)?;
+
+        let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &exch_order);
+        self.orders_to.append(exch_order, local_recv_timestamp);
+        Ok(())
+    }
+}
+
+impl<AT, LM, QM, MD, FM, MM> Processor for L3PartialFillExchange<AT, LM, QM, MD, FM, MM>
+where
+    AT: AssetType,
+    LM: LatencyModel,
+    QM: L3QueueModel<MD>,
+    MD: L3MarketDepth + ApplySnapshot,
+    FM: FeeModel,
+    MM: MarginModel,
+    BacktestError: From<<MD as L3MarketDepth>::Error>,
+{
+    fn initialize_data(&mut self) -> Result<i64, BacktestError> {
+        self.data = self.reader.next_data()?;
+        for rn in 0..self.data.len() {
+            if self.data[rn].is(EXCH_EVENT) {
+                self.row_num = rn;
+                return Ok(self.data[rn].exch_ts);
+            }
+        }
+        Err(BacktestError::EndOfData)
+    }
+
+    fn process_data(&mut self) -> Result<(i64, i64), BacktestError> {
+        let row_num = self.row_num;
+        if self.data[row_num].is(EXCH_BID_DEPTH_CLEAR_EVENT) {
+            self.depth.clear_orders(Side::Buy);
+            let expired = self.queue_model.clear_orders(Side::Buy);
+            for order in expired {
+                self.expired(order, self.data[row_num].exch_ts)?;
+            }
+        } else if self.data[row_num].is(EXCH_ASK_DEPTH_CLEAR_EVENT) {
+            self.depth.clear_orders(Side::Sell);
+            let expired = self.queue_model.clear_orders(Side::Sell);
+            for order in expired {
+                self.expired(order, self.data[row_num].exch_ts)?;
+            }
+        } else if self.data[row_num].is(EXCH_DEPTH_CLEAR_EVENT) {
+            self.depth.clear_orders(Side::None);
+            let expired = self.queue_model.clear_orders(Side::None);
+            for order in expired {
+                self.expired(order, self.data[row_num].exch_ts)?;
+            }
+        } else if self.data[row_num].is(EXCH_BID_ADD_ORDER_EVENT) {
+            let (prev_best_bid_tick, best_bid_tick) = self.depth.add_buy_order(
+                self.data[row_num].order_id,
+                self.data[row_num].px,
+                self.data[row_num].qty,
+                self.data[row_num].exch_ts,
+            )?;
+            self.queue_model
+                .add_market_feed_order(&self.data[row_num], &self.depth)?;
+            if best_bid_tick > prev_best_bid_tick {
+                self.fill_ask_orders_by_crossing(
+                    prev_best_bid_tick,
+                    best_bid_tick,
+                    self.data[row_num].exch_ts,
+                )?;
+            }
+        } else if self.data[row_num].is(EXCH_ASK_ADD_ORDER_EVENT) {
+            let (prev_best_ask_tick, best_ask_tick) = self.depth.add_sell_order(
+                self.data[row_num].order_id,
+                self.data[row_num].px,
+                self.data[row_num].qty,
+                self.data[row_num].exch_ts,
+            )?;
+            self.queue_model
+                .add_market_feed_order(&self.data[row_num], &self.depth)?;
+            if best_ask_tick < prev_best_ask_tick {
+                self.fill_bid_orders_by_crossing(
+                    prev_best_ask_tick,
+                    best_ask_tick,
+                    self.data[row_num].exch_ts,
+                )?;
+            }
+        } else if self.data[row_num].is(EXCH_MODIFY_ORDER_EVENT) {
+            let (side, prev_best_tick, best_tick) = self.depth.modify_order(
+                self.data[row_num].order_id,
+                self.data[row_num].px,
+                self.data[row_num].qty,
+                self.data[row_num].exch_ts,
+            )?;
+            self.queue_model.modify_market_feed_order(
+                self.data[row_num].order_id,
+                &self.data[row_num],
+                &self.depth,
+            )?;
+            if side == Side::Buy {
+                if best_tick > prev_best_tick {
+                    self.fill_ask_orders_by_crossing(
+                        prev_best_tick,
+                        best_tick,
+                        self.data[row_num].exch_ts,
+                    )?;
+                }
+            } else if best_tick < prev_best_tick {
+                self.fill_bid_orders_by_crossing(
+                    prev_best_tick,
+                    best_tick,
+                    self.data[row_num].exch_ts,
+                )?;
+            }
+        } else if self.data[row_num].is(EXCH_CANCEL_ORDER_EVENT) {
+            let order_id = self.data[row_num].order_id;
+            self.depth
+                .delete_order(order_id, self.data[row_num].exch_ts)?;
+            self.queue_model
+                .cancel_market_feed_order(self.data[row_num].order_id, &self.depth)?;
+        } else if self.data[row_num].is(EXCH_FILL_EVENT) {
+            // todo: handle properly if no side is provided.
+            if self.data[row_num].is(BUY_EVENT) || self.data[row_num].is(SELL_EVENT) {
+                let filled = self.queue_model.partially_fill_market_feed_order::<false>(
+                    self.data[row_num].order_id,
+                    &self.data[row_num],
+                    &self.depth,
+                )?;
+                let timestamp = self.data[row_num].exch_ts;
+                for (mut order, exec_qty) in filled {
+                    let price_tick = order.price_tick;
+                    self.fill(&mut order, timestamp, true, price_tick, exec_qty)?;
+                }
+                let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+                self.trigger_conditional_orders(TriggerSource::LastPrice, price_tick, timestamp)?;
+            }
+        } else if self.data[row_num].is(EXCH_FUNDING_EVENT) {
+            self.state
+                .apply_funding(self.data[row_num].px, self.data[row_num].fval);
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        } else if self.data[row_num].is(EXCH_MARK_PRICE_EVENT) {
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        }
+        self.check_liquidation(self.data[row_num].exch_ts)?;
+        self.expire_reduce_only_orders(self.data[row_num].exch_ts)?;
+        self.order_latency
+            .on_feed(self.data[row_num].exch_ts, &self.data[row_num]);
+
+        // Checks
+        let mut next_ts = 0;
+        for rn in (self.row_num + 1)..self.data.len() {
+            if self.data[rn].is(EXCH_EVENT) {
+                self.row_num = rn;
+                next_ts = self.data[rn].exch_ts;
+                break;
+            }
+        }
+
+        if next_ts <= 0 {
+            let next_data = self.reader.next_data()?;
+            let next_row = &next_data[0];
+            next_ts = next_row.exch_ts;
+            let data = mem::replace(&mut self.data, next_data);
+            self.reader.release(data);
+            self.row_num = 0;
+        }
+        Ok((next_ts, i64::MAX))
+    }
+
+    fn process_recv_order(
+        &mut self,
+        timestamp: i64,
+        _wait_resp_order_id: Option<OrderId>,
+    ) -> Result<bool, BacktestError> {
+        self.expire_orders(timestamp)?;
+
+        // Processes the order part.
+        while !self.orders_from.is_empty() {
+            let recv_timestamp = self.orders_from.earliest_timestamp().unwrap();
+            if timestamp == recv_timestamp {
+                let (order, _) = self.orders_from.pop_front().unwrap();
+                self.order_latency.on_request(order.req);
+                self.process_recv_order_(order, recv_timestamp)?;
+                self.order_latency.on_request(Status::None);
+            } else {
+                assert!(recv_timestamp > timestamp);
+                break;
+            }
+        }
+        self.expire_reduce_only_orders(timestamp)?;
+        Ok(false)
+    }
+
+    fn earliest_recv_order_timestamp(&self) -> i64 {
+        // Wakes up at the expire timestamp of the good-till-date order as well.
+        self.orders_from
+            .earliest_timestamp()
+            .unwrap_or(i64::MAX)
+            .min(self.order_expiry.earliest_timestamp().unwrap_or(i64::MAX))
+    }
+
+    fn earliest_send_order_timestamp(&self) -> i64 {
+        self.orders_to.earliest_timestamp().unwrap_or(i64::MAX)
+    }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        checkpoint::encode(&ExchCheckpoint {
+            data_num: self.reader.data_num(),
+            row_num: self.row_num,
+            depth: DepthCheckpoint::l3(&self.depth),
+            state_values: self.state.state_values.clone(),
+            orders: Vec::new(),
+            queue: self.queue_model.checkpoint()?,
+            conditional_orders: checkpoint::encode(&self.conditional_orders)?,
+            order_expiry: checkpoint::encode(&self.order_expiry)?,
+            reduce_only_orders: checkpoint::encode(&self.reduce_only_orders)?,
+            rate_limit: match &self.rate_limit {
+                Some(rate_limit) => rate_limit.checkpoint()?,
+                None => Vec::new(),
+            },
+            consumed_bid: Vec::new(),
+            consumed_ask: Vec::new(),
+            order_latency: self.order_latency.checkpoint()?,
+        })
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let state: ExchCheckpoint = checkpoint::decode(checkpoint)?;
+        checkpoint::restore_data(&mut self.reader, &mut self.data, state.data_num)?;
+        self.row_num = state.row_num;
+        state.depth.restore_l3(&mut self.depth)?;
+        self.state.state_values = state.state_values;
+        self.conditional_orders = checkpoint::decode(&state.conditional_orders)?;
+        self.order_expiry = checkpoint::decode(&state.order_expiry)?;
+        self.reduce_only_orders = checkpoint::decode(&state.reduce_only_orders)?;
+        if let Some(rate_limit) = &mut self.rate_limit {
+            rate_limit.restore(&state.rate_limit)?;
+        }
+        self.order_latency.restore(&state.order_latency)?;
+        self.queue_model.restore(&state.queue)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            data::Data,
+            fixture::{l3_asset_builder, l3_event, to_data},
+            Backtest,
+            ExchangeKind,
+        },
+        depth::HashMapMarketDepth,
+        prelude::{Bot, OrdType, RejectReason, Status, TimeInForce},
+        types::{Event, ADD_ORDER_EVENT, BUY_EVENT, CANCEL_ORDER_EVENT, FILL_EVENT, SELL_EVENT},
+    };
+
+    fn build_backtest(data: &[Data<Event>]) -> Backtest<HashMapMarketDepth> {
+        Backtest::builder()
+            .add_asset(
+                l3_asset_builder(data)
+                    .exchange(ExchangeKind::PartialFillExchange)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn fill_resting_order_in_queue() {
+        let data = [to_data(&[
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 1_000, 1, 99.0, 2.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 2, 101.0, 3.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 3_000, 3, 99.0, 5.0),
+            // The market-feed order ahead of the backtest order is filled.
+            l3_event(BUY_EVENT | FILL_EVENT, 4_000, 1, 99.0, 2.0),
+            l3_event(BUY_EVENT | CANCEL_ORDER_EVENT, 4_000, 1, 99.0, 0.0),
+            // The fill of the market-feed order behind executes the backtest order up to the fill
+            // quantity.
+            l3_event(BUY_EVENT | FILL_EVENT, 5_000, 3, 99.0, 2.0),
+            l3_event(BUY_EVENT | FILL_EVENT, 6_000, 3, 99.0, 4.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 20_000, 4, 98.0, 1.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_buy_order(0, 1, 99.0, 3.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+
+        hbt.elapse(2_500).unwrap();
+        assert_eq!(hbt.orders(0)[&1].status, Status::New);
+        assert_eq!(hbt.position(0), 0.0);
+
+        hbt.elapse(1_000).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::PartiallyFilled);
+        assert_eq!(order.leaves_qty, 1.0);
+        assert_eq!(hbt.position(0), 2.0);
+
+        // The partially filled order keeps its queue position.
+        hbt.elapse(1_000).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_qty, 1.0);
+        assert_eq!(hbt.position(0), 3.0);
+    }
+
+    #[test]
+    fn walk_order_book_with_taker_order() {
+        let data = [to_data(&[
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 1_000, 1, 99.0, 2.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 1_000, 2, 98.0, 2.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 3, 101.0, 1.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 4, 101.0, 1.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 5, 102.0, 2.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 6, 104.0, 5.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 20_000, 7, 97.0, 1.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        // Takes both orders at 101 and the order at 102, and the rest expires as there is no
+        // quantity at 103.
+        hbt.submit_buy_order(0, 1, 103.0, 5.0, TimeInForce::IOC, OrdType::Limit, true)
+            .unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.leaves_qty, 1.0);
+        assert_eq!(hbt.position(0), 4.0);
+
+        // The quantity up to 104 is not enough to fill the order in its entirety.
+        hbt.submit_buy_order(0, 2, 104.0, 10.0, TimeInForce::FOK, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.orders(0)[&2].status, Status::Expired);
+        assert_eq!(hbt.position(0), 4.0);
+
+        hbt.submit_sell_order(0, 3, 98.0, 3.0, TimeInForce::GTC, OrdType::Market, true)
+            .unwrap();
+        let order = &hbt.orders(0)[&3];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_price_tick, 98);
+        assert_eq!(hbt.position(0), 1.0);
+    }
+
+    #[test]
+    fn cancel_and_modify_order() {
+        let data = [to_data(&[
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 1_000, 1, 99.0, 2.0),
+            l3_event(SELL_EVENT | ADD_ORDER_EVENT, 1_000, 2, 101.0, 3.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 3_000, 3, 99.0, 5.0),
+            l3_event(BUY_EVENT | FILL_EVENT, 5_000, 3, 99.0, 2.0),
+            l3_event(BUY_EVENT | FILL_EVENT, 8_000, 3, 99.0, 1.0),
+            l3_event(BUY_EVENT | ADD_ORDER_EVENT, 20_000, 4, 98.0, 1.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_buy_order(0, 1, 99.0, 3.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        hbt.submit_buy_order(0, 2, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        hbt.elapse(3_500).unwrap();
+        assert_eq!(hbt.orders(0)[&1].status, Status::PartiallyFilled);
+        assert_eq!(hbt.position(0), 2.0);
+
+        // The modified quantity doesn't exceed the quantity already filled.
+        hbt.modify(0, 1, 99.0, 2.0, true).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.qty, 3.0);
+        assert_eq!(order.reject_reason, RejectReason::BelowFilledQty);
+
+        // Increasing the quantity sends the order behind the market-feed order at 99, so the
+        // subsequent fill of the market-feed order doesn't reach it.
+        hbt.modify(0, 1, 99.0, 4.0, true).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.qty, 4.0);
+        assert_eq!(order.leaves_qty, 2.0);
+        hbt.elapse(3_000).unwrap();
+        assert_eq!(hbt.orders(0)[&1].leaves_qty, 2.0);
+        assert_eq!(hbt.position(0), 2.0);
+
+        // The modified order that crosses the order book takes the market.
+        hbt.modify(0, 2, 101.0, 1.0, true).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_price_tick, 101);
+        assert_eq!(hbt.position(0), 3.0);
+
+        hbt.cancel(0, 1, true).unwrap();
+        assert_eq!(hbt.orders(0)[&1].status, Status::Canceled);
+
+        // The exchange no longer holds the canceled order.
+        hbt.cancel(0, 1, true).unwrap();
+        assert_eq!(hbt.orders(0)[&1].reject_reason, RejectReason::UnknownOrder);
+        assert_eq!(hbt.position(0), 3.0);
+    }
+}
@@ -6,19 +6,25 @@ use std::{
 use crate::{
     backtest::{
         assettype::AssetType,
+        checkpoint::{self, DepthCheckpoint, LocalCheckpoint},
         data::{Data, Reader},
-        models::{FeeModel, LatencyModel},
+        models::{FaultInjector, FeeModel, LatencyModel},
+        observer::BacktestObserver,
         order::OrderBus,
         proc::{LocalProcessor, Processor},
         state::State,
         BacktestError,
+        InstrumentRules,
     },
-    depth::{L2MarketDepth, MarketDepth},
+    depth::{ApplySnapshot, L2MarketDepth, MarketDepth},
     types::{
         Event,
         OrdType,
         Order,
         OrderId,
+        OrderRequest,
+        PositionSide,
+        RejectReason,
         Side,
         StateValues,
         Status,
@@ -31,6 +37,7 @@ use crate::{
         LOCAL_BID_DEPTH_SNAPSHOT_EVENT,
         LOCAL_DEPTH_CLEAR_EVENT,
         LOCAL_EVENT,
+        LOCAL_FUNDING_EVENT,
         LOCAL_TRADE_EVENT,
     },
 };
@@ -55,6 +62,10 @@ where
     trades: Vec<Event>,
     last_feed_latency: Option<(i64, i64)>,
     last_order_latency: Option<(i64, i64, i64)>,
+    observer: Option<Box<dyn BacktestObserver<MD>>>,
+    instrument_rules: InstrumentRules,
+    out_of_order_requests: bool,
+    fault_injector: Option<FaultInjector>,
 }
 
 impl<AT, LM, MD, FM> Local<AT, LM, MD, FM>
@@ -87,19 +98,97 @@ where
             trades: Vec::with_capacity(last_trades_cap),
             last_feed_latency: None,
             last_order_latency: None,
+            observer: None,
+            instrument_rules: Default::default(),
+            out_of_order_requests: false,
+            fault_injector: None,
         }
     }
 
-    fn process_recv_order_(&mut self, order: Order) -> Result<(), BacktestError> {
-        if order.status == Status::Filled {
-            self.state.apply_fill(&order);
+    /// Registers a [`BacktestObserver`] that gets notified of the order responses and the market
+    /// feed.
+    pub fn with_observer(self, observer: Box<dyn BacktestObserver<MD>>) -> Self {
+        Self {
+            observer: Some(observer),
+            ..self
+        }
+    }
+
+    /// Sets the [`InstrumentRules`] that the orders are checked against before being sent to the
+    /// exchange.
+    pub fn with_instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
+        }
+    }
+
+    /// Allows an order to be canceled while its new order request is still in flight, which only
+    /// makes sense when the requests can overtake each other on the way to the exchange.
+    pub fn with_out_of_order_requests(self, out_of_order_requests: bool) -> Self {
+        Self {
+            out_of_order_requests,
+            ..self
+        }
+    }
+
+    /// Registers a [`FaultInjector`] that applies faults to the requests sent to and the responses
+    /// received from the exchange.
+    pub fn with_fault_injector(self, fault_injector: FaultInjector) -> Self {
+        Self {
+            fault_injector: Some(fault_injector),
+            ..self
+        }
+    }
+
+    fn entry_latency(&mut self, timestamp: i64, order: &Order) -> i64 {
+        let entry_latency = self.order_latency.entry(timestamp, order);
+        match &mut self.fault_injector {
+            Some(fault_injector) => fault_injector.request(timestamp, entry_latency),
+            None => entry_latency,
+        }
+    }
+
+    fn process_recv_order_(&mut self, order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        self.apply_fill(&order, timestamp);
+        self.update_order(order, timestamp)
+    }
+
+    /// Applies the fill in the order response, if any, to the state.
+    fn apply_fill(&mut self, order: &Order, timestamp: i64) {
+        // A rejected request carries the status of the local order it was made from, which
+        // doesn't represent a new fill.
+        let filled = (order.status == Status::Filled
+            || order.status == Status::PartiallyFilled
+            || order.status == Status::Liquidated)
+            && order.exec_qty > 0.0
+            && order.req != Status::Rejected;
+        if filled {
+            self.state.apply_fill(order);
+            if let Some(observer) = &mut self.observer {
+                observer.on_fill(order, timestamp);
+            }
+        }
+    }
+
+    fn update_order(&mut self, order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if let Some(observer) = &mut self.observer {
+            observer.on_order_update(&order, timestamp);
         }
         // Applies the received order response to the local orders.
         match self.orders.entry(order.order_id) {
             Entry::Occupied(mut entry) => {
                 let local_order = entry.get_mut();
                 if order.req == Status::Rejected {
-                    if order.local_timestamp == local_order.local_timestamp {
+                    if local_order.req == Status::New
+                        && order.reject_reason == RejectReason::UnknownOrder
+                    {
+                        // A cancel that overtook the new order request is rejected because the
+                        // exchange doesn't hold the order yet. The order is still in flight, so
+                        // only the rejection is recorded, for the strategy to cancel it again.
+                        local_order.reject_reason = order.reject_reason;
+                    } else if order.local_timestamp == local_order.local_timestamp {
+                        local_order.reject_reason = order.reject_reason;
                         if local_order.req == Status::New {
                             local_order.req = Status::None;
                             local_order.status = Status::Expired;
@@ -125,7 +214,7 @@ impl<AT, LM, MD, FM> LocalProcessor<MD> for Local<AT, LM, MD, FM>
 where
     AT: AssetType,
     LM: LatencyModel,
-    MD: MarketDepth + L2MarketDepth,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot,
     FM: FeeModel,
 {
     fn submit_order(
@@ -138,25 +227,88 @@ where
         time_in_force: TimeInForce,
         current_timestamp: i64,
     ) -> Result<(), BacktestError> {
-        if self.orders.contains_key(&order_id) {
+        self.submit_order_request(
+            &OrderRequest {
+                order_id,
+                price,
+                qty,
+                side,
+                time_in_force,
+                order_type,
+                trigger: None,
+                expire_timestamp: 0,
+                display_qty: None,
+                reduce_only: false,
+                position_side: PositionSide::Both,
+            },
+            current_timestamp,
+        )
+    }
+
+    fn submit_order_request(
+        &mut self,
+        request: &OrderRequest,
+        current_timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        // Only the conditional order types take a trigger condition, and they require one.
+        if request.order_type.is_conditional() != request.trigger.is_some() {
+            return Err(BacktestError::InvalidOrderRequest);
+        }
+        if request.time_in_force == TimeInForce::GTD && request.expire_timestamp <= 0 {
+            return Err(BacktestError::InvalidOrderRequest);
+        }
+        if let Some(display_qty) = request.display_qty {
+            // An iceberg order should display at least one lot at a time.
+            let display_lots = (display_qty / self.depth.lot_size()).round();
+            if display_qty != 0.0 && !(display_qty.is_finite() && display_lots >= 1.0) {
+                return Err(BacktestError::InvalidOrderRequest);
+            }
+        }
+        if self.orders.contains_key(&request.order_id) {
             return Err(BacktestError::OrderIdExist);
         }
+        let rule_check = self.instrument_rules.check(
+            request.order_type,
+            request.price,
+            request.qty,
+            &self.depth,
+        );
 
-        let price_tick = (price / self.depth.tick_size()).round() as i64;
+        let price_tick = (request.price / self.depth.tick_size()).round() as i64;
         let mut order = Order::new(
-            order_id,
+            request.order_id,
             price_tick,
             self.depth.tick_size(),
-            qty,
-            side,
-            order_type,
-            time_in_force,
+            request.qty,
+            request.side,
+            request.order_type,
+            request.time_in_force,
         );
+        if let Some(trigger) = &request.trigger {
+            order.set_trigger(trigger);
+        }
+        if request.time_in_force == TimeInForce::GTD {
+            order.expire_timestamp = request.expire_timestamp;
+        }
+        if let Some(display_qty) = request.display_qty {
+            order.display_qty = display_qty;
+        }
+        order.reduce_only = request.reduce_only;
+        order.position_side = request.position_side;
         order.req = Status::New;
         order.local_timestamp = current_timestamp;
         self.orders.insert(order.order_id, order.clone());
 
-        let order_entry_latency = self.order_latency.entry(current_timestamp, &order);
+        // The order that breaks the instrument rules is rejected right away without reaching the
+        // exchange.
+        if let Err(reason) = rule_check {
+            order.req = Status::Rejected;
+            order.reject_reason = reason;
+            self.orders_from.append(order, current_timestamp);
+            return Ok(());
+        }
+
+        let order_entry_latency = self.entry_latency(current_timestamp, &order);
         // Negative latency indicates that the order is rejected for technical reasons, and its
         // value represents the latency that the local experiences when receiving the rejection
         // notification.
@@ -178,24 +330,85 @@ where
             .get_mut(&order_id)
             .ok_or(BacktestError::OrderNotFound)?;
 
+        let in_flight = self.out_of_order_requests && order.req == Status::New;
+        if order.req != Status::None && !in_flight {
+            return Err(BacktestError::OrderRequestInProcess);
+        }
+
+        // If the new order request is still in flight, the local order keeps waiting for its
+        // response, and the cancel is sent as a separate request timestamped when it is sent.
+        let req_order = if in_flight {
+            let mut req_order = order.clone();
+            req_order.req = Status::Canceled;
+            req_order.local_timestamp = current_timestamp;
+            req_order
+        } else {
+            order.req = Status::Canceled;
+            order.clone()
+        };
+        let order_entry_latency = self.entry_latency(current_timestamp, &req_order);
+        // Negative latency indicates that the order is rejected for technical reasons, and its
+        // value represents the latency that the local experiences when receiving the rejection
+        // notification.
+        if order_entry_latency < 0 {
+            // Rejects the order.
+            let mut order_ = req_order;
+            order_.req = Status::Rejected;
+            let rej_recv_timestamp = current_timestamp - order_entry_latency;
+            self.orders_from.append(order_, rej_recv_timestamp);
+        } else {
+            let exch_recv_timestamp = current_timestamp + order_entry_latency;
+            self.orders_to.append(req_order, exch_recv_timestamp);
+        }
+        Ok(())
+    }
+
+    fn modify(
+        &mut self,
+        order_id: OrderId,
+        price: f64,
+        qty: f64,
+        current_timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        let order = self
+            .orders
+            .get_mut(&order_id)
+            .ok_or(BacktestError::OrderNotFound)?;
+
         if order.req != Status::None {
             return Err(BacktestError::OrderRequestInProcess);
         }
 
-        order.req = Status::Canceled;
-        let order_entry_latency = self.order_latency.entry(current_timestamp, order);
+        // The local order keeps its current price and quantity until the exchange accepts the
+        // modification; only the request sent to the exchange carries the new values.
+        let mut order_ = order.clone();
+        order_.price_tick = (price / order.tick_size).round() as i64;
+        order_.qty = qty;
+        order_.req = Status::Replaced;
+        order.req = Status::Replaced;
+
+        let rule_check = self
+            .instrument_rules
+            .check(order.order_type, price, qty, &self.depth);
+        if let Err(reason) = rule_check {
+            order_.req = Status::Rejected;
+            order_.reject_reason = reason;
+            self.orders_from.append(order_, current_timestamp);
+            return Ok(());
+        }
+
+        let order_entry_latency = self.entry_latency(current_timestamp, &order_);
         // Negative latency indicates that the order is rejected for technical reasons, and its
         // value represents the latency that the local experiences when receiving the rejection
         // notification.
         if order_entry_latency < 0 {
             // Rejects the order.
-            let mut order_ = order.clone();
             order_.req = Status::Rejected;
             let rej_recv_timestamp = current_timestamp - order_entry_latency;
             self.orders_from.append(order_, rej_recv_timestamp);
         } else {
             let exch_recv_timestamp = current_timestamp + order_entry_latency;
-            self.orders_to.append(order.clone(), exch_recv_timestamp);
+            self.orders_to.append(order_, exch_recv_timestamp);
         }
         Ok(())
     }
@@ -205,6 +418,7 @@ where
             order.status != Status::Expired
                 && order.status != Status::Filled
                 && order.status != Status::Canceled
+                && order.status != Status::Liquidated
         })
     }
 
@@ -245,7 +459,7 @@ impl<AT, LM, MD, FM> Processor for Local<AT, LM, MD, FM>
 where
     AT: AssetType,
     LM: LatencyModel,
-    MD: MarketDepth + L2MarketDepth,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot,
     FM: FeeModel,
 {
     fn initialize_data(&mut self) -> Result<i64, BacktestError> {
@@ -262,6 +476,7 @@ where
 
     fn process_data(&mut self) -> Result<(i64, i64), BacktestError> {
         let ev = &self.data[self.row_num];
+        let mut depth_updated = true;
         // Processes a depth event
         if ev.is(LOCAL_BID_DEPTH_CLEAR_EVENT) {
             self.depth.clear_depth(Side::Buy, ev.px);
@@ -275,12 +490,28 @@ where
             self.depth.update_ask_depth(ev.px, ev.qty, ev.local_ts);
         }
         // Processes a trade event
-        else if ev.is(LOCAL_TRADE_EVENT) && self.trades.capacity() > 0 {
-            self.trades.push(ev.clone());
+        else {
+            depth_updated = false;
+            if ev.is(LOCAL_TRADE_EVENT) && self.trades.capacity() > 0 {
+                self.trades.push(ev.clone());
+            }
+            // Processes a funding event
+            else if ev.is(LOCAL_FUNDING_EVENT) {
+                self.state.apply_funding(ev.px, ev.fval);
+            }
+        }
+
+        if let Some(observer) = &mut self.observer {
+            if depth_updated {
+                observer.on_depth_change(&self.depth, ev);
+            } else if ev.is(LOCAL_TRADE_EVENT) {
+                observer.on_trade(ev);
+            }
         }
 
         // Stores the current feed latency
         self.last_feed_latency = Some((ev.exch_ts, ev.local_ts));
+        self.order_latency.on_feed(ev.local_ts, ev);
 
         // Checks
         let mut next_ts = 0;
@@ -316,6 +547,24 @@ where
             if timestamp == recv_timestamp {
                 let (order, _) = self.orders_from.pop_front().unwrap();
 
+                // Only the responses that the exchange sent can be dropped or held.
+                if let Some(fault_injector) = &mut self.fault_injector {
+                    if order.exch_timestamp > 0 {
+                        match fault_injector.response(recv_timestamp, &order) {
+                            None => {
+                                // The position and the balance still follow the exchange.
+                                self.apply_fill(&order, recv_timestamp);
+                                continue;
+                            }
+                            Some(deliver_timestamp) if deliver_timestamp > recv_timestamp => {
+                                self.orders_from.append(order, deliver_timestamp);
+                                continue;
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                }
+
                 // Updates the order latency only if it has a valid exchange timestamp. When the
                 // order is rejected before it reaches the matching engine, it has no exchange
                 // timestamp. This situation occurs in crypto exchanges.
@@ -330,20 +579,166 @@ where
                     }
                 }
 
-                self.process_recv_order_(order)?;
+                self.process_recv_order_(order, recv_timestamp)?;
             } else {
                 assert!(recv_timestamp > timestamp);
                 break;
             }
         }
+
+        // Refreshes the status of the orders whose responses were dropped.
+        while let Some(order) = self
+            .fault_injector
+            .as_mut()
+            .and_then(|fault_injector| fault_injector.pop_refresh(timestamp))
+        {
+            if wait_resp_order_id == Some(order.order_id) {
+                wait_resp_order_received = true;
+            }
+            self.update_order(order, timestamp)?;
+        }
         Ok(wait_resp_order_received)
     }
 
     fn earliest_recv_order_timestamp(&self) -> i64 {
-        self.orders_from.earliest_timestamp().unwrap_or(i64::MAX)
+        let refresh_timestamp = self
+            .fault_injector
+            .as_ref()
+            .and_then(|fault_injector| fault_injector.earliest_refresh_timestamp());
+        self.orders_from
+            .earliest_timestamp()
+            .unwrap_or(i64::MAX)
+            .min(refresh_timestamp.unwrap_or(i64::MAX))
     }
 
     fn earliest_send_order_timestamp(&self) -> i64 {
         self.orders_to.earliest_timestamp().unwrap_or(i64::MAX)
     }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let mut orders: Vec<_> = self.orders.values().cloned().collect();
+        orders.sort_by_key(|order| order.order_id);
+        checkpoint::encode(&LocalCheckpoint {
+            data_num: self.reader.data_num(),
+            row_num: self.row_num,
+            depth: DepthCheckpoint::l2(&self.depth),
+            state_values: self.state.state_values.clone(),
+            orders,
+            orders_to: self.orders_to.to_vec(),
+            orders_from: self.orders_from.to_vec(),
+            trades: self.trades.clone(),
+            last_feed_latency: self.last_feed_latency,
+            last_order_latency: self.last_order_latency,
+            order_latency: self.order_latency.checkpoint()?,
+            fault_injector: match &self.fault_injector {
+                Some(fault_injector) => fault_injector.checkpoint()?,
+                None => Vec::new(),
+            },
+        })
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let state: LocalCheckpoint = checkpoint::decode(checkpoint)?;
+        checkpoint::restore_data(&mut self.reader, &mut self.data, state.data_num)?;
+        self.row_num = state.row_num;
+        state.depth.restore_l2(&mut self.depth);
+        self.state.state_values = state.state_values;
+        self.orders = state
+            .orders
+            .into_iter()
+            .map(|order| (order.order_id, order))
+            .collect();
+        self.orders_to.reset();
+        for (order, timestamp) in state.orders_to {
+            self.orders_to.append(order, timestamp);
+        }
+        self.orders_from.reset();
+        for (order, timestamp) in state.orders_from {
+            self.orders_from.append(order, timestamp);
+        }
+        self.trades.clear();
+        self.trades.extend(state.trades);
+        self.last_feed_latency = state.last_feed_latency;
+        self.last_order_latency = state.last_order_latency;
+        self.order_latency.restore(&state.order_latency)?;
+        if let Some(fault_injector) = &mut self.fault_injector {
+            fault_injector.restore(&state.fault_injector)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            assettype::LinearAsset,
+            fixture::{event, to_data},
+            models::{
+                CommonFees,
+                ConstantLatency,
+                RequestTypeLatency,
+                RiskAdverseQueueModel,
+                TradingValueFeeModel,
+            },
+            Backtest,
+            DataSource,
+            L2AssetBuilder,
+        },
+        depth::HashMapMarketDepth,
+        prelude::{Bot, OrdType, RejectReason, Status, TimeInForce},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn cancel_overtaking_new_order() {
+        let data = to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ]);
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                L2AssetBuilder::new()
+                    .data(vec![DataSource::Data(data)])
+                    .latency_model(
+                        RequestTypeLatency::new(ConstantLatency::new(100, 10))
+                            .with_cancel(ConstantLatency::new(10, 10)),
+                    )
+                    .asset_type(LinearAsset::new(1.0))
+                    .fee_model(TradingValueFeeModel::new(CommonFees::new(-0.0001, 0.0005)))
+                    .queue_model(RiskAdverseQueueModel::new())
+                    .depth(|| HashMapMarketDepth::new(1.0, 1.0))
+                    .out_of_order_requests(true)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        hbt.elapse(1_000).unwrap();
+        let timestamp = hbt.current_timestamp();
+        hbt.submit_buy_order(0, 1, 98.0, 1.0, TimeInForce::GTC, OrdType::Limit, false)
+            .unwrap();
+
+        // The cancel reaches the exchange before the new order and is rejected, which the
+        // strategy sees, while the local order keeps waiting for the response to the new order.
+        hbt.cancel(0, 1, true).unwrap();
+        assert_eq!(hbt.current_timestamp(), timestamp + 20);
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::None);
+        assert_eq!(order.req, Status::New);
+        assert_eq!(order.reject_reason, RejectReason::UnknownOrder);
+
+        // The new order still goes live once it arrives.
+        hbt.wait_order_response(0, 1, 1_000).unwrap();
+        assert_eq!(hbt.current_timestamp(), timestamp + 110);
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::New);
+        assert_eq!(order.req, Status::None);
+        assert_eq!(order.exch_timestamp, timestamp + 100);
+
+        hbt.cancel(0, 1, true).unwrap();
+        assert_eq!(hbt.orders(0)[&1].status, Status::Canceled);
+    }
 }
@@ -1,6 +1,10 @@
+mod conditional;
+mod expiry;
 mod local;
 mod nopartialfillexchange;
 mod partialfillexchange;
+mod reduce_only;
+mod self_trade;
 
 use std::collections::HashMap;
 
@@ -11,14 +15,25 @@ pub use partialfillexchange::PartialFillExchange;
 mod l3_local;
 
 mod l3_nopartialfillexchange;
+mod l3_partialfillexchange;
 
 pub use l3_local::L3Local;
 pub use l3_nopartialfillexchange::L3NoPartialFillExchange;
+pub use l3_partialfillexchange::L3PartialFillExchange;
 
 use crate::{
     backtest::BacktestError,
     depth::MarketDepth,
-    prelude::{Event, OrdType, Order, OrderId, Side, StateValues, TimeInForce},
+    prelude::{
+        Event,
+        OrdType,
+        Order,
+        OrderId,
+        OrderRequest,
+        Side,
+        StateValues,
+        TimeInForce,
+    },
 };
 
 /// Provides local-specific interaction.
@@ -26,7 +41,8 @@ pub trait LocalProcessor<MD>: Processor
 where
     MD: MarketDepth,
 {
-    /// Submits a new order.
+    /// Submits a new order. The conditional order types and [`TimeInForce::GTD`] should be
+    /// submitted by [`submit_order_request`](LocalProcessor::submit_order_request) instead.
     ///
     /// * `order_id` - The unique order ID; there should not be any existing order with the same ID
     ///                on both local and exchange sides.
@@ -49,12 +65,39 @@ where
         current_timestamp: i64,
     ) -> Result<(), BacktestError>;
 
+    /// Submits a new order with all the attributes given in the [`OrderRequest`], such as the
+    /// trigger condition of a conditional order or the expire timestamp of a
+    /// [`TimeInForce::GTD`] order.
+    ///
+    /// * `request` - The order request; there should not be any existing order with the same ID
+    ///   on both local and exchange sides.
+    /// * `current_timestamp` - The current backtesting timestamp.
+    fn submit_order_request(
+        &mut self,
+        request: &OrderRequest,
+        current_timestamp: i64,
+    ) -> Result<(), BacktestError>;
+
     /// Cancels the specified order.
     ///
     /// * `order_id` - Order ID to cancel.
     /// * `current_timestamp` - The current backtesting timestamp.
     fn cancel(&mut self, order_id: OrderId, current_timestamp: i64) -> Result<(), BacktestError>;
 
+    /// Modifies the price and quantity of the specified order.
+    ///
+    /// * `order_id` - Order ID to modify.
+    /// * `price` - New order price.
+    /// * `qty` - New order quantity.
+    /// * `current_timestamp` - The current backtesting timestamp.
+    fn modify(
+        &mut self,
+        order_id: OrderId,
+        price: f64,
+        qty: f64,
+        current_timestamp: i64,
+    ) -> Result<(), BacktestError>;
+
     /// Clears inactive orders from the local orders whose status is neither
     /// [`Status::New`](crate::types::Status::New) nor
     /// [`Status::PartiallyFilled`](crate::types::Status::PartiallyFilled).
@@ -87,7 +130,7 @@ where
 }
 
 /// Processes the historical feed data and the order interaction.
-pub trait Processor {
+pub trait Processor: Send {
     /// Prepares to process the data. This is invoked when the backtesting is initiated.
     /// If successful, returns the timestamp of the first event.
     fn initialize_data(&mut self) -> Result<i64, BacktestError>;
@@ -112,4 +155,13 @@ pub trait Processor {
     /// Returns the foremost timestamp at which an order sent by this processor is to be received by
     /// the corresponding processor.
     fn earliest_send_order_timestamp(&self) -> i64;
+
+    /// Returns the state of this processor, which is needed to resume the backtest from the current
+    /// point, as bytes.
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError>;
+
+    /// Restores the state saved by [`checkpoint`](Processor::checkpoint). This processor should be
+    /// constructed in the same way as the one that made the checkpoint, with the same data and
+    /// models, and should not have processed any data yet.
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError>;
 }
@@ -1,30 +1,40 @@
 use std::{
-    cell::RefCell,
     cmp::Ordering,
     collections::{HashMap, HashSet},
     mem,
-    rc::Rc,
+    sync::{Arc, Mutex},
 };
 
 use crate::{
     backtest::{
         assettype::AssetType,
+        checkpoint::{self, DepthCheckpoint, ExchCheckpoint},
         data::{Data, Reader},
-        models::{FeeModel, LatencyModel, QueueModel},
+        models::{FeeModel, LatencyModel, MarginModel, NoMarginModel, QueueModel, RateLimitModel},
         order::OrderBus,
-        proc::Processor,
+        proc::{
+            conditional::ConditionalOrders,
+            expiry::OrderExpiry,
+            reduce_only::ReduceOnlyOrders,
+            self_trade::{self, SelfTradeAction},
+            Processor,
+        },
         state::State,
         BacktestError,
+        InstrumentRules,
+        SelfTradePrevention,
     },
-    depth::{L2MarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
+    depth::{ApplySnapshot, L2MarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
     prelude::OrdType,
     types::{
         Event,
         Order,
         OrderId,
+        RejectReason,
         Side,
         Status,
         TimeInForce,
+        TriggerSource,
         EXCH_ASK_DEPTH_CLEAR_EVENT,
         EXCH_ASK_DEPTH_EVENT,
         EXCH_ASK_DEPTH_SNAPSHOT_EVENT,
@@ -34,6 +44,8 @@ use crate::{
         EXCH_BUY_TRADE_EVENT,
         EXCH_DEPTH_CLEAR_EVENT,
         EXCH_EVENT,
+        EXCH_FUNDING_EVENT,
+        EXCH_MARK_PRICE_EVENT,
         EXCH_SELL_TRADE_EVENT,
     },
 };
@@ -41,7 +53,7 @@ use crate::{
 /// The exchange model without partial fills.
 ///
 /// Support order types: [OrdType::Limit](crate::types::OrdType::Limit)
-/// Support time-in-force: [`TimeInForce::GTC`], [`TimeInForce::GTX`]
+/// Support time-in-force: [`TimeInForce::GTC`], [`TimeInForce::GTX`], [`TimeInForce::GTD`]
 ///
 /// **Conditions for Full Execution**
 ///
@@ -63,20 +75,21 @@ use crate::{
 /// best. Be aware that this may cause unrealistic fill simulations if you attempt to execute a
 /// large quantity.
 ///
-pub struct NoPartialFillExchange<AT, LM, QM, MD, FM>
+pub struct NoPartialFillExchange<AT, LM, QM, MD, FM, MM = NoMarginModel>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: QueueModel<MD>,
     MD: MarketDepth,
     FM: FeeModel,
+    MM: MarginModel,
 {
     reader: Reader<Event>,
     data: Data<Event>,
     row_num: usize,
 
     // key: order_id, value: Order<Q>
-    orders: Rc<RefCell<HashMap<OrderId, Order>>>,
+    orders: Arc<Mutex<HashMap<OrderId, Order>>>,
     // key: order's price tick, value: order_ids
     buy_orders: HashMap<i64, HashSet<OrderId>>,
     sell_orders: HashMap<i64, HashSet<OrderId>>,
@@ -85,26 +98,33 @@ where
     orders_from: OrderBus,
 
     depth: MD,
-    state: State<AT, FM>,
+    state: State<AT, FM, MM>,
     order_latency: LM,
     queue_model: QM,
+    rate_limit: Option<Box<dyn RateLimitModel>>,
+    conditional_orders: ConditionalOrders,
+    order_expiry: OrderExpiry,
+    reduce_only_orders: ReduceOnlyOrders,
+    instrument_rules: InstrumentRules,
+    self_trade_prevention: SelfTradePrevention,
 
     filled_orders: Vec<OrderId>,
 }
 
-impl<AT, LM, QM, MD, FM> NoPartialFillExchange<AT, LM, QM, MD, FM>
+impl<AT, LM, QM, MD, FM, MM> NoPartialFillExchange<AT, LM, QM, MD, FM, MM>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: QueueModel<MD>,
     MD: MarketDepth,
     FM: FeeModel,
+    MM: MarginModel,
 {
     /// Constructs an instance of `NoPartialFillExchange`.
     pub fn new(
         reader: Reader<Event>,
         depth: MD,
-        state: State<AT, FM>,
+        state: State<AT, FM, MM>,
         order_latency: LM,
         queue_model: QM,
         orders_to: OrderBus,
@@ -123,15 +143,60 @@ where
             state,
             order_latency,
             queue_model,
+            rate_limit: None,
+            conditional_orders: Default::default(),
+            order_expiry: Default::default(),
+            reduce_only_orders: Default::default(),
+            instrument_rules: Default::default(),
+            self_trade_prevention: SelfTradePrevention::None,
             filled_orders: Default::default(),
         }
     }
 
+    /// Sets a [`RateLimitModel`] that rejects the requests exceeding the limits.
+    pub fn with_rate_limit(self, rate_limit: Box<dyn RateLimitModel>) -> Self {
+        Self {
+            rate_limit: Some(rate_limit),
+            ..self
+        }
+    }
+
+    /// Sets the [`InstrumentRules`] that the new and modified orders are checked against. By
+    /// default, no rules are applied.
+    pub fn with_instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
+        }
+    }
+
+    /// Sets the self-trade prevention mode. The default value is [`SelfTradePrevention::None`].
+    pub fn with_self_trade_prevention(self, self_trade_prevention: SelfTradePrevention) -> Self {
+        Self {
+            self_trade_prevention,
+            ..self
+        }
+    }
+
     fn process_recv_order_(
         &mut self,
         mut order: Order,
         recv_timestamp: i64,
     ) -> Result<(), BacktestError> {
+        if let Some(rate_limit) = &mut self.rate_limit {
+            let num_open_orders = self.orders.lock().unwrap().len();
+            if let Err(reason) = rate_limit.check(&order, recv_timestamp, num_open_orders) {
+                // The request is rejected before it reaches the matching engine, so it has no
+                // exchange timestamp.
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                let local_recv_timestamp =
+                    recv_timestamp + self.order_latency.response(recv_timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
         // Processes a new order.
         if order.req == Status::New {
             order.req = Status::None;
@@ -141,6 +206,11 @@ where
         else if order.req == Status::Canceled {
             order.req = Status::None;
             self.ack_cancel(order, recv_timestamp)?;
+        }
+        // Processes a modify order.
+        else if order.req == Status::Replaced {
+            order.req = Status::None;
+            self.ack_modify(order, recv_timestamp)?;
         } else {
             return Err(BacktestError::InvalidOrderRequest);
         }
@@ -164,6 +234,12 @@ where
                 // Updates the order's queue position.
                 self.queue_model.trade(order, qty, &self.depth);
                 if self.queue_model.is_filled(order, &self.depth) > 0.0 {
+                    if order.slice_qty(self.depth.lot_size()) < order.leaves_qty {
+                        // The iceberg order displays its next slice at the back of the queue.
+                        self.fill_slice(order, timestamp)?;
+                        self.queue_model.refresh(order, &self.depth);
+                        return Ok(());
+                    }
                     self.filled_orders.push(order.order_id);
                     return self.fill(order, timestamp, true, order.price_tick);
                 }
@@ -189,6 +265,12 @@ where
                 // Updates the order's queue position.
                 self.queue_model.trade(order, qty, &self.depth);
                 if self.queue_model.is_filled(order, &self.depth) > 0.0 {
+                    if order.slice_qty(self.depth.lot_size()) < order.leaves_qty {
+                        // The iceberg order displays its next slice at the back of the queue.
+                        self.fill_slice(order, timestamp)?;
+                        self.queue_model.refresh(order, &self.depth);
+                        return Ok(());
+                    }
                     self.filled_orders.push(order.order_id);
                     return self.fill(order, timestamp, true, order.price_tick);
                 }
@@ -230,9 +312,31 @@ where
         Ok(())
     }
 
+    fn fill_slice(&mut self, order: &mut Order, timestamp: i64) -> Result<(), BacktestError> {
+        if order.status == Status::Expired
+            || order.status == Status::Canceled
+            || order.status == Status::Filled
+        {
+            return Err(BacktestError::InvalidOrderStatus);
+        }
+
+        order.maker = true;
+        order.exec_price_tick = order.price_tick;
+        order.exec_qty = order.slice_qty(self.depth.lot_size());
+        order.leaves_qty -= order.exec_qty;
+        order.status = Status::PartiallyFilled;
+        order.exch_timestamp = timestamp;
+        let local_recv_timestamp =
+            order.exch_timestamp + self.order_latency.response(timestamp, order);
+
+        self.state.apply_fill(order);
+        self.orders_to.append(order.clone(), local_recv_timestamp);
+        Ok(())
+    }
+
     fn remove_filled_orders(&mut self) {
         if !self.filled_orders.is_empty() {
-            let mut orders = self.orders.borrow_mut();
+            let mut orders = self.orders.lock().unwrap();
             for order_id in self.filled_orders.drain(..) {
                 let order = orders.remove(&order_id).unwrap();
                 if order.side == Side::Buy {
@@ -254,10 +358,15 @@ where
         let orders = self.orders.clone();
         if let Some(order_ids) = self.buy_orders.get(&price_tick) {
             for order_id in order_ids.iter() {
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 let order = orders_borrowed.get_mut(order_id).unwrap();
-                self.queue_model
-                    .depth(order, prev_qty, new_qty, &self.depth);
+                if order.is_hidden() {
+                    // The hidden order yields its priority to the displayed quantity.
+                    self.queue_model.refresh(order, &self.depth);
+                } else {
+                    self.queue_model
+                        .depth(order, prev_qty, new_qty, &self.depth);
+                }
             }
         }
     }
@@ -266,10 +375,15 @@ where
         let orders = self.orders.clone();
         if let Some(order_ids) = self.sell_orders.get(&price_tick) {
             for order_id in order_ids.iter() {
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 let order = orders_borrowed.get_mut(order_id).unwrap();
-                self.queue_model
-                    .depth(order, prev_qty, new_qty, &self.depth);
+                if order.is_hidden() {
+                    // The hidden order yields its priority to the displayed quantity.
+                    self.queue_model.refresh(order, &self.depth);
+                } else {
+                    self.queue_model
+                        .depth(order, prev_qty, new_qty, &self.depth);
+                }
             }
         }
     }
@@ -284,7 +398,7 @@ where
         // better to iterate orders dict instead of order price ladder.
         {
             let orders = self.orders.clone();
-            let mut orders_borrowed = orders.borrow_mut();
+            let mut orders_borrowed = orders.lock().unwrap();
             if prev_best_tick == INVALID_MIN
                 || (orders_borrowed.len() as i64) < new_best_tick - prev_best_tick
             {
@@ -320,7 +434,7 @@ where
         // better to iterate orders dict instead of order price ladder.
         {
             let orders = self.orders.clone();
-            let mut orders_borrowed = orders.borrow_mut();
+            let mut orders_borrowed = orders.lock().unwrap();
             if prev_best_tick == INVALID_MAX
                 || (orders_borrowed.len() as i64) < prev_best_tick - new_best_tick
             {
@@ -346,10 +460,276 @@ where
         Ok(())
     }
 
+    fn check_liquidation(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let fills = self.state.liquidation_fills(&self.depth);
+        if fills.is_empty() {
+            return Ok(());
+        }
+        // The open orders are canceled first so that they cannot reopen the position.
+        self.cancel_all_orders(timestamp)?;
+        for mut order in fills {
+            self.state.apply_fill(&order);
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+        Ok(())
+    }
+
+    /// Cancels all open orders, including the untriggered conditional orders, in the order of their
+    /// order IDs.
+    fn cancel_all_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let mut open_orders: Vec<_> = self
+            .orders
+            .lock()
+            .unwrap()
+            .values()
+            .chain(self.conditional_orders.orders())
+            .cloned()
+            .collect();
+        open_orders.sort_by_key(|order| order.order_id);
+        for order in open_orders {
+            self.ack_cancel(order, timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the self-trade prevention to the order before it is matched. Returns `false` if the
+    /// order itself is expired, in which case its response has already been sent.
+    fn prevent_self_trade(
+        &mut self,
+        order: &mut Order,
+        timestamp: i64,
+    ) -> Result<bool, BacktestError> {
+        let (actions, expired) = self_trade::prevent_self_trade(
+            self.self_trade_prevention,
+            order,
+            self.orders.lock().unwrap().values(),
+            self.depth.lot_size(),
+        );
+        for action in actions {
+            match action {
+                SelfTradeAction::Cancel(order_id) => {
+                    let resting = self.orders.lock().unwrap()[&order_id].clone();
+                    self.ack_cancel(resting, timestamp)?;
+                }
+                SelfTradeAction::Decrement(order_id, qty) => {
+                    let mut orders = self.orders.lock().unwrap();
+                    let resting = orders.get_mut(&order_id).unwrap();
+                    resting.qty -= qty;
+                    resting.leaves_qty -= qty;
+                    resting.exch_timestamp = timestamp;
+                    let local_recv_timestamp =
+                        timestamp + self.order_latency.response(timestamp, resting);
+                    self.orders_to
+                        .append(resting.clone(), local_recv_timestamp);
+                }
+            }
+        }
+        if expired {
+            order.status = Status::Expired;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, order);
+            self.orders_to.append(order.clone(), local_recv_timestamp);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn trigger_conditional_orders(
+        &mut self,
+        source: TriggerSource,
+        price_tick: i64,
+        timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        for mut order in self.conditional_orders.update(source, price_tick) {
+            if !self.state.has_margin_for(&order, &self.depth) {
+                // The order has already been accepted, so it expires instead of being rejected.
+                order.status = Status::Expired;
+                order.reject_reason = RejectReason::InsufficientMargin;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+            } else {
+                self.ack_new(order, timestamp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expire_orders(&mut self, timestamp: i64) {
+        for order_id in self.order_expiry.pop_expired(timestamp) {
+            let exch_order = if self
+                .conditional_orders
+                .get(order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                self.conditional_orders.cancel(order_id)
+            } else if self
+                .orders
+                .lock()
+                .unwrap()
+                .get(&order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                let order = self.orders.lock().unwrap().remove(&order_id).unwrap();
+                if order.side == Side::Buy {
+                    self.buy_orders
+                        .get_mut(&order.price_tick)
+                        .unwrap()
+                        .remove(&order_id);
+                } else {
+                    self.sell_orders
+                        .get_mut(&order.price_tick)
+                        .unwrap()
+                        .remove(&order_id);
+                }
+                Some(order)
+            } else {
+                // The order has already been filled or canceled.
+                None
+            };
+            if let Some(mut exch_order) = exch_order {
+                exch_order.status = Status::Expired;
+                exch_order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &exch_order);
+                self.orders_to.append(exch_order, local_recv_timestamp);
+            }
+        }
+    }
+
+    /// Cancels the resting reduce-only orders that would increase the position, which the fills
+    /// have changed.
+    fn expire_reduce_only_orders(&mut self, timestamp: i64) {
+        let lot_size = self.depth.lot_size();
+        for order_id in self
+            .reduce_only_orders
+            .pop_position_increasing(self.state.values(), lot_size)
+        {
+            if !self
+                .orders
+                .lock()
+                .unwrap()
+                .get(&order_id)
+                .is_some_and(ReduceOnlyOrders::is_reduce_only)
+            {
+                // The order has already been filled or canceled.
+                continue;
+            }
+            let mut order = self.orders.lock().unwrap().remove(&order_id).unwrap();
+            if order.side == Side::Buy {
+                self.buy_orders
+                    .get_mut(&order.price_tick)
+                    .unwrap()
+                    .remove(&order_id);
+            } else {
+                self.sell_orders
+                    .get_mut(&order.price_tick)
+                    .unwrap()
+                    .remove(&order_id);
+            }
+            order.status = Status::Expired;
+            order.reject_reason = RejectReason::ReduceOnly;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+    }
+
     fn ack_new(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
-        if self.orders.borrow().contains_key(&order.order_id) {
+        if self.orders.lock().unwrap().contains_key(&order.order_id) {
             return Err(BacktestError::OrderIdExist);
         }
+        if self.conditional_orders.contains(order.order_id) {
+            return Err(BacktestError::OrderIdExist);
+        }
+
+        // The triggered order has already been checked against the rules when it was placed.
+        if !order.triggered {
+            let rule_check = self.instrument_rules.check(
+                order.order_type,
+                order.price(),
+                order.qty,
+                &self.depth,
+            );
+            if let Err(reason) = rule_check {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
+        // A good-till-date order whose expire timestamp has already passed is not accepted.
+        if OrderExpiry::has_expired(&order, timestamp) {
+            order.status = Status::Expired;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+        self.order_expiry.schedule(&order);
+
+        // The conditional order is held without being matched until it is triggered.
+        if order.order_type.is_conditional() {
+            order.exch_timestamp = timestamp;
+            if let Err(reason) = self.conditional_orders.add(&mut order) {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+            }
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if ReduceOnlyOrders::is_reduce_only(&order) {
+            // The reduce-only order is clipped to the position, and is not accepted if it would
+            // only increase the position.
+            let reducible_qty = ReduceOnlyOrders::reducible_qty(
+                order.side,
+                order.position_side,
+                self.state.values(),
+            );
+            if (reducible_qty / self.depth.lot_size()).round() <= 0.0 {
+                if order.triggered {
+                    // The triggered order has already been accepted, so it expires instead.
+                    order.status = Status::Expired;
+                } else {
+                    order.req = Status::Rejected;
+                }
+                order.reject_reason = RejectReason::ReduceOnly;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+            if order.qty > reducible_qty {
+                order.qty = reducible_qty;
+                order.leaves_qty = reducible_qty;
+            }
+            self.reduce_only_orders.add(&order);
+        }
+
+        if !self.state.has_margin_for(&order, &self.depth) {
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::InsufficientMargin;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if order.time_in_force != TimeInForce::GTX
+            && !self.prevent_self_trade(&mut order, timestamp)?
+        {
+            return Ok(());
+        }
 
         if order.side == Side::Buy {
             match order.order_type {
@@ -366,7 +746,10 @@ where
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
                                 Ok(())
                             }
-                            TimeInForce::GTC | TimeInForce::FOK | TimeInForce::IOC => {
+                            TimeInForce::GTC
+                            | TimeInForce::GTD
+                            | TimeInForce::FOK
+                            | TimeInForce::IOC => {
                                 // Since this always fills the full quantity, both FOK and IOC
                                 // orders are also fully filled at the best price.
                                 // Takes the market.
@@ -376,7 +759,7 @@ where
                         }
                     } else {
                         match order.time_in_force {
-                            TimeInForce::GTC | TimeInForce::GTX => {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
                                 // Initializes the order's queue position.
                                 self.queue_model.new_order(&mut order, &self.depth);
                                 order.status = Status::New;
@@ -390,7 +773,7 @@ where
                                 let local_recv_timestamp =
                                     timestamp + self.order_latency.response(timestamp, &order);
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
-                                self.orders.borrow_mut().insert(order.order_id, order);
+                                self.orders.lock().unwrap().insert(order.order_id, order);
                                 Ok(())
                             }
                             TimeInForce::FOK | TimeInForce::IOC => {
@@ -410,7 +793,7 @@ where
                     // Takes the market.
                     self.fill(&mut order, timestamp, false, self.depth.best_ask_tick())
                 }
-                OrdType::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                _ => Err(BacktestError::InvalidOrderRequest),
             }
         } else {
             match order.order_type {
@@ -427,7 +810,10 @@ where
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
                                 Ok(())
                             }
-                            TimeInForce::GTC | TimeInForce::FOK | TimeInForce::IOC => {
+                            TimeInForce::GTC
+                            | TimeInForce::GTD
+                            | TimeInForce::FOK
+                            | TimeInForce::IOC => {
                                 // Since this always fills the full quantity, both FOK and IOC
                                 // orders are also fully filled at the best price.
                                 // Takes the market.
@@ -437,7 +823,7 @@ where
                         }
                     } else {
                         match order.time_in_force {
-                            TimeInForce::GTC | TimeInForce::GTX => {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
                                 // Initializes the order's queue position.
                                 self.queue_model.new_order(&mut order, &self.depth);
                                 order.status = Status::New;
@@ -451,7 +837,7 @@ where
                                 let local_recv_timestamp =
                                     timestamp + self.order_latency.response(timestamp, &order);
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
-                                self.orders.borrow_mut().insert(order.order_id, order);
+                                self.orders.lock().unwrap().insert(order.order_id, order);
                                 Ok(())
                             }
                             TimeInForce::FOK | TimeInForce::IOC => {
@@ -471,19 +857,29 @@ where
                     // Takes the market.
                     self.fill(&mut order, timestamp, false, self.depth.best_bid_tick())
                 }
-                OrdType::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                _ => Err(BacktestError::InvalidOrderRequest),
             }
         }
     }
 
     fn ack_cancel(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if let Some(mut exch_order) = self.conditional_orders.cancel(order.order_id) {
+            exch_order.status = Status::Canceled;
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
         let exch_order = {
-            let mut order_borrowed = self.orders.borrow_mut();
+            let mut order_borrowed = self.orders.lock().unwrap();
             order_borrowed.remove(&order.order_id)
         };
 
         if exch_order.is_none() {
             order.req = Status::Rejected;
+            order.reject_reason = RejectReason::UnknownOrder;
             order.exch_timestamp = timestamp;
             let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
             self.orders_to.append(order, local_recv_timestamp);
@@ -514,13 +910,37 @@ where
     }
 
     fn ack_modify(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        let rule_check = self.instrument_rules.check(
+            order.order_type,
+            order.price(),
+            order.qty,
+            &self.depth,
+        );
+        if let Err(reason) = rule_check {
+            order.req = Status::Rejected;
+            order.reject_reason = reason;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if let Some(mut exch_order) = self.conditional_orders.modify(&order) {
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
         let mut exch_order = {
-            let mut order_borrowed = self.orders.borrow_mut();
+            let mut order_borrowed = self.orders.lock().unwrap();
             let exch_order = order_borrowed.remove(&order.order_id);
 
             // The order can be already deleted due to fill or expiration.
             if exch_order.is_none() {
                 order.req = Status::Rejected;
+                order.reject_reason = RejectReason::UnknownOrder;
                 order.exch_timestamp = timestamp;
                 let local_recv_timestamp =
                     timestamp + self.order_latency.response(timestamp, &order);
@@ -532,13 +952,28 @@ where
         };
 
         let prev_price_tick = exch_order.price_tick;
+        let prev_leaves_qty = exch_order.leaves_qty;
         exch_order.price_tick = order.price_tick;
         // No partial fill occurs.
         exch_order.qty = order.qty;
-        // The initialization of the order queue position may not occur when the modified quantity
-        // is smaller than the previous quantity, depending on the exchanges. It may need to
-        // implement exchange-specific specialization.
-        let init_q_pos = true;
+        exch_order.leaves_qty = order.qty;
+
+        if exch_order.time_in_force != TimeInForce::GTX
+            && !self.prevent_self_trade(&mut exch_order, timestamp)?
+        {
+            if exch_order.side == Side::Buy {
+                self.buy_orders
+                    .get_mut(&prev_price_tick)
+                    .unwrap()
+                    .remove(&exch_order.order_id);
+            } else {
+                self.sell_orders
+                    .get_mut(&prev_price_tick)
+                    .unwrap()
+                    .remove(&exch_order.order_id);
+            }
+            return Ok(());
+        }
 
         if exch_order.side == Side::Buy {
             // Checks if the buy order price is greater than or equal to the current best ask.
@@ -578,19 +1013,21 @@ where
                         .or_default()
                         .insert(exch_order.order_id);
                 }
-                if init_q_pos || prev_price_tick != exch_order.price_tick {
-                    // Initializes the order's queue position.
-                    self.queue_model.new_order(&mut exch_order, &self.depth);
-                }
-                exch_order.status = Status::New;
-
+                // Updates the order's queue position. The order loses its priority if the price
+                // changes or the quantity increases.
+                self.queue_model.amend(
+                    &mut exch_order,
+                    prev_price_tick,
+                    prev_leaves_qty,
+                    &self.depth,
+                );
                 exch_order.exch_timestamp = timestamp;
                 let local_recv_timestamp =
                     timestamp + self.order_latency.response(timestamp, &exch_order);
                 self.orders_to
                     .append(exch_order.clone(), local_recv_timestamp);
 
-                let mut order_borrowed = self.orders.borrow_mut();
+                let mut order_borrowed = self.orders.lock().unwrap();
                 order_borrowed.insert(exch_order.order_id, exch_order);
 
                 Ok(())
@@ -633,19 +1070,21 @@ where
                         .or_default()
                         .insert(exch_order.order_id);
                 }
-                if init_q_pos || prev_price_tick != exch_order.price_tick {
-                    // Initialize the order's queue position.
-                    self.queue_model.new_order(&mut exch_order, &self.depth);
-                }
-                exch_order.status = Status::New;
-
+                // Updates the order's queue position. The order loses its priority if the price
+                // changes or the quantity increases.
+                self.queue_model.amend(
+                    &mut exch_order,
+                    prev_price_tick,
+                    prev_leaves_qty,
+                    &self.depth,
+                );
                 exch_order.exch_timestamp = timestamp;
                 let local_recv_timestamp =
                     timestamp + self.order_latency.response(timestamp, &exch_order);
                 self.orders_to
                     .append(exch_order.clone(), local_recv_timestamp);
 
-                let mut order_borrowed = self.orders.borrow_mut();
+                let mut order_borrowed = self.orders.lock().unwrap();
                 order_borrowed.insert(exch_order.order_id, exch_order);
 
                 Ok(())
@@ -654,13 +1093,14 @@ where
     }
 }
 
-impl<AT, LM, QM, MD, FM> Processor for NoPartialFillExchange<AT, LM, QM, MD, FM>
+impl<AT, LM, QM, MD, FM, MM> Processor for NoPartialFillExchange<AT, LM, QM, MD, FM, MM>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: QueueModel<MD>,
-    MD: MarketDepth + L2MarketDepth,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot,
     FM: FeeModel,
+    MM: MarginModel,
 {
     fn initialize_data(&mut self) -> Result<i64, BacktestError> {
         self.data = self.reader.next_data()?;
@@ -712,7 +1152,7 @@ where
             let qty = self.data[row_num].qty;
             {
                 let orders = self.orders.clone();
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 if self.depth.best_bid_tick() == INVALID_MIN
                     || (orders_borrowed.len() as i64) < price_tick - self.depth.best_bid_tick()
                 {
@@ -743,12 +1183,17 @@ where
                 }
             }
             self.remove_filled_orders();
+            self.trigger_conditional_orders(
+                TriggerSource::LastPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
         } else if self.data[row_num].is(EXCH_SELL_TRADE_EVENT) {
             let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
             let qty = self.data[row_num].qty;
             {
                 let orders = self.orders.clone();
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 if self.depth.best_ask_tick() == INVALID_MAX
                     || (orders_borrowed.len() as i64) < self.depth.best_ask_tick() - price_tick
                 {
@@ -779,7 +1224,32 @@ where
                 }
             }
             self.remove_filled_orders();
+            self.trigger_conditional_orders(
+                TriggerSource::LastPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        } else if self.data[row_num].is(EXCH_FUNDING_EVENT) {
+            self.state
+                .apply_funding(self.data[row_num].px, self.data[row_num].fval);
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        } else if self.data[row_num].is(EXCH_MARK_PRICE_EVENT) {
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
         }
+        self.check_liquidation(self.data[row_num].exch_ts)?;
+        self.expire_reduce_only_orders(self.data[row_num].exch_ts);
+        self.order_latency
+            .on_feed(self.data[row_num].exch_ts, &self.data[row_num]);
 
         // Checks
         let mut next_ts = 0;
@@ -807,25 +1277,307 @@ where
         timestamp: i64,
         _wait_resp_order_id: Option<OrderId>,
     ) -> Result<bool, BacktestError> {
+        self.expire_orders(timestamp);
+
         // Processes the order part.
         while !self.orders_from.is_empty() {
             let recv_timestamp = self.orders_from.earliest_timestamp().unwrap();
             if timestamp == recv_timestamp {
                 let (order, _) = self.orders_from.pop_front().unwrap();
+                self.order_latency.on_request(order.req);
                 self.process_recv_order_(order, recv_timestamp)?;
+                self.order_latency.on_request(Status::None);
             } else {
                 assert!(recv_timestamp > timestamp);
                 break;
             }
         }
+        self.expire_reduce_only_orders(timestamp);
         Ok(false)
     }
 
     fn earliest_recv_order_timestamp(&self) -> i64 {
-        self.orders_from.earliest_timestamp().unwrap_or(i64::MAX)
+        // Wakes up at the expire timestamp of the good-till-date order as well.
+        self.orders_from
+            .earliest_timestamp()
+            .unwrap_or(i64::MAX)
+            .min(self.order_expiry.earliest_timestamp().unwrap_or(i64::MAX))
     }
 
     fn earliest_send_order_timestamp(&self) -> i64 {
         self.orders_to.earliest_timestamp().unwrap_or(i64::MAX)
     }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let mut orders = Vec::new();
+        for order in self.orders.lock().unwrap().values() {
+            orders.push((order.clone(), self.queue_model.encode_q(order)?));
+        }
+        orders.sort_by_key(|(order, _)| order.order_id);
+        checkpoint::encode(&ExchCheckpoint {
+            data_num: self.reader.data_num(),
+            row_num: self.row_num,
+            depth: DepthCheckpoint::l2(&self.depth),
+            state_values: self.state.state_values.clone(),
+            orders,
+            queue: Vec::new(),
+            conditional_orders: checkpoint::encode(&self.conditional_orders)?,
+            order_expiry: checkpoint::encode(&self.order_expiry)?,
+            reduce_only_orders: checkpoint::encode(&self.reduce_only_orders)?,
+            rate_limit: match &self.rate_limit {
+                Some(rate_limit) => rate_limit.checkpoint()?,
+                None => Vec::new(),
+            },
+            consumed_bid: Vec::new(),
+            consumed_ask: Vec::new(),
+            order_latency: self.order_latency.checkpoint()?,
+        })
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let state: ExchCheckpoint = checkpoint::decode(checkpoint)?;
+        checkpoint::restore_data(&mut self.reader, &mut self.data, state.data_num)?;
+        self.row_num = state.row_num;
+        state.depth.restore_l2(&mut self.depth);
+        self.state.state_values = state.state_values;
+        self.conditional_orders = checkpoint::decode(&state.conditional_orders)?;
+        self.order_expiry = checkpoint::decode(&state.order_expiry)?;
+        self.reduce_only_orders = checkpoint::decode(&state.reduce_only_orders)?;
+        if let Some(rate_limit) = &mut self.rate_limit {
+            rate_limit.restore(&state.rate_limit)?;
+        }
+        self.order_latency.restore(&state.order_latency)?;
+
+        let mut orders = self.orders.lock().unwrap();
+        orders.clear();
+        self.buy_orders.clear();
+        self.sell_orders.clear();
+        for (mut order, q) in state.orders {
+            self.queue_model.decode_q(&mut order, &q)?;
+            if order.side == Side::Buy {
+                self.buy_orders
+                    .entry(order.price_tick)
+                    .or_default()
+                    .insert(order.order_id);
+            } else {
+                self.sell_orders
+                    .entry(order.price_tick)
+                    .or_default()
+                    .insert(order.order_id);
+            }
+            orders.insert(order.order_id, order);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{asset_builder, build_backtest, event, limit_order, to_data},
+            Backtest,
+            BacktestError,
+            InstrumentRules,
+            SelfTradePrevention,
+        },
+        prelude::{Bot, OrdType, OrderRequest, RejectReason, Side, Status, TimeInForce},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT, TRADE_EVENT},
+    };
+
+    #[test]
+    fn prevent_self_trade() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 10_000, 101.0, 4.0),
+        ])];
+
+        // (mode, (status, leaves_qty) of the resting sell order, status of the incoming buy order)
+        let cases = [
+            (SelfTradePrevention::CancelNewest, (Status::New, 3.0), Status::Expired),
+            (SelfTradePrevention::CancelOldest, (Status::Canceled, 3.0), Status::New),
+            (SelfTradePrevention::CancelBoth, (Status::Canceled, 3.0), Status::Expired),
+            (SelfTradePrevention::DecrementAndCancel, (Status::New, 2.0), Status::Expired),
+        ];
+        for (mode, (sell_status, sell_leaves_qty), buy_status) in cases {
+            let mut hbt = Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .self_trade_prevention(mode)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap();
+
+            hbt.elapse(2_000).unwrap();
+            hbt.submit_sell_order(0, 1, 100.0, 3.0, TimeInForce::GTC, OrdType::Limit, true)
+                .unwrap();
+            // The buy order doesn't cross the market depth but crosses the resting sell order.
+            hbt.submit_buy_order(0, 2, 100.0, 1.0, TimeInForce::GTC, OrdType::Limit, true)
+                .unwrap();
+            hbt.elapse(100).unwrap();
+
+            let orders = hbt.orders(0);
+            assert_eq!(orders[&1].status, sell_status, "{mode:?}");
+            assert_eq!(orders[&1].leaves_qty, sell_leaves_qty, "{mode:?}");
+            assert_eq!(orders[&2].status, buy_status, "{mode:?}");
+            assert_eq!(hbt.position(0), 0.0);
+        }
+    }
+
+    #[test]
+    fn iceberg_and_hidden_orders() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | TRADE_EVENT, 2_500, 99.0, 6.0),
+            event(SELL_EVENT | TRADE_EVENT, 3_500, 99.0, 2.0),
+            event(SELL_EVENT | TRADE_EVENT, 4_500, 99.0, 4.0),
+            event(BUY_EVENT | DEPTH_EVENT, 5_500, 98.0, 3.0),
+            event(SELL_EVENT | TRADE_EVENT, 6_500, 98.0, 2.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 98.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        let requests = [
+            (1, 99.0, 3.0, Some(1.0)),
+            (2, 98.0, 1.0, Some(0.0)),
+            // An iceberg order should display at least one lot.
+            (3, 98.0, 1.0, Some(0.4)),
+        ];
+        for (order_id, price, qty, display_qty) in requests {
+            let request = OrderRequest {
+                display_qty,
+                ..limit_order(order_id, Side::Buy, price, qty)
+            };
+            let result = hbt.submit_order(0, request, true);
+            if order_id == 3 {
+                assert!(matches!(result, Err(BacktestError::InvalidOrderRequest)));
+            } else {
+                result.unwrap();
+            }
+        }
+
+        // Only the displayed slice is executed.
+        hbt.elapse(1_000).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::PartiallyFilled);
+        assert_eq!(order.leaves_qty, 2.0);
+        assert_eq!(hbt.position(0), 1.0);
+
+        // The next slice is displayed at the back of the queue.
+        hbt.elapse(1_000).unwrap();
+        assert_eq!(hbt.orders(0)[&1].leaves_qty, 2.0);
+        hbt.elapse(1_000).unwrap();
+        assert_eq!(hbt.orders(0)[&1].leaves_qty, 1.0);
+        assert_eq!(hbt.position(0), 2.0);
+
+        // The hidden order stays behind the displayed quantity that arrives later.
+        hbt.elapse(2_000).unwrap();
+        assert_eq!(hbt.orders(0)[&1].status, Status::Filled);
+        assert_eq!(hbt.orders(0)[&2].status, Status::New);
+        assert_eq!(hbt.position(0), 3.0);
+    }
+
+    #[test]
+    fn reject_orders_breaking_instrument_rules() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+        let rules = InstrumentRules {
+            tick_size: 1.0,
+            lot_size: 1.0,
+            max_qty: 10.0,
+            min_notional: 150.0,
+            multiplier_up: 1.1,
+            multiplier_down: 0.9,
+            ..Default::default()
+        };
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                asset_builder(&data)
+                    .instrument_rules(rules)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        hbt.elapse(1_000).unwrap();
+        for (order_id, price, qty, reason) in [
+            (1, 98.5, 2.0, RejectReason::PriceFilter),
+            (2, 98.0, 2.5, RejectReason::LotSize),
+            (3, 98.0, 11.0, RejectReason::LotSize),
+            (4, 98.0, 1.0, RejectReason::MinNotional),
+            (5, 89.0, 2.0, RejectReason::PercentPrice),
+        ] {
+            hbt.submit_order(0, limit_order(order_id, Side::Buy, price, qty), true)
+                .unwrap();
+            let order = &hbt.orders(0)[&order_id];
+            assert_eq!(order.status, Status::Expired);
+            assert_eq!(order.reject_reason, reason);
+        }
+
+        hbt.submit_order(0, limit_order(6, Side::Buy, 98.0, 2.0), true)
+            .unwrap();
+        assert_eq!(hbt.orders(0)[&6].status, Status::New);
+
+        // The modification that breaks the rules is rejected, and the order remains unchanged.
+        hbt.modify(0, 6, 98.0, 1.0, true).unwrap();
+        let order = &hbt.orders(0)[&6];
+        assert_eq!(order.status, Status::New);
+        assert_eq!(order.qty, 2.0);
+        assert_eq!(order.reject_reason, RejectReason::MinNotional);
+    }
+
+    #[test]
+    fn modify_order() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 98.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | TRADE_EVENT, 3_000, 99.0, 4.0),
+            event(SELL_EVENT | TRADE_EVENT, 6_000, 99.0, 3.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 99.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_buy_order(0, 1, 99.0, 2.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        hbt.submit_buy_order(0, 2, 99.0, 2.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        // Both orders advance to the front of the queue, with one lot ahead of them.
+        hbt.elapse(1_000).unwrap();
+
+        // Decreasing the quantity keeps the queue position, while moving the price sends the order
+        // to the back of the queue, even if it moves back to the previous price.
+        hbt.modify(0, 1, 99.0, 1.0, true).unwrap();
+        hbt.modify(0, 2, 98.0, 2.0, true).unwrap();
+        hbt.modify(0, 2, 99.0, 2.0, true).unwrap();
+        hbt.elapse(4_000).unwrap();
+        let orders = hbt.orders(0);
+        assert_eq!(orders[&1].status, Status::Filled);
+        assert_eq!(orders[&2].status, Status::New);
+        assert_eq!(orders[&2].price_tick, 99);
+        assert_eq!(hbt.position(0), 1.0);
+
+        // The exchange no longer holds the filled order.
+        hbt.modify(0, 1, 99.0, 2.0, true).unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.qty, 1.0);
+        assert_eq!(order.reject_reason, RejectReason::UnknownOrder);
+
+        // The modified order that crosses the market depth takes the market.
+        hbt.modify(0, 2, 101.0, 2.0, true).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_price_tick, 101);
+        assert_eq!(hbt.position(0), 3.0);
+    }
 }
@@ -1,30 +1,42 @@
 use std::{
-    cell::RefCell,
     cmp::Ordering,
     collections::{HashMap, HashSet},
     mem,
-    rc::Rc,
+    sync::{Arc, Mutex},
 };
 
 use crate::{
     backtest::{
         assettype::AssetType,
+        checkpoint::{self, DepthCheckpoint, ExchCheckpoint},
         data::{Data, Reader},
-        models::{FeeModel, LatencyModel, QueueModel},
+        models::{FeeModel, LatencyModel, MarginModel, NoMarginModel, QueueModel, RateLimitModel},
         order::OrderBus,
-        proc::Processor,
+        proc::{
+            conditional::ConditionalOrders,
+            expiry::OrderExpiry,
+            reduce_only::ReduceOnlyOrders,
+            self_trade::{self, SelfTradeAction},
+            Processor,
+        },
         state::State,
         BacktestError,
+        InstrumentRules,
+        LiquidityImpact,
+        PriceBand,
+        SelfTradePrevention,
     },
-    depth::{L2MarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
+    depth::{ApplySnapshot, L2MarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
     prelude::OrdType,
     types::{
         Event,
         Order,
         OrderId,
+        RejectReason,
         Side,
         Status,
         TimeInForce,
+        TriggerSource,
         EXCH_ASK_DEPTH_CLEAR_EVENT,
         EXCH_ASK_DEPTH_EVENT,
         EXCH_ASK_DEPTH_SNAPSHOT_EVENT,
@@ -34,6 +46,8 @@ use crate::{
         EXCH_BUY_TRADE_EVENT,
         EXCH_DEPTH_CLEAR_EVENT,
         EXCH_EVENT,
+        EXCH_FUNDING_EVENT,
+        EXCH_MARK_PRICE_EVENT,
         EXCH_SELL_TRADE_EVENT,
     },
 };
@@ -42,7 +56,7 @@ use crate::{
 ///
 /// * Support order types: [OrdType::Limit](crate::types::OrdType::Limit)
 /// * Support time-in-force: [`TimeInForce::GTC`], [`TimeInForce::FOK`], [`TimeInForce::IOC`],
-///                          [`TimeInForce::GTX`]
+///                          [`TimeInForce::GTX`], [`TimeInForce::GTD`]
 ///
 /// **Conditions for Full Execution**
 /// Buy order in the order book
@@ -77,20 +91,21 @@ use crate::{
 /// results.
 /// (more comment will be added...)
 ///
-pub struct PartialFillExchange<AT, LM, QM, MD, FM>
+pub struct PartialFillExchange<AT, LM, QM, MD, FM, MM = NoMarginModel>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: QueueModel<MD>,
     MD: MarketDepth,
     FM: FeeModel,
+    MM: MarginModel,
 {
     reader: Reader<Event>,
     data: Data<Event>,
     row_num: usize,
 
     // key: order_id, value: Order
-    orders: Rc<RefCell<HashMap<OrderId, Order>>>,
+    orders: Arc<Mutex<HashMap<OrderId, Order>>>,
     // key: order's price tick, value: order_ids
     buy_orders: HashMap<i64, HashSet<OrderId>>,
     sell_orders: HashMap<i64, HashSet<OrderId>>,
@@ -99,26 +114,40 @@ where
     orders_from: OrderBus,
 
     depth: MD,
-    state: State<AT, FM>,
+    state: State<AT, FM, MM>,
     order_latency: LM,
     queue_model: QM,
+    rate_limit: Option<Box<dyn RateLimitModel>>,
+    conditional_orders: ConditionalOrders,
+    order_expiry: OrderExpiry,
+    reduce_only_orders: ReduceOnlyOrders,
+    instrument_rules: InstrumentRules,
+    self_trade_prevention: SelfTradePrevention,
+    price_band: PriceBand,
+
+    liquidity_impact: LiquidityImpact,
+    // key: price tick, value: the quantity consumed by taker fills that is still removed from the
+    // market depth
+    consumed_bid: HashMap<i64, f64>,
+    consumed_ask: HashMap<i64, f64>,
 
     filled_orders: Vec<OrderId>,
 }
 
-impl<AT, LM, QM, MD, FM> PartialFillExchange<AT, LM, QM, MD, FM>
+impl<AT, LM, QM, MD, FM, MM> PartialFillExchange<AT, LM, QM, MD, FM, MM>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: QueueModel<MD>,
-    MD: MarketDepth,
+    MD: MarketDepth + L2MarketDepth,
     FM: FeeModel,
+    MM: MarginModel,
 {
     /// Constructs an instance of `PartialFillExchange`.
     pub fn new(
         reader: Reader<Event>,
         depth: MD,
-        state: State<AT, FM>,
+        state: State<AT, FM, MM>,
         order_latency: LM,
         queue_model: QM,
         orders_to: OrderBus,
@@ -137,15 +166,79 @@ where
             state,
             order_latency,
             queue_model,
+            rate_limit: None,
+            conditional_orders: Default::default(),
+            order_expiry: Default::default(),
+            reduce_only_orders: Default::default(),
+            instrument_rules: Default::default(),
+            self_trade_prevention: SelfTradePrevention::None,
+            price_band: PriceBand::None,
+            liquidity_impact: LiquidityImpact::None,
+            consumed_bid: Default::default(),
+            consumed_ask: Default::default(),
             filled_orders: Default::default(),
         }
     }
 
+    /// Sets a [`RateLimitModel`] that rejects the requests exceeding the limits.
+    pub fn with_rate_limit(self, rate_limit: Box<dyn RateLimitModel>) -> Self {
+        Self {
+            rate_limit: Some(rate_limit),
+            ..self
+        }
+    }
+
+    /// Sets the [`InstrumentRules`] that the new and modified orders are checked against. By
+    /// default, no rules are applied.
+    pub fn with_instrument_rules(self, instrument_rules: InstrumentRules) -> Self {
+        Self {
+            instrument_rules,
+            ..self
+        }
+    }
+
+    /// Sets the self-trade prevention mode. The default value is [`SelfTradePrevention::None`].
+    pub fn with_self_trade_prevention(self, self_trade_prevention: SelfTradePrevention) -> Self {
+        Self {
+            self_trade_prevention,
+            ..self
+        }
+    }
+
+    /// Sets the [`PriceBand`] that limits how far a market order can sweep the market depth. The
+    /// default value is [`PriceBand::None`].
+    pub fn with_price_band(self, price_band: PriceBand) -> Self {
+        Self { price_band, ..self }
+    }
+
+    /// Sets how the liquidity consumed by the taker fills impacts the market depth. The default
+    /// value is [`LiquidityImpact::None`].
+    pub fn with_liquidity_impact(self, liquidity_impact: LiquidityImpact) -> Self {
+        Self {
+            liquidity_impact,
+            ..self
+        }
+    }
+
     fn process_recv_order_(
         &mut self,
         mut order: Order,
         recv_timestamp: i64,
     ) -> Result<(), BacktestError> {
+        if let Some(rate_limit) = &mut self.rate_limit {
+            let num_open_orders = self.orders.lock().unwrap().len();
+            if let Err(reason) = rate_limit.check(&order, recv_timestamp, num_open_orders) {
+                // The request is rejected before it reaches the matching engine, so it has no
+                // exchange timestamp.
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                let local_recv_timestamp =
+                    recv_timestamp + self.order_latency.response(recv_timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
         // Processes a new order.
         if order.req == Status::New {
             order.req = Status::None;
@@ -155,6 +248,11 @@ where
         else if order.req == Status::Canceled {
             order.req = Status::None;
             self.ack_cancel(order, recv_timestamp)?;
+        }
+        // Processes a modify order.
+        else if order.req == Status::Replaced {
+            order.req = Status::None;
+            self.ack_modify(order, recv_timestamp)?;
         } else {
             return Err(BacktestError::InvalidOrderRequest);
         }
@@ -184,9 +282,23 @@ where
                     // order.
                     // let q_qty =
                     //     (-order.front_q_qty / self.depth.lot_size()).floor() * self.depth.lot_size();
-                    let exec_qty = filled_qty.min(qty).min(order.leaves_qty);
-                    self.filled_orders.push(order.order_id);
-                    return self.fill(order, timestamp, true, order.price_tick, exec_qty);
+                    let slice_qty = order.slice_qty(self.depth.lot_size());
+                    let exec_qty = filled_qty.min(qty).min(slice_qty);
+                    if slice_qty < order.leaves_qty {
+                        // The iceberg order executes up to its displayed slice, and then displays
+                        // its next slice at the back of the queue.
+                        self.fill(order, timestamp, true, order.price_tick, exec_qty)?;
+                        if exec_qty >= slice_qty {
+                            self.queue_model.refresh(order, &self.depth);
+                        }
+                        return Ok(());
+                    }
+                    self.fill(order, timestamp, true, order.price_tick, exec_qty)?;
+                    // The partially filled order keeps its place in the book.
+                    if order.status == Status::Filled {
+                        self.filled_orders.push(order.order_id);
+                    }
+                    return Ok(());
                 }
             }
         }
@@ -216,9 +328,23 @@ where
                     // order.
                     // let q_qty =
                     //     (-order.front_q_qty / self.depth.lot_size()).floor() * self.depth.lot_size();
-                    let exec_qty = filled_qty.min(qty).min(order.leaves_qty);
-                    self.filled_orders.push(order.order_id);
-                    return self.fill(order, timestamp, true, order.price_tick, exec_qty);
+                    let slice_qty = order.slice_qty(self.depth.lot_size());
+                    let exec_qty = filled_qty.min(qty).min(slice_qty);
+                    if slice_qty < order.leaves_qty {
+                        // The iceberg order executes up to its displayed slice, and then displays
+                        // its next slice at the back of the queue.
+                        self.fill(order, timestamp, true, order.price_tick, exec_qty)?;
+                        if exec_qty >= slice_qty {
+                            self.queue_model.refresh(order, &self.depth);
+                        }
+                        return Ok(());
+                    }
+                    self.fill(order, timestamp, true, order.price_tick, exec_qty)?;
+                    // The partially filled order keeps its place in the book.
+                    if order.status == Status::Filled {
+                        self.filled_orders.push(order.order_id);
+                    }
+                    return Ok(());
                 }
             }
         }
@@ -260,12 +386,81 @@ where
 
         self.state.apply_fill(order);
         self.orders_to.append(order.clone(), local_recv_timestamp);
+        if !maker {
+            self.consume_liquidity(order.side, exec_price_tick, exec_qty, timestamp);
+        }
         Ok(())
     }
 
+    /// Removes the liquidity consumed by the taker fill from the market depth so that the
+    /// subsequent orders and the queue model cannot consume it again.
+    fn consume_liquidity(&mut self, side: Side, price_tick: i64, qty: f64, timestamp: i64) {
+        if self.liquidity_impact == LiquidityImpact::None {
+            return;
+        }
+        let price = price_tick as f64 * self.depth.tick_size();
+        match side {
+            Side::Buy => {
+                let consumed = qty.min(self.depth.ask_qty_at_tick(price_tick));
+                if consumed <= 0.0 {
+                    return;
+                }
+                let (_, _, _, prev_qty, new_qty, _) = self.depth.update_ask_depth(
+                    price,
+                    self.depth.ask_qty_at_tick(price_tick) - consumed,
+                    timestamp,
+                );
+                self.on_ask_qty_chg(price_tick, prev_qty, new_qty);
+                *self.consumed_ask.entry(price_tick).or_default() += consumed;
+            }
+            Side::Sell => {
+                let consumed = qty.min(self.depth.bid_qty_at_tick(price_tick));
+                if consumed <= 0.0 {
+                    return;
+                }
+                let (_, _, _, prev_qty, new_qty, _) = self.depth.update_bid_depth(
+                    price,
+                    self.depth.bid_qty_at_tick(price_tick) - consumed,
+                    timestamp,
+                );
+                self.on_bid_qty_chg(price_tick, prev_qty, new_qty);
+                *self.consumed_bid.entry(price_tick).or_default() += consumed;
+            }
+            Side::None | Side::Unsupported => unreachable!(),
+        }
+    }
+
+    /// Returns the quantity of the feed update at the price level, less the consumed liquidity
+    /// that still remains after the update.
+    fn impacted_qty(&mut self, side: Side, price: f64, qty: f64) -> f64 {
+        let price_tick = (price / self.depth.tick_size()).round() as i64;
+        let consumed = match side {
+            Side::Buy => &mut self.consumed_bid,
+            Side::Sell => &mut self.consumed_ask,
+            Side::None | Side::Unsupported => unreachable!(),
+        };
+        let Some(remaining) = consumed.get_mut(&price_tick) else {
+            return qty;
+        };
+        if let LiquidityImpact::Decay(factor) = self.liquidity_impact {
+            *remaining *= factor;
+            if (*remaining / self.depth.lot_size()).round() > 0.0 {
+                return (qty - *remaining).max(0.0);
+            }
+        }
+        consumed.remove(&price_tick);
+        qty
+    }
+
+    fn save_consumed(consumed: &HashMap<i64, f64>) -> Vec<(i64, f64)> {
+        let mut saved: Vec<_> = consumed.iter().map(|(&tick, &qty)| (tick, qty)).collect();
+        saved.sort_by_key(|(price_tick, _)| *price_tick);
+        saved
+    }
+
     fn remove_filled_orders(&mut self) {
         if !self.filled_orders.is_empty() {
-            let mut orders = self.orders.borrow_mut();
+            let mut orders = self.orders.lock().unwrap();
             for order_id in self.filled_orders.drain(..) {
                 let order = orders.remove(&order_id).unwrap();
                 if order.side == Side::Buy {
@@ -287,10 +482,15 @@ where
         let orders = self.orders.clone();
         if let Some(order_ids) = self.buy_orders.get(&price_tick) {
             for order_id in order_ids.iter() {
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 let order = orders_borrowed.get_mut(order_id).unwrap();
-                self.queue_model
-                    .depth(order, prev_qty, new_qty, &self.depth);
+                if order.is_hidden() {
+                    // The hidden order yields its priority to the displayed quantity.
+                    self.queue_model.refresh(order, &self.depth);
+                } else {
+                    self.queue_model
+                        .depth(order, prev_qty, new_qty, &self.depth);
+                }
             }
         }
     }
@@ -299,10 +499,15 @@ where
         let orders = self.orders.clone();
         if let Some(order_ids) = self.sell_orders.get(&price_tick) {
             for order_id in order_ids.iter() {
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 let order = orders_borrowed.get_mut(order_id).unwrap();
-                self.queue_model
-                    .depth(order, prev_qty, new_qty, &self.depth);
+                if order.is_hidden() {
+                    // The hidden order yields its priority to the displayed quantity.
+                    self.queue_model.refresh(order, &self.depth);
+                } else {
+                    self.queue_model
+                        .depth(order, prev_qty, new_qty, &self.depth);
+                }
             }
         }
     }
@@ -317,7 +522,7 @@ where
         // better to iterate orders dict instead of order price ladder.
         {
             let orders = self.orders.clone();
-            let mut orders_borrowed = orders.borrow_mut();
+            let mut orders_borrowed = orders.lock().unwrap();
             if prev_best_tick == INVALID_MIN
                 || (orders_borrowed.len() as i64) < new_best_tick - prev_best_tick
             {
@@ -353,7 +558,7 @@ where
         // better to iterate orders dict instead of order price ladder.
         {
             let orders = self.orders.clone();
-            let mut orders_borrowed = orders.borrow_mut();
+            let mut orders_borrowed = orders.lock().unwrap();
             if prev_best_tick == INVALID_MAX
                 || (orders_borrowed.len() as i64) < prev_best_tick - new_best_tick
             {
@@ -379,11 +584,283 @@ where
         Ok(())
     }
 
+    fn check_liquidation(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let fills = self.state.liquidation_fills(&self.depth);
+        if fills.is_empty() {
+            return Ok(());
+        }
+        // The open orders are canceled first so that they cannot reopen the position.
+        self.cancel_all_orders(timestamp)?;
+        for mut order in fills {
+            self.state.apply_fill(&order);
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+        Ok(())
+    }
+
+    /// Cancels all open orders, including the untriggered conditional orders, in the order of their
+    /// order IDs.
+    fn cancel_all_orders(&mut self, timestamp: i64) -> Result<(), BacktestError> {
+        let mut open_orders: Vec<_> = self
+            .orders
+            .lock()
+            .unwrap()
+            .values()
+            .chain(self.conditional_orders.orders())
+            .cloned()
+            .collect();
+        open_orders.sort_by_key(|order| order.order_id);
+        for order in open_orders {
+            self.ack_cancel(order, timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the self-trade prevention to the order before it is matched. Returns `false` if the
+    /// order itself is expired, in which case its response has already been sent.
+    fn prevent_self_trade(
+        &mut self,
+        order: &mut Order,
+        timestamp: i64,
+    ) -> Result<bool, BacktestError> {
+        let (actions, expired) = self_trade::prevent_self_trade(
+            self.self_trade_prevention,
+            order,
+            self.orders.lock().unwrap().values(),
+            self.depth.lot_size(),
+        );
+        for action in actions {
+            match action {
+                SelfTradeAction::Cancel(order_id) => {
+                    let resting = self.orders.lock().unwrap()[&order_id].clone();
+                    self.ack_cancel(resting, timestamp)?;
+                }
+                SelfTradeAction::Decrement(order_id, qty) => {
+                    let mut orders = self.orders.lock().unwrap();
+                    let resting = orders.get_mut(&order_id).unwrap();
+                    resting.qty -= qty;
+                    resting.leaves_qty -= qty;
+                    resting.exch_timestamp = timestamp;
+                    let local_recv_timestamp =
+                        timestamp + self.order_latency.response(timestamp, resting);
+                    self.orders_to
+                        .append(resting.clone(), local_recv_timestamp);
+                }
+            }
+        }
+        if expired {
+            order.status = Status::Expired;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, order);
+            self.orders_to.append(order.clone(), local_recv_timestamp);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn trigger_conditional_orders(
+        &mut self,
+        source: TriggerSource,
+        price_tick: i64,
+        timestamp: i64,
+    ) -> Result<(), BacktestError> {
+        for mut order in self.conditional_orders.update(source, price_tick) {
+            if !self.state.has_margin_for(&order, &self.depth) {
+                // The order has already been accepted, so it expires instead of being rejected.
+                order.status = Status::Expired;
+                order.reject_reason = RejectReason::InsufficientMargin;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+            } else {
+                self.ack_new(order, timestamp)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expire_orders(&mut self, timestamp: i64) {
+        for order_id in self.order_expiry.pop_expired(timestamp) {
+            let exch_order = if self
+                .conditional_orders
+                .get(order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                self.conditional_orders.cancel(order_id)
+            } else if self
+                .orders
+                .lock()
+                .unwrap()
+                .get(&order_id)
+                .is_some_and(|order| OrderExpiry::has_expired(order, timestamp))
+            {
+                let order = self.orders.lock().unwrap().remove(&order_id).unwrap();
+                if order.side == Side::Buy {
+                    self.buy_orders
+                        .get_mut(&order.price_tick)
+                        .unwrap()
+                        .remove(&order_id);
+                } else {
+                    self.sell_orders
+                        .get_mut(&order.price_tick)
+                        .unwrap()
+                        .remove(&order_id);
+                }
+                Some(order)
+            } else {
+                // The order has already been filled or canceled.
+                None
+            };
+            if let Some(mut exch_order) = exch_order {
+                exch_order.status = Status::Expired;
+                exch_order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &exch_order);
+                self.orders_to.append(exch_order, local_recv_timestamp);
+            }
+        }
+    }
+
+    /// Cancels the resting reduce-only orders that would increase the position, which the fills
+    /// have changed.
+    fn expire_reduce_only_orders(&mut self, timestamp: i64) {
+        let lot_size = self.depth.lot_size();
+        for order_id in self
+            .reduce_only_orders
+            .pop_position_increasing(self.state.values(), lot_size)
+        {
+            if !self
+                .orders
+                .lock()
+                .unwrap()
+                .get(&order_id)
+                .is_some_and(ReduceOnlyOrders::is_reduce_only)
+            {
+                // The order has already been filled or canceled.
+                continue;
+            }
+            let mut order = self.orders.lock().unwrap().remove(&order_id).unwrap();
+            if order.side == Side::Buy {
+                self.buy_orders
+                    .get_mut(&order.price_tick)
+                    .unwrap()
+                    .remove(&order_id);
+            } else {
+                self.sell_orders
+                    .get_mut(&order.price_tick)
+                    .unwrap()
+                    .remove(&order_id);
+            }
+            order.status = Status::Expired;
+            order.reject_reason = RejectReason::ReduceOnly;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+        }
+    }
+
     fn ack_new(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
-        if self.orders.borrow().contains_key(&order.order_id) {
+        if self.orders.lock().unwrap().contains_key(&order.order_id) {
+            return Err(BacktestError::OrderIdExist);
+        }
+        if self.conditional_orders.contains(order.order_id) {
             return Err(BacktestError::OrderIdExist);
         }
 
+        // The triggered order has already been checked against the rules when it was placed.
+        if !order.triggered {
+            let rule_check = self.instrument_rules.check(
+                order.order_type,
+                order.price(),
+                order.qty,
+                &self.depth,
+            );
+            if let Err(reason) = rule_check {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+        }
+
+        // A good-till-date order whose expire timestamp has already passed is not accepted.
+        if OrderExpiry::has_expired(&order, timestamp) {
+            order.status = Status::Expired;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+        self.order_expiry.schedule(&order);
+
+        // The conditional order is held without being matched until it is triggered.
+        if order.order_type.is_conditional() {
+            order.exch_timestamp = timestamp;
+            if let Err(reason) = self.conditional_orders.add(&mut order) {
+                order.req = Status::Rejected;
+                order.reject_reason = reason;
+            }
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if ReduceOnlyOrders::is_reduce_only(&order) {
+            // The reduce-only order is clipped to the position, and is not accepted if it would
+            // only increase the position.
+            let reducible_qty = ReduceOnlyOrders::reducible_qty(
+                order.side,
+                order.position_side,
+                self.state.values(),
+            );
+            if (reducible_qty / self.depth.lot_size()).round() <= 0.0 {
+                if order.triggered {
+                    // The triggered order has already been accepted, so it expires instead.
+                    order.status = Status::Expired;
+                } else {
+                    order.req = Status::Rejected;
+                }
+                order.reject_reason = RejectReason::ReduceOnly;
+                order.exch_timestamp = timestamp;
+                let local_recv_timestamp =
+                    timestamp + self.order_latency.response(timestamp, &order);
+                self.orders_to.append(order, local_recv_timestamp);
+                return Ok(());
+            }
+            if order.qty > reducible_qty {
+                order.qty = reducible_qty;
+                order.leaves_qty = reducible_qty;
+            }
+            self.reduce_only_orders.add(&order);
+        }
+
+        if !self.state.has_margin_for(&order, &self.depth) {
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::InsufficientMargin;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if order.time_in_force != TimeInForce::GTX
+            && !self.prevent_self_trade(&mut order, timestamp)?
+        {
+            return Ok(());
+        }
+
+        self.match_order(order, timestamp)
+    }
+
+    /// Matches the order that has passed the admission checks against the market depth, and either
+    /// rests it in the book or executes it according to its order type and time-in-force.
+    fn match_order(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
         if order.side == Side::Buy {
             match order.order_type {
                 OrdType::Limit => {
@@ -455,7 +932,7 @@ where
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
                                 Ok(())
                             }
-                            TimeInForce::GTC => {
+                            TimeInForce::GTC | TimeInForce::GTD => {
                                 // Takes the market.
                                 for t in self.depth.best_ask_tick()..order.price_tick {
                                     let qty = self.depth.ask_qty_at_tick(t);
@@ -479,7 +956,7 @@ where
                         }
                     } else {
                         match order.time_in_force {
-                            TimeInForce::GTC | TimeInForce::GTX => {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
                                 // Initializes the order's queue position.
                                 self.queue_model.new_order(&mut order, &self.depth);
                                 order.status = Status::New;
@@ -494,7 +971,7 @@ where
                                     timestamp + self.order_latency.response(timestamp, &order);
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
 
-                                self.orders.borrow_mut().insert(order.order_id, order);
+                                self.orders.lock().unwrap().insert(order.order_id, order);
                                 Ok(())
                             }
                             TimeInForce::FOK | TimeInForce::IOC => {
@@ -511,16 +988,16 @@ where
                     }
                 }
                 OrdType::Market => {
-                    // todo: set the proper upper bound.
-                    for t in self.depth.best_ask_tick()..(self.depth.best_ask_tick() + 100) {
-                        let qty = self.depth.ask_qty_at_tick(t);
-                        if qty > 0.0 {
-                            let exec_qty = qty.min(order.leaves_qty);
-                            self.fill(&mut order, timestamp, false, t, exec_qty)?;
-                        }
+                    // Sweeps the ask levels up to the price band.
+                    let limit_tick = self.price_band.buy_limit_tick(self.depth.best_ask_tick());
+                    let mut t = self.depth.best_ask_tick();
+                    while t != INVALID_MAX && t <= limit_tick {
+                        let exec_qty = self.depth.ask_qty_at_tick(t).min(order.leaves_qty);
+                        self.fill(&mut order, timestamp, false, t, exec_qty)?;
                         if order.status == Status::Filled {
                             return Ok(());
                         }
+                        t = self.depth.next_ask_tick(t);
                     }
                     order.status = Status::Expired;
 
@@ -530,7 +1007,7 @@ where
                     self.orders_to.append(order.clone(), local_recv_timestamp);
                     Ok(())
                 }
-                OrdType::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                _ => Err(BacktestError::InvalidOrderRequest),
             }
         } else {
             match order.order_type {
@@ -603,7 +1080,7 @@ where
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
                                 Ok(())
                             }
-                            TimeInForce::GTC => {
+                            TimeInForce::GTC | TimeInForce::GTD => {
                                 // Takes the market.
                                 for t in (order.price_tick..=self.depth.best_bid_tick()).rev() {
                                     let qty = self.depth.bid_qty_at_tick(t);
@@ -629,7 +1106,7 @@ where
                         }
                     } else {
                         match order.time_in_force {
-                            TimeInForce::GTC | TimeInForce::GTX => {
+                            TimeInForce::GTC | TimeInForce::GTD | TimeInForce::GTX => {
                                 // Initializes the order's queue position.
                                 self.queue_model.new_order(&mut order, &self.depth);
                                 order.status = Status::New;
@@ -644,7 +1121,7 @@ where
                                     timestamp + self.order_latency.response(timestamp, &order);
                                 self.orders_to.append(order.clone(), local_recv_timestamp);
 
-                                self.orders.borrow_mut().insert(order.order_id, order);
+                                self.orders.lock().unwrap().insert(order.order_id, order);
 
                                 Ok(())
                             }
@@ -662,17 +1139,16 @@ where
                     }
                 }
                 OrdType::Market => {
-                    // todo: set the proper lower bound.
-                    for t in ((self.depth.best_bid_tick() - 100)..=self.depth.best_bid_tick()).rev()
-                    {
-                        let qty = self.depth.bid_qty_at_tick(t);
-                        if qty > 0.0 {
-                            let exec_qty = qty.min(order.leaves_qty);
-                            self.fill(&mut order, timestamp, false, t, exec_qty)?;
-                        }
+                    // Sweeps the bid levels down to the price band.
+                    let limit_tick = self.price_band.sell_limit_tick(self.depth.best_bid_tick());
+                    let mut t = self.depth.best_bid_tick();
+                    while t != INVALID_MIN && t >= limit_tick {
+                        let exec_qty = self.depth.bid_qty_at_tick(t).min(order.leaves_qty);
+                        self.fill(&mut order, timestamp, false, t, exec_qty)?;
                         if order.status == Status::Filled {
                             return Ok(());
                         }
+                        t = self.depth.next_bid_tick(t);
                     }
                     order.status = Status::Expired;
 
@@ -682,19 +1158,29 @@ where
                     self.orders_to.append(order.clone(), local_recv_timestamp);
                     Ok(())
                 }
-                OrdType::Unsupported => Err(BacktestError::InvalidOrderRequest),
+                _ => Err(BacktestError::InvalidOrderRequest),
             }
         }
     }
 
     fn ack_cancel(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
+        if let Some(mut exch_order) = self.conditional_orders.cancel(order.order_id) {
+            exch_order.status = Status::Canceled;
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
         let exch_order = {
-            let mut order_borrowed = self.orders.borrow_mut();
+            let mut order_borrowed = self.orders.lock().unwrap();
             order_borrowed.remove(&order.order_id)
         };
 
         if exch_order.is_none() {
             order.req = Status::Rejected;
+            order.reject_reason = RejectReason::UnknownOrder;
             order.exch_timestamp = timestamp;
             let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
             self.orders_to.append(order, local_recv_timestamp);
@@ -725,144 +1211,158 @@ where
     }
 
     fn ack_modify(&mut self, mut order: Order, timestamp: i64) -> Result<(), BacktestError> {
-        todo!();
-        // let mut exch_order = {
-        //     let mut order_borrowed = self.orders.borrow_mut();
-        //     let exch_order = order_borrowed.remove(&order.order_id);
-        //
-        //     // The order can be already deleted due to fill or expiration.
-        //     if exch_order.is_none() {
-        //         order.req = Status::Rejected;
-        //         order.exch_timestamp = timestamp;
-        //         let local_recv_timestamp =
-        //             timestamp + self.order_latency.response(timestamp, &order);
-        //         self.orders_to.append(order, local_recv_timestamp);
-        //         return Ok(local_recv_timestamp);
-        //     }
-        //
-        //     exch_order.unwrap()
-        // };
-        //
-        // let prev_price_tick = exch_order.price_tick;
-        // exch_order.price_tick = order.price_tick;
-        // // No partial fill occurs.
-        // exch_order.qty = order.qty;
-        // // The initialization of the order queue position may not occur when the modified quantity
-        // // is smaller than the previous quantity, depending on the exchanges. It may need to
-        // // implement exchange-specific specialization.
-        // let init_q_pos = true;
-        //
-        // if exch_order.side == Side::Buy {
-        //     // Check if the buy order price is greater than or equal to the current best ask.
-        //     if exch_order.price_tick >= self.depth.best_ask_tick {
-        //         self.buy_orders
-        //             .get_mut(&prev_price_tick)
-        //             .unwrap()
-        //             .remove(&exch_order.order_id);
-        //
-        //         if exch_order.time_in_force == TimeInForce::GTX {
-        //             exch_order.status = Status::Expired;
-        //         } else {
-        //             // Take the market.
-        //             return self.fill(&mut exch_order, timestamp, false, self.depth.best_ask_tick);
-        //         }
-        //
-        //         exch_order.exch_timestamp = timestamp;
-        //         let local_recv_timestamp =
-        //             timestamp + self.order_latency.response(timestamp, &exch_order);
-        //         self.orders_to
-        //             .append(exch_order.clone(), local_recv_timestamp);
-        //         Ok(local_recv_timestamp)
-        //     } else {
-        //         // The exchange accepts this order.
-        //         if prev_price_tick != exch_order.price_tick {
-        //             self.buy_orders
-        //                 .get_mut(&prev_price_tick)
-        //                 .unwrap()
-        //                 .remove(&exch_order.order_id);
-        //             self.buy_orders
-        //                 .entry(exch_order.price_tick)
-        //                 .or_insert(HashSet::new())
-        //                 .insert(exch_order.order_id);
-        //         }
-        //         if init_q_pos || prev_price_tick != exch_order.price_tick {
-        //             // Initialize the order's queue position.
-        //             self.queue_model.new_order(&mut exch_order, &self.depth);
-        //         }
-        //         exch_order.status = Status::New;
-        //
-        //         exch_order.exch_timestamp = timestamp;
-        //         let local_recv_timestamp =
-        //             timestamp + self.order_latency.response(timestamp, &exch_order);
-        //         self.orders_to
-        //             .append(exch_order.clone(), local_recv_timestamp);
-        //
-        //         let mut order_borrowed = self.orders.borrow_mut();
-        //         order_borrowed.insert(exch_order.order_id, exch_order);
-        //
-        //         Ok(local_recv_timestamp)
-        //     }
-        // } else {
-        //     // Check if the sell order price is less than or equal to the current best bid.
-        //     if exch_order.price_tick <= self.depth.best_bid_tick {
-        //         self.sell_orders
-        //             .get_mut(&prev_price_tick)
-        //             .unwrap()
-        //             .remove(&exch_order.order_id);
-        //
-        //         if exch_order.time_in_force == TimeInForce::GTX {
-        //             exch_order.status = Status::Expired;
-        //         } else {
-        //             // Take the market.
-        //             return self.fill(&mut exch_order, timestamp, false, self.depth.best_bid_tick);
-        //         }
-        //
-        //         exch_order.exch_timestamp = timestamp;
-        //         let local_recv_timestamp =
-        //             timestamp + self.order_latency.response(timestamp, &exch_order);
-        //         self.orders_to
-        //             .append(exch_order.clone(), local_recv_timestamp);
-        //         Ok(local_recv_timestamp)
-        //     } else {
-        //         // The exchange accepts this order.
-        //         if prev_price_tick != exch_order.price_tick {
-        //             self.sell_orders
-        //                 .get_mut(&prev_price_tick)
-        //                 .unwrap()
-        //                 .remove(&exch_order.order_id);
-        //             self.sell_orders
-        //                 .entry(exch_order.price_tick)
-        //                 .or_insert(HashSet::new())
-        //                 .insert(exch_order.order_id);
-        //         }
-        //         if init_q_pos || prev_price_tick != exch_order.price_tick {
-        //             // Initialize the order's queue position.
-        //             self.queue_model.new_order(&mut exch_order, &self.depth);
-        //         }
-        //         exch_order.status = Status::New;
-        //
-        //         exch_order.exch_timestamp = timestamp;
-        //         let local_recv_timestamp =
-        //             timestamp + self.order_latency.response(timestamp, &exch_order);
-        //         self.orders_to
-        //             .append(exch_order.clone(), local_recv_timestamp);
-        //
-        //         let mut order_borrowed = self.orders.borrow_mut();
-        //         order_borrowed.insert(exch_order.order_id, exch_order);
-        //
-        //         Ok(local_recv_timestamp)
-        //     }
-        // }
+        let rule_check = self.instrument_rules.check(
+            order.order_type,
+            order.price(),
+            order.qty,
+            &self.depth,
+        );
+        if let Err(reason) = rule_check {
+            order.req = Status::Rejected;
+            order.reject_reason = reason;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        if let Some(mut exch_order) = self.conditional_orders.modify(&order) {
+            exch_order.exch_timestamp = timestamp;
+            let local_recv_timestamp =
+                timestamp + self.order_latency.response(timestamp, &exch_order);
+            self.orders_to.append(exch_order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        let exch_order = {
+            let mut order_borrowed = self.orders.lock().unwrap();
+            order_borrowed.remove(&order.order_id)
+        };
+
+        // The order can be already deleted due to fill or expiration.
+        if exch_order.is_none() {
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::UnknownOrder;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        let mut exch_order = exch_order.unwrap();
+
+        // The modified quantity is the total order quantity, including the quantity already
+        // executed. The modification is rejected if it does not exceed the executed quantity.
+        let filled_qty = exch_order.qty - exch_order.leaves_qty;
+        let leaves_qty = order.qty - filled_qty;
+        if (leaves_qty / self.depth.lot_size()).round() <= 0.0 {
+            self.orders
+                .lock()
+                .unwrap()
+                .insert(exch_order.order_id, exch_order);
+
+            order.req = Status::Rejected;
+            order.reject_reason = RejectReason::BelowFilledQty;
+            order.exch_timestamp = timestamp;
+            let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &order);
+            self.orders_to.append(order, local_recv_timestamp);
+            return Ok(());
+        }
+
+        let prev_price_tick = exch_order.price_tick;
+        let prev_leaves_qty = exch_order.leaves_qty;
+        exch_order.price_tick = order.price_tick;
+        exch_order.qty = order.qty;
+        exch_order.leaves_qty = leaves_qty;
+
+        if exch_order.time_in_force != TimeInForce::GTX
+            && !self.prevent_self_trade(&mut exch_order, timestamp)?
+        {
+            if exch_order.side == Side::Buy {
+                self.buy_orders
+                    .get_mut(&prev_price_tick)
+                    .unwrap()
+                    .remove(&exch_order.order_id);
+            } else {
+                self.sell_orders
+                    .get_mut(&prev_price_tick)
+                    .unwrap()
+                    .remove(&exch_order.order_id);
+            }
+            return Ok(());
+        }
+
+        if exch_order.side == Side::Buy {
+            self.buy_orders
+                .get_mut(&prev_price_tick)
+                .unwrap()
+                .remove(&exch_order.order_id);
+
+            // Checks if the buy order price is greater than or equal to the current best ask.
+            if exch_order.price_tick >= self.depth.best_ask_tick() {
+                // The order is matched in the same way as a new order that crosses the book,
+                // without going through the admission checks again.
+                return self.match_order(exch_order, timestamp);
+            }
+
+            // The exchange accepts this modification.
+            self.buy_orders
+                .entry(exch_order.price_tick)
+                .or_default()
+                .insert(exch_order.order_id);
+        } else {
+            self.sell_orders
+                .get_mut(&prev_price_tick)
+                .unwrap()
+                .remove(&exch_order.order_id);
+
+            // Checks if the sell order price is less than or equal to the current best bid.
+            if exch_order.price_tick <= self.depth.best_bid_tick() {
+                // The order is matched in the same way as a new order that crosses the book,
+                // without going through the admission checks again.
+                return self.match_order(exch_order, timestamp);
+            }
+
+            // The exchange accepts this modification.
+            self.sell_orders
+                .entry(exch_order.price_tick)
+                .or_default()
+                .insert(exch_order.order_id);
+        }
+
+        // Updates the order's queue position. The order loses its priority if the price changes or
+        // the quantity increases.
+        self.queue_model.amend(
+            &mut exch_order,
+            prev_price_tick,
+            prev_leaves_qty,
+            &self.depth,
+        );
+
+        // The response to the modification is not a fill, even if the order has already been
+        // partially filled.
+        exch_order.exec_qty = 0.0;
+        exch_order.exch_timestamp = timestamp;
+        let local_recv_timestamp = timestamp + self.order_latency.response(timestamp, &exch_order);
+        self.orders_to
+            .append(exch_order.clone(), local_recv_timestamp);
+
+        self.orders
+            .lock()
+            .unwrap()
+            .insert(exch_order.order_id, exch_order);
+        Ok(())
     }
 }
 
-impl<AT, LM, QM, MD, FM> Processor for PartialFillExchange<AT, LM, QM, MD, FM>
+impl<AT, LM, QM, MD, FM, MM> Processor for PartialFillExchange<AT, LM, QM, MD, FM, MM>
 where
     AT: AssetType,
     LM: LatencyModel,
     QM: QueueModel<MD>,
-    MD: MarketDepth + L2MarketDepth,
+    MD: MarketDepth + L2MarketDepth + ApplySnapshot,
     FM: FeeModel,
+    MM: MarginModel,
 {
     fn initialize_data(&mut self) -> Result<i64, BacktestError> {
         self.data = self.reader.next_data()?;
@@ -879,17 +1379,23 @@ where
         let row_num = self.row_num;
         if self.data[row_num].is(EXCH_BID_DEPTH_CLEAR_EVENT) {
             self.depth.clear_depth(Side::Buy, self.data[row_num].px);
+            self.consumed_bid.clear();
         } else if self.data[row_num].is(EXCH_ASK_DEPTH_CLEAR_EVENT) {
             self.depth.clear_depth(Side::Sell, self.data[row_num].px);
+            self.consumed_ask.clear();
         } else if self.data[row_num].is(EXCH_DEPTH_CLEAR_EVENT) {
             self.depth.clear_depth(Side::None, 0.0);
+            self.consumed_bid.clear();
+            self.consumed_ask.clear();
         } else if self.data[row_num].is(EXCH_BID_DEPTH_EVENT)
             || self.data[row_num].is(EXCH_BID_DEPTH_SNAPSHOT_EVENT)
         {
+            let qty =
+                self.impacted_qty(Side::Buy, self.data[row_num].px, self.data[row_num].qty);
             let (price_tick, prev_best_bid_tick, best_bid_tick, prev_qty, new_qty, timestamp) =
                 self.depth.update_bid_depth(
                     self.data[row_num].px,
-                    self.data[row_num].qty,
+                    qty,
                     self.data[row_num].exch_ts,
                 );
             self.on_bid_qty_chg(price_tick, prev_qty, new_qty);
@@ -899,10 +1405,12 @@ where
         } else if self.data[row_num].is(EXCH_ASK_DEPTH_EVENT)
             || self.data[row_num].is(EXCH_ASK_DEPTH_SNAPSHOT_EVENT)
         {
+            let qty =
+                self.impacted_qty(Side::Sell, self.data[row_num].px, self.data[row_num].qty);
             let (price_tick, prev_best_ask_tick, best_ask_tick, prev_qty, new_qty, timestamp) =
                 self.depth.update_ask_depth(
                     self.data[row_num].px,
-                    self.data[row_num].qty,
+                    qty,
                     self.data[row_num].exch_ts,
                 );
             self.on_ask_qty_chg(price_tick, prev_qty, new_qty);
@@ -914,7 +1422,7 @@ where
             let qty = self.data[row_num].qty;
             {
                 let orders = self.orders.clone();
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 if self.depth.best_bid_tick() == INVALID_MIN
                     || (orders_borrowed.len() as i64) < price_tick - self.depth.best_bid_tick()
                 {
@@ -945,12 +1453,17 @@ where
                 }
             }
             self.remove_filled_orders();
+            self.trigger_conditional_orders(
+                TriggerSource::LastPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
         } else if self.data[row_num].is(EXCH_SELL_TRADE_EVENT) {
             let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
             let qty = self.data[row_num].qty;
             {
                 let orders = self.orders.clone();
-                let mut orders_borrowed = orders.borrow_mut();
+                let mut orders_borrowed = orders.lock().unwrap();
                 if self.depth.best_ask_tick() == INVALID_MAX
                     || (orders_borrowed.len() as i64) < self.depth.best_ask_tick() - price_tick
                 {
@@ -981,7 +1494,32 @@ where
                 }
             }
             self.remove_filled_orders();
+            self.trigger_conditional_orders(
+                TriggerSource::LastPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        } else if self.data[row_num].is(EXCH_FUNDING_EVENT) {
+            self.state
+                .apply_funding(self.data[row_num].px, self.data[row_num].fval);
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
+        } else if self.data[row_num].is(EXCH_MARK_PRICE_EVENT) {
+            let price_tick = (self.data[row_num].px / self.depth.tick_size()).round() as i64;
+            self.trigger_conditional_orders(
+                TriggerSource::MarkPrice,
+                price_tick,
+                self.data[row_num].exch_ts,
+            )?;
         }
+        self.check_liquidation(self.data[row_num].exch_ts)?;
+        self.expire_reduce_only_orders(self.data[row_num].exch_ts);
+        self.order_latency
+            .on_feed(self.data[row_num].exch_ts, &self.data[row_num]);
 
         // Checks
         let mut next_ts = 0;
@@ -1009,25 +1547,274 @@ where
         timestamp: i64,
         _wait_resp_order_id: Option<OrderId>,
     ) -> Result<bool, BacktestError> {
+        self.expire_orders(timestamp);
+
         // Processes the order part.
         while !self.orders_from.is_empty() {
             let recv_timestamp = self.orders_from.earliest_timestamp().unwrap();
             if timestamp == recv_timestamp {
                 let (order, _) = self.orders_from.pop_front().unwrap();
+                self.order_latency.on_request(order.req);
                 self.process_recv_order_(order, recv_timestamp)?;
+                self.order_latency.on_request(Status::None);
             } else {
                 assert!(recv_timestamp > timestamp);
                 break;
             }
         }
+        self.expire_reduce_only_orders(timestamp);
         Ok(false)
     }
 
     fn earliest_recv_order_timestamp(&self) -> i64 {
-        self.orders_from.earliest_timestamp().unwrap_or(i64::MAX)
+        // Wakes up at the expire timestamp of the good-till-date order as well.
+        self.orders_from
+            .earliest_timestamp()
+            .unwrap_or(i64::MAX)
+            .min(self.order_expiry.earliest_timestamp().unwrap_or(i64::MAX))
     }
 
     fn earliest_send_order_timestamp(&self) -> i64 {
         self.orders_to.earliest_timestamp().unwrap_or(i64::MAX)
     }
+
+    fn checkpoint(&self) -> Result<Vec<u8>, BacktestError> {
+        let mut orders = Vec::new();
+        for order in self.orders.lock().unwrap().values() {
+            orders.push((order.clone(), self.queue_model.encode_q(order)?));
+        }
+        orders.sort_by_key(|(order, _)| order.order_id);
+        checkpoint::encode(&ExchCheckpoint {
+            data_num: self.reader.data_num(),
+            row_num: self.row_num,
+            depth: DepthCheckpoint::l2(&self.depth),
+            state_values: self.state.state_values.clone(),
+            orders,
+            queue: Vec::new(),
+            conditional_orders: checkpoint::encode(&self.conditional_orders)?,
+            order_expiry: checkpoint::encode(&self.order_expiry)?,
+            reduce_only_orders: checkpoint::encode(&self.reduce_only_orders)?,
+            rate_limit: match &self.rate_limit {
+                Some(rate_limit) => rate_limit.checkpoint()?,
+                None => Vec::new(),
+            },
+            consumed_bid: Self::save_consumed(&self.consumed_bid),
+            consumed_ask: Self::save_consumed(&self.consumed_ask),
+            order_latency: self.order_latency.checkpoint()?,
+        })
+    }
+
+    fn restore(&mut self, checkpoint: &[u8]) -> Result<(), BacktestError> {
+        let state: ExchCheckpoint = checkpoint::decode(checkpoint)?;
+        checkpoint::restore_data(&mut self.reader, &mut self.data, state.data_num)?;
+        self.row_num = state.row_num;
+        state.depth.restore_l2(&mut self.depth);
+        self.state.state_values = state.state_values;
+        self.conditional_orders = checkpoint::decode(&state.conditional_orders)?;
+        self.order_expiry = checkpoint::decode(&state.order_expiry)?;
+        self.reduce_only_orders = checkpoint::decode(&state.reduce_only_orders)?;
+        if let Some(rate_limit) = &mut self.rate_limit {
+            rate_limit.restore(&state.rate_limit)?;
+        }
+        self.order_latency.restore(&state.order_latency)?;
+        self.consumed_bid = state.consumed_bid.into_iter().collect();
+        self.consumed_ask = state.consumed_ask.into_iter().collect();
+
+        let mut orders = self.orders.lock().unwrap();
+        orders.clear();
+        self.buy_orders.clear();
+        self.sell_orders.clear();
+        for (mut order, q) in state.orders {
+            self.queue_model.decode_q(&mut order, &q)?;
+            if order.side == Side::Buy {
+                self.buy_orders
+                    .entry(order.price_tick)
+                    .or_default()
+                    .insert(order.order_id);
+            } else {
+                self.sell_orders
+                    .entry(order.price_tick)
+                    .or_default()
+                    .insert(order.order_id);
+            }
+            orders.insert(order.order_id, order);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{asset_builder, event, to_data},
+            Backtest,
+            ExchangeKind,
+            LiquidityImpact,
+            PriceBand,
+        },
+        prelude::{Bot, OrdType, RejectReason, Status, TimeInForce},
+        types::{BuildError, BUY_EVENT, DEPTH_EVENT, SELL_EVENT, TRADE_EVENT},
+    };
+
+    #[test]
+    fn consume_liquidity_by_taker_fills() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 2.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 102.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 10_000, 101.0, 3.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 99.0, 4.0),
+        ])];
+
+        // (impact, status of the second order, final position)
+        let cases = [
+            (LiquidityImpact::None, Status::Filled, 6.0),
+            (LiquidityImpact::Resync, Status::Expired, 5.0),
+            // Half of the consumed quantity remains removed after the feed update.
+            (LiquidityImpact::Decay(0.5), Status::Expired, 4.0),
+        ];
+        for (impact, status, position) in cases {
+            let build = || {
+                Backtest::builder()
+                    .add_asset(
+                        asset_builder(&data)
+                            .exchange(ExchangeKind::PartialFillExchange)
+                            .liquidity_impact(impact)
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap()
+            };
+            let mut hbt = build();
+
+            hbt.elapse(2_000).unwrap();
+            hbt.submit_buy_order(0, 1, 101.0, 2.0, TimeInForce::IOC, OrdType::Limit, true)
+                .unwrap();
+            assert_eq!(hbt.orders(0)[&1].status, Status::Filled, "{impact:?}");
+            // The liquidity at 101 has been consumed by the first order.
+            hbt.submit_buy_order(0, 2, 101.0, 1.0, TimeInForce::IOC, OrdType::Limit, true)
+                .unwrap();
+            assert_eq!(hbt.orders(0)[&2].status, status, "{impact:?}");
+
+            // The consumed liquidity is restored from the checkpoint.
+            let mut checkpoint = Vec::new();
+            hbt.checkpoint(&mut checkpoint).unwrap();
+            let mut hbt = build();
+            hbt.restore(&mut checkpoint.as_slice()).unwrap();
+
+            // After the feed update at 101.
+            hbt.elapse(10_000).unwrap();
+            hbt.submit_buy_order(0, 3, 101.0, 3.0, TimeInForce::IOC, OrdType::Limit, true)
+                .unwrap();
+            assert_eq!(hbt.position(0), position, "{impact:?}");
+        }
+    }
+
+    #[test]
+    fn sweep_market_order_within_price_band() {
+        // The ask side is sparse, with its levels far apart.
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 1.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 300.0, 1.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 500.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+
+        // (price band, (status, position) after the market buy order)
+        let cases = [
+            (PriceBand::None, (Status::Filled, 3.0)),
+            (PriceBand::Ticks(250), (Status::Expired, 2.0)),
+            (PriceBand::Percentage(10.0), (Status::Expired, 1.0)),
+        ];
+        for (price_band, (status, position)) in cases {
+            let mut hbt = Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .exchange(ExchangeKind::PartialFillExchange)
+                        .price_band(price_band)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap();
+
+            hbt.elapse(2_000).unwrap();
+            hbt.submit_buy_order(0, 1, 101.0, 3.0, TimeInForce::IOC, OrdType::Market, true)
+                .unwrap();
+            assert_eq!(hbt.orders(0)[&1].status, status, "{price_band:?}");
+            assert_eq!(hbt.position(0), position, "{price_band:?}");
+        }
+
+        // The no-partial-fill exchange has no price band to apply.
+        let result = asset_builder(&data)
+            .exchange(ExchangeKind::NoPartialFillExchange)
+            .price_band(PriceBand::Ticks(250))
+            .build();
+        assert!(matches!(result, Err(BuildError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn modify_order() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 98.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | TRADE_EVENT, 3_000, 99.0, 4.0),
+            event(SELL_EVENT | TRADE_EVENT, 6_000, 99.0, 3.0),
+            event(SELL_EVENT | TRADE_EVENT, 9_000, 99.0, 4.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 99.0, 4.0),
+        ])];
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                asset_builder(&data)
+                    .exchange(ExchangeKind::PartialFillExchange)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_buy_order(0, 1, 99.0, 2.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        hbt.submit_buy_order(0, 2, 99.0, 3.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        // Both orders advance to the front of the queue, with one lot ahead of them.
+        hbt.elapse(1_000).unwrap();
+
+        // Decreasing the quantity keeps the queue position, while moving the price sends the order
+        // to the back of the queue, even if it moves back to the previous price.
+        hbt.modify(0, 1, 99.0, 1.0, true).unwrap();
+        hbt.modify(0, 2, 98.0, 3.0, true).unwrap();
+        hbt.modify(0, 2, 99.0, 3.0, true).unwrap();
+        hbt.elapse(4_000).unwrap();
+        let orders = hbt.orders(0);
+        assert_eq!(orders[&1].status, Status::Filled);
+        assert_eq!(orders[&2].status, Status::New);
+        assert_eq!(orders[&2].price_tick, 99);
+        assert_eq!(hbt.position(0), 1.0);
+
+        hbt.elapse(3_000).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.status, Status::PartiallyFilled);
+        assert_eq!(order.leaves_qty, 1.0);
+        assert_eq!(hbt.position(0), 3.0);
+
+        // The modified quantity doesn't exceed the quantity already filled.
+        hbt.modify(0, 2, 99.0, 2.0, true).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.qty, 3.0);
+        assert_eq!(order.reject_reason, RejectReason::BelowFilledQty);
+
+        // The modified order that crosses the market depth takes the market for the leaves
+        // quantity.
+        hbt.modify(0, 2, 101.0, 3.0, true).unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.status, Status::Filled);
+        assert_eq!(order.exec_price_tick, 101);
+        assert_eq!(hbt.position(0), 4.0);
+    }
 }
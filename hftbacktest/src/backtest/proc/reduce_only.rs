@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use bincode::{Decode, Encode};
+
+use crate::types::{Order, OrderId, PositionSide, Side, StateValues};
+
+/// Tracks the reduce-only orders that the exchange holds so that it can cancel the ones that would
+/// increase the position once fills change the position.
+///
+/// In hedge mode, the orders that close the long or short position are reduce-only against that
+/// position as well.
+///
+/// Filled or canceled orders are not removed from the tracking, so the exchange should check that
+/// it still holds the reduce-only order before canceling it.
+#[derive(Default, Encode, Decode)]
+pub struct ReduceOnlyOrders {
+    // The sides and the position sides of the reduce-only orders by order ID.
+    orders: BTreeMap<OrderId, (Side, PositionSide)>,
+}
+
+impl ReduceOnlyOrders {
+    /// Returns `true` if the order can only reduce the position, either because it is flagged
+    /// reduce-only or because it closes the long or short position in hedge mode.
+    pub fn is_reduce_only(order: &Order) -> bool {
+        order.reduce_only
+            || matches!(
+                (order.position_side, order.side),
+                (PositionSide::Long, Side::Sell) | (PositionSide::Short, Side::Buy)
+            )
+    }
+
+    /// Returns the quantity that an order on the given side can execute without increasing the
+    /// position it trades, which is 0 if the order is on the same side as the position.
+    pub fn reducible_qty(side: Side, position_side: PositionSide, values: &StateValues) -> f64 {
+        let position = match position_side {
+            PositionSide::Long => values.long_position,
+            PositionSide::Short => values.short_position,
+            PositionSide::Both | PositionSide::Unsupported => values.position,
+        };
+        match side {
+            Side::Buy if position < 0.0 => -position,
+            Side::Sell if position > 0.0 => position,
+            _ => 0.0,
+        }
+    }
+
+    /// Tracks the order if it is reduce-only.
+    pub fn add(&mut self, order: &Order) {
+        if Self::is_reduce_only(order) {
+            self.orders
+                .insert(order.order_id, (order.side, order.position_side));
+        }
+    }
+
+    /// Removes and returns the IDs of the orders that would increase the position.
+    pub fn pop_position_increasing(&mut self, values: &StateValues, lot_size: f64) -> Vec<OrderId> {
+        let mut popped = Vec::new();
+        self.orders.retain(|order_id, (side, position_side)| {
+            if (Self::reducible_qty(*side, *position_side, values) / lot_size).round() > 0.0 {
+                true
+            } else {
+                popped.push(*order_id);
+                false
+            }
+        });
+        popped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::fixture::{build_backtest, event, limit_order, to_data},
+        prelude::{Bot, OrderRequest, RejectReason, Side, Status},
+        types::{BUY_EVENT, DEPTH_EVENT, SELL_EVENT},
+    };
+
+    #[test]
+    fn reduce_only_orders() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+        let request = |order_id: u64, price: f64, qty: f64, side: Side, reduce_only: bool| {
+            OrderRequest {
+                reduce_only,
+                ..limit_order(order_id, side, price, qty)
+            }
+        };
+
+        hbt.elapse(1_000).unwrap();
+        // There is no position to reduce.
+        hbt.submit_order(0, request(1, 102.0, 1.0, Side::Sell, true), true)
+            .unwrap();
+        let order = &hbt.orders(0)[&1];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.reject_reason, RejectReason::ReduceOnly);
+
+        hbt.submit_order(0, request(2, 101.0, 2.0, Side::Buy, false), true)
+            .unwrap();
+        assert_eq!(hbt.position(0), 2.0);
+
+        // The quantity is clipped to the position.
+        hbt.submit_order(0, request(3, 102.0, 5.0, Side::Sell, true), true)
+            .unwrap();
+        let order = &hbt.orders(0)[&3];
+        assert_eq!(order.status, Status::New);
+        assert_eq!(order.qty, 2.0);
+        hbt.submit_order(0, request(4, 103.0, 1.0, Side::Sell, true), true)
+            .unwrap();
+        assert_eq!(hbt.orders(0)[&4].status, Status::New);
+
+        // Once the position is closed, the resting reduce-only orders would increase the position.
+        hbt.submit_order(0, request(5, 99.0, 2.0, Side::Sell, false), true)
+            .unwrap();
+        assert_eq!(hbt.position(0), 0.0);
+        hbt.elapse(100).unwrap();
+        for order_id in [3, 4] {
+            let order = &hbt.orders(0)[&order_id];
+            assert_eq!(order.status, Status::Expired);
+            assert_eq!(order.reject_reason, RejectReason::ReduceOnly);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    backtest::SelfTradePrevention,
+    prelude::OrdType,
+    types::{Order, OrderId, Side},
+};
+
+/// What the exchange does to a resting order of the same account to prevent a self-trade.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SelfTradeAction {
+    /// Cancels the resting order.
+    Cancel(OrderId),
+    /// Decrements the quantity of the resting order by the given quantity.
+    Decrement(OrderId, f64),
+}
+
+/// Applies the self-trade prevention mode to the incoming order before it is matched, and returns
+/// the actions on the resting orders along with whether the incoming order expires.
+///
+/// The incoming order is checked against the resting orders on the opposite side that it would
+/// match, in price-time priority. The walk stops once the leaves quantity of the incoming order is
+/// used up by the resting orders, so the resting orders that the incoming order cannot reach are
+/// left untouched. For [`SelfTradePrevention::DecrementAndCancel`], the quantity of the incoming
+/// order is decremented in place.
+pub fn prevent_self_trade<'a>(
+    mode: SelfTradePrevention,
+    order: &mut Order,
+    resting_orders: impl Iterator<Item = &'a Order>,
+    lot_size: f64,
+) -> (Vec<SelfTradeAction>, bool) {
+    if mode == SelfTradePrevention::None {
+        return (Vec::new(), false);
+    }
+    let mut matched: Vec<_> = resting_orders
+        .filter(|resting| match order.side {
+            Side::Buy => {
+                resting.side == Side::Sell
+                    && (order.order_type == OrdType::Market
+                        || resting.price_tick <= order.price_tick)
+            }
+            Side::Sell => {
+                resting.side == Side::Buy
+                    && (order.order_type == OrdType::Market
+                        || resting.price_tick >= order.price_tick)
+            }
+            Side::None | Side::Unsupported => false,
+        })
+        .map(|resting| {
+            let price_priority = if order.side == Side::Buy {
+                resting.price_tick
+            } else {
+                -resting.price_tick
+            };
+            (
+                (price_priority, resting.exch_timestamp, resting.order_id),
+                resting.leaves_qty,
+            )
+        })
+        .collect();
+    if matched.is_empty() {
+        return (Vec::new(), false);
+    }
+    matched.sort_unstable_by_key(|(priority, _)| *priority);
+
+    let is_used_up = |leaves_qty: f64| (leaves_qty / lot_size).round() <= 0.0;
+    let mut actions = Vec::new();
+    match mode {
+        SelfTradePrevention::None => (Vec::new(), false),
+        SelfTradePrevention::CancelNewest => (actions, true),
+        SelfTradePrevention::CancelOldest | SelfTradePrevention::CancelBoth => {
+            let mut leaves_qty = order.leaves_qty;
+            for ((_, _, order_id), resting_leaves_qty) in matched {
+                actions.push(SelfTradeAction::Cancel(order_id));
+                leaves_qty -= resting_leaves_qty;
+                if is_used_up(leaves_qty) {
+                    break;
+                }
+            }
+            (actions, mode == SelfTradePrevention::CancelBoth)
+        }
+        SelfTradePrevention::DecrementAndCancel => {
+            // The smaller of the two is canceled, and the larger is decremented by the smaller's
+            // quantity.
+            for ((_, _, order_id), resting_leaves_qty) in matched {
+                if resting_leaves_qty <= order.leaves_qty {
+                    order.qty -= resting_leaves_qty;
+                    order.leaves_qty -= resting_leaves_qty;
+                    actions.push(SelfTradeAction::Cancel(order_id));
+                } else {
+                    actions.push(SelfTradeAction::Decrement(order_id, order.leaves_qty));
+                    order.leaves_qty = 0.0;
+                }
+                if is_used_up(order.leaves_qty) {
+                    break;
+                }
+            }
+            let expired = is_used_up(order.leaves_qty);
+            (actions, expired)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            proc::self_trade::{prevent_self_trade, SelfTradeAction},
+            SelfTradePrevention,
+        },
+        types::{OrdType, Order, Side, TimeInForce},
+    };
+
+    #[test]
+    fn match_only_reachable_resting_orders() {
+        let order = |order_id, price_tick, qty, side, exch_timestamp| {
+            let mut order = Order::new(
+                order_id,
+                price_tick,
+                1.0,
+                qty,
+                side,
+                OrdType::Limit,
+                TimeInForce::GTC,
+            );
+            order.exch_timestamp = exch_timestamp;
+            order
+        };
+        let resting_orders = [
+            order(1, 101, 1.0, Side::Sell, 1_000),
+            order(2, 100, 2.0, Side::Sell, 2_000),
+            order(3, 100, 1.0, Side::Sell, 1_000),
+            order(4, 102, 1.0, Side::Sell, 1_000),
+        ];
+
+        // The incoming order is used up before it reaches the resting order at 101.
+        let cases = [
+            (SelfTradePrevention::CancelNewest, vec![], (true, 2.0)),
+            (
+                SelfTradePrevention::CancelOldest,
+                vec![SelfTradeAction::Cancel(3), SelfTradeAction::Cancel(2)],
+                (false, 2.0),
+            ),
+            (
+                SelfTradePrevention::CancelBoth,
+                vec![SelfTradeAction::Cancel(3), SelfTradeAction::Cancel(2)],
+                (true, 2.0),
+            ),
+            (
+                SelfTradePrevention::DecrementAndCancel,
+                vec![SelfTradeAction::Cancel(3), SelfTradeAction::Decrement(2, 1.0)],
+                (true, 1.0),
+            ),
+        ];
+        for (mode, expected_actions, (expected_expired, expected_qty)) in cases {
+            let mut incoming = order(5, 101, 2.0, Side::Buy, 3_000);
+            let (actions, expired) =
+                prevent_self_trade(mode, &mut incoming, resting_orders.iter(), 1.0);
+            assert_eq!(actions, expected_actions, "{mode:?}");
+            assert_eq!(expired, expected_expired, "{mode:?}");
+            assert_eq!(incoming.qty, expected_qty, "{mode:?}");
+        }
+    }
+}
@@ -1,17 +1,32 @@
 use crate::{
-    backtest::{assettype::AssetType, models::FeeModel},
-    types::{Order, StateValues},
+    backtest::{
+        assettype::AssetType,
+        models::{FeeModel, MarginModel, NoMarginModel},
+    },
+    depth::{MarketDepth, INVALID_MAX, INVALID_MIN},
+    types::{
+        OrdType,
+        Order,
+        PositionSide,
+        Side,
+        StateValues,
+        Status,
+        TimeInForce,
+        LIQUIDATION_ORDER_ID,
+    },
 };
 
 #[derive(Debug)]
-pub struct State<AT, FM>
+pub struct State<AT, FM, MM = NoMarginModel>
 where
     AT: AssetType,
     FM: FeeModel,
+    MM: MarginModel,
 {
     pub state_values: StateValues,
     pub asset_type: AT,
     pub fee_model: FM,
+    pub margin_model: MM,
 }
 
 impl<AT, FM> State<AT, FM>
@@ -20,6 +35,17 @@ where
     FM: FeeModel,
 {
     pub fn new(asset_type: AT, fee_model: FM) -> Self {
+        Self::with_margin_model(asset_type, fee_model, NoMarginModel)
+    }
+}
+
+impl<AT, FM, MM> State<AT, FM, MM>
+where
+    AT: AssetType,
+    FM: FeeModel,
+    MM: MarginModel,
+{
+    pub fn with_margin_model(asset_type: AT, fee_model: FM, margin_model: MM) -> Self {
         Self {
             state_values: StateValues {
                 position: 0.0,
@@ -28,16 +54,37 @@ where
                 num_trades: 0,
                 trading_volume: 0.0,
                 trading_value: 0.0,
+                funding: 0.0,
+                long_position: 0.0,
+                short_position: 0.0,
             },
             fee_model,
             asset_type,
+            margin_model,
         }
     }
 
     #[inline]
     pub fn apply_fill(&mut self, order: &Order) {
         let amount = self.asset_type.amount(order.exec_price(), order.exec_qty);
-        self.state_values.position += order.exec_qty * AsRef::<f64>::as_ref(&order.side);
+        let qty = order.exec_qty * AsRef::<f64>::as_ref(&order.side);
+        self.state_values.position += qty;
+        match order.position_side {
+            PositionSide::Long => self.state_values.long_position += qty,
+            PositionSide::Short => self.state_values.short_position += qty,
+            PositionSide::Both | PositionSide::Unsupported => {}
+        }
+        if order.status == Status::Liquidated && order.leaves_qty == 0.0 {
+            // The last fill of the liquidation closes the position exactly, regardless of the
+            // rounding errors accumulated across its fills.
+            match order.position_side {
+                PositionSide::Long => self.state_values.long_position = 0.0,
+                PositionSide::Short => self.state_values.short_position = 0.0,
+                PositionSide::Both | PositionSide::Unsupported => {}
+            }
+            self.state_values.position =
+                self.state_values.long_position + self.state_values.short_position;
+        }
         self.state_values.balance -= amount * AsRef::<f64>::as_ref(&order.side);
         self.state_values.fee += self.fee_model.amount(order, amount);
         self.state_values.num_trades += 1;
@@ -45,6 +92,15 @@ where
         self.state_values.trading_value += amount;
     }
 
+    #[inline]
+    pub fn apply_funding(&mut self, mark_price: f64, rate: f64) {
+        let amount = self
+            .asset_type
+            .funding(mark_price, self.state_values.position, rate);
+        self.state_values.balance += amount;
+        self.state_values.funding += amount;
+    }
+
     #[inline]
     pub fn equity(&self, mid: f64) -> f64 {
         self.asset_type.equity(
@@ -59,4 +115,428 @@ where
     pub fn values(&self) -> &StateValues {
         &self.state_values
     }
+
+    /// Returns the positions that are margined separately, as pairs of the position side and the
+    /// position: the long and short positions in hedge mode, or the net position in one-way mode.
+    fn legs(&self) -> [(PositionSide, f64); 2] {
+        let values = &self.state_values;
+        if values.long_position != 0.0 || values.short_position != 0.0 {
+            [
+                (PositionSide::Long, values.long_position),
+                (PositionSide::Short, values.short_position),
+            ]
+        } else {
+            [(PositionSide::Both, values.position), (PositionSide::Both, 0.0)]
+        }
+    }
+
+    /// Returns `true` if the collateral plus the equity covers the initial margin of the position
+    /// that results from the order being fully filled at its price. In hedge mode, the initial
+    /// margin is the sum of those of the long and short positions. Orders that don't increase the
+    /// position they trade, or that require no initial margin, are always accepted. The margin of
+    /// the other open orders is not reserved.
+    pub fn has_margin_for<MD>(&self, order: &Order, depth: &MD) -> bool
+    where
+        MD: MarketDepth,
+    {
+        let values = &self.state_values;
+        let (position, other_position) = match order.position_side {
+            PositionSide::Long => (values.long_position, values.short_position),
+            PositionSide::Short => (values.short_position, values.long_position),
+            PositionSide::Both | PositionSide::Unsupported => (values.position, 0.0),
+        };
+        let new_position = position + order.qty * AsRef::<f64>::as_ref(&order.side);
+        if new_position.abs() <= position.abs() {
+            return true;
+        }
+        let price = order.price_tick as f64 * order.tick_size;
+        let mut mid = (depth.best_bid() + depth.best_ask()) / 2.0;
+        if mid.is_nan() {
+            mid = price;
+        }
+        let initial_margin = self
+            .margin_model
+            .initial_margin(self.asset_type.amount(price, new_position.abs()))
+            + self
+                .margin_model
+                .initial_margin(self.asset_type.amount(mid, other_position.abs()));
+        initial_margin <= 0.0 || initial_margin <= self.margin_model.collateral() + self.equity(mid)
+    }
+
+    /// Returns the fills that liquidate the entire position, if the collateral plus the equity has
+    /// fallen below the maintenance margin. In hedge mode, the maintenance margin is the sum of
+    /// those of the long and short positions, and both are liquidated.
+    ///
+    /// Each position is closed like a market order, walking the market depth on the opposite side
+    /// from the best price, and the quantity that the market depth cannot absorb is filled at the
+    /// last level. The fills are already executed, with their status set to [`Status::Liquidated`]
+    /// and `leaves_qty` set to the quantity of the position left to close, but are not yet applied
+    /// to the state.
+    pub fn liquidation_fills<MD>(&self, depth: &MD) -> Vec<Order>
+    where
+        MD: MarketDepth,
+    {
+        let legs = self.legs();
+        if legs.iter().all(|&(_, position)| position == 0.0) {
+            return Vec::new();
+        }
+        let mid = (depth.best_bid() + depth.best_ask()) / 2.0;
+        if mid.is_nan() {
+            return Vec::new();
+        }
+        let maintenance_margin: f64 = legs
+            .iter()
+            .map(|&(_, position)| {
+                self.margin_model
+                    .maintenance_margin(self.asset_type.amount(mid, position.abs()))
+            })
+            .sum();
+        if maintenance_margin <= 0.0
+            || self.margin_model.collateral() + self.equity(mid) >= maintenance_margin
+        {
+            return Vec::new();
+        }
+
+        let mut fills = Vec::new();
+        for (position_side, position) in legs {
+            if position == 0.0 {
+                continue;
+            }
+            let side = if position > 0.0 { Side::Sell } else { Side::Buy };
+            let mut price_tick = match side {
+                Side::Sell => depth.best_bid_tick(),
+                _ => depth.best_ask_tick(),
+            };
+            let mut leaves_qty = position.abs();
+            while leaves_qty > 0.0 {
+                let (qty, next_tick) = match side {
+                    Side::Sell => (
+                        depth.bid_qty_at_tick(price_tick),
+                        depth.next_bid_tick(price_tick),
+                    ),
+                    _ => (
+                        depth.ask_qty_at_tick(price_tick),
+                        depth.next_ask_tick(price_tick),
+                    ),
+                };
+                let exhausted = next_tick == INVALID_MIN || next_tick == INVALID_MAX;
+                let exec_qty = if exhausted {
+                    leaves_qty
+                } else {
+                    qty.min(leaves_qty)
+                };
+                if exec_qty > 0.0 {
+                    leaves_qty -= exec_qty;
+                    let mut order = Order::new(
+                        LIQUIDATION_ORDER_ID,
+                        price_tick,
+                        depth.tick_size(),
+                        position.abs(),
+                        side,
+                        OrdType::Market,
+                        TimeInForce::IOC,
+                    );
+                    order.position_side = position_side;
+                    order.status = Status::Liquidated;
+                    order.exec_price_tick = price_tick;
+                    order.exec_qty = exec_qty;
+                    order.leaves_qty = leaves_qty;
+                    order.maker = false;
+                    fills.push(order);
+                }
+                price_tick = next_tick;
+            }
+        }
+        fills
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backtest::{
+            fixture::{asset_builder, build_backtest, event, limit_order, to_data},
+            models::{LeverageTier, TieredMarginModel},
+            Backtest,
+        },
+        prelude::{
+            Bot,
+            Event,
+            OrdType,
+            OrderRequest,
+            PositionSide,
+            RejectReason,
+            Side,
+            Status,
+            TimeInForce,
+            Trigger,
+            TriggerSource,
+        },
+        types::{BUY_EVENT, DEPTH_EVENT, FUNDING_EVENT, LIQUIDATION_ORDER_ID, SELL_EVENT},
+    };
+
+    #[test]
+    fn settle_funding() {
+        let funding = |exch_ts: i64, px: f64, rate: f64| Event {
+            fval: rate,
+            ..event(FUNDING_EVENT, exch_ts, px, 0.0)
+        };
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            funding(10_000, 100.0, 0.0001),
+            funding(20_000, 110.0, -0.0002),
+            event(BUY_EVENT | DEPTH_EVENT, 30_000, 99.0, 3.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+
+        hbt.elapse(2_000).unwrap();
+        hbt.submit_buy_order(0, 1, 101.0, 2.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.position(0), 2.0);
+        while hbt.elapse(10_000).unwrap() {}
+
+        let expected = -2.0 * 100.0 * 0.0001 - 2.0 * 110.0 * -0.0002;
+        let state_values = hbt.state_values(0);
+        assert!((state_values.funding - expected).abs() < 1e-12);
+        assert!((state_values.balance - (-202.0 + expected)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reject_and_liquidate_on_margin() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 40.0, 3.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 39.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 10_000, 101.0, 4.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 99.0, 0.0),
+            event(SELL_EVENT | DEPTH_EVENT, 30_000, 101.0, 3.0),
+        ])];
+        let margin_model = TieredMarginModel::new(
+            100.0,
+            10.0,
+            vec![LeverageTier {
+                notional_cap: 1_000_000.0,
+                max_leverage: 20.0,
+                maintenance_margin_rate: 0.05,
+            }],
+        );
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                asset_builder(&data)
+                    .margin_model(margin_model)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        hbt.elapse(2_000).unwrap();
+        // The initial margin of 202 exceeds the collateral of 100.
+        hbt.submit_buy_order(0, 1, 101.0, 20.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.position(0), 0.0);
+        assert_eq!(hbt.orders(0).get(&1).unwrap().status, Status::Expired);
+
+        hbt.submit_buy_order(0, 2, 101.0, 5.0, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.position(0), 5.0);
+
+        // The mid-price falls to 70.5 once the best bid is removed, which breaches the maintenance
+        // margin, and the position is sold down the bid side from the new best bid.
+        while hbt.elapse(10_000).unwrap() {}
+        assert_eq!(hbt.position(0), 0.0);
+        let order = hbt.orders(0).get(&LIQUIDATION_ORDER_ID).unwrap();
+        assert_eq!(order.status, Status::Liquidated);
+        assert_eq!(order.side, Side::Sell);
+        assert_eq!(order.exec_price_tick, 39);
+        assert_eq!(order.exec_qty, 2.0);
+        assert_eq!(order.leaves_qty, 0.0);
+        let state_values = hbt.state_values(0);
+        assert_eq!(state_values.num_trades, 3);
+        assert!((state_values.balance - (-505.0 + 3.0 * 40.0 + 2.0 * 39.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cancel_open_orders_on_liquidation() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 40.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 99.0, 0.0),
+            // This would fill the resting buy order after the liquidation.
+            event(SELL_EVENT | DEPTH_EVENT, 30_000, 97.0, 3.0),
+            event(SELL_EVENT | DEPTH_EVENT, 40_000, 101.0, 3.0),
+        ])];
+        let margin_model = TieredMarginModel::new(
+            10.0,
+            10.0,
+            vec![LeverageTier {
+                notional_cap: 1_000_000.0,
+                max_leverage: 20.0,
+                maintenance_margin_rate: 0.05,
+            }],
+        );
+        let mut hbt = Backtest::builder()
+            .add_asset(
+                asset_builder(&data)
+                    .margin_model(margin_model)
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+
+        hbt.elapse(2_000).unwrap();
+        hbt.submit_buy_order(0, 1, 101.0, 0.5, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        assert_eq!(hbt.position(0), 0.5);
+        hbt.submit_buy_order(0, 2, 98.0, 0.2, TimeInForce::GTC, OrdType::Limit, true)
+            .unwrap();
+        let request = OrderRequest {
+            order_type: OrdType::StopMarket,
+            trigger: Some(Trigger::new(105.0, TriggerSource::LastPrice)),
+            ..limit_order(3, Side::Buy, 105.0, 0.2)
+        };
+        hbt.submit_order(0, request, true).unwrap();
+        assert_eq!(hbt.orders(0)[&2].status, Status::New);
+        assert_eq!(hbt.orders(0)[&3].status, Status::New);
+
+        while hbt.elapse(10_000).unwrap() {}
+        assert_eq!(hbt.position(0), 0.0);
+        assert_eq!(hbt.orders(0)[&LIQUIDATION_ORDER_ID].exec_qty, 0.5);
+        for order_id in [2, 3] {
+            let order = &hbt.orders(0)[&order_id];
+            assert_eq!(order.status, Status::Canceled);
+            assert_eq!(order.exch_timestamp, 20_000);
+        }
+    }
+
+    #[test]
+    fn margin_hedge_mode_positions_separately() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            // The mid-price rises to 300 without the book being crossed.
+            event(SELL_EVENT | DEPTH_EVENT, 20_000, 101.0, 0.0),
+            event(SELL_EVENT | DEPTH_EVENT, 20_000, 301.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 99.0, 0.0),
+            event(BUY_EVENT | DEPTH_EVENT, 20_000, 299.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 30_000, 301.0, 3.0),
+        ])];
+        let build = |collateral: f64| {
+            let margin_model = TieredMarginModel::new(
+                collateral,
+                10.0,
+                vec![LeverageTier {
+                    notional_cap: 1_000_000.0,
+                    max_leverage: 20.0,
+                    maintenance_margin_rate: 0.05,
+                }],
+            );
+            Backtest::builder()
+                .add_asset(
+                    asset_builder(&data)
+                        .margin_model(margin_model)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap()
+        };
+        let request = |order_id: u64, price: f64, side: Side, position_side| OrderRequest {
+            position_side,
+            ..limit_order(order_id, side, price, 0.5)
+        };
+
+        // Opening the short position requires its own initial margin of 4.95 on top of the 5.0 of
+        // the long position, even though the net position becomes flat.
+        let mut hbt = build(10.0);
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_order(0, request(1, 101.0, Side::Buy, PositionSide::Long), true)
+            .unwrap();
+        hbt.submit_order(0, request(2, 99.0, Side::Sell, PositionSide::Short), true)
+            .unwrap();
+        let order = &hbt.orders(0)[&2];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.reject_reason, RejectReason::InsufficientMargin);
+        assert_eq!(hbt.state_values(0).long_position, 0.5);
+
+        // The maintenance margin of both positions, 15 at the mid-price of 300, breaches the
+        // collateral plus the equity, which stays around 10.95 as the net position is flat, so
+        // both positions are liquidated.
+        let mut hbt = build(12.0);
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_order(0, request(1, 101.0, Side::Buy, PositionSide::Long), true)
+            .unwrap();
+        hbt.submit_order(0, request(2, 99.0, Side::Sell, PositionSide::Short), true)
+            .unwrap();
+        let state_values = hbt.state_values(0);
+        assert_eq!(state_values.long_position, 0.5);
+        assert_eq!(state_values.short_position, -0.5);
+        assert_eq!(state_values.position, 0.0);
+
+        while hbt.elapse(10_000).unwrap() {}
+        let state_values = hbt.state_values(0);
+        assert_eq!(state_values.long_position, 0.0);
+        assert_eq!(state_values.short_position, 0.0);
+        assert_eq!(state_values.position, 0.0);
+        assert_eq!(state_values.num_trades, 4);
+        assert!((state_values.balance - (-1.0 + 0.5 * 299.0 - 0.5 * 301.0)).abs() < 1e-12);
+        let order = &hbt.orders(0)[&LIQUIDATION_ORDER_ID];
+        assert_eq!(order.status, Status::Liquidated);
+        assert_eq!(order.position_side, PositionSide::Short);
+        assert_eq!(order.side, Side::Buy);
+        assert_eq!(order.exec_price_tick, 301);
+    }
+
+    #[test]
+    fn hedge_mode_positions() {
+        let data = [to_data(&[
+            event(BUY_EVENT | DEPTH_EVENT, 1_000, 99.0, 5.0),
+            event(SELL_EVENT | DEPTH_EVENT, 1_000, 101.0, 5.0),
+            event(BUY_EVENT | DEPTH_EVENT, 10_000, 99.0, 4.0),
+        ])];
+        let mut hbt = build_backtest(&data);
+        let request = |order_id: u64, price: f64, qty: f64, side: Side, position_side| {
+            OrderRequest {
+                position_side,
+                ..limit_order(order_id, side, price, qty)
+            }
+        };
+
+        hbt.elapse(1_000).unwrap();
+        hbt.submit_order(0, request(1, 101.0, 3.0, Side::Buy, PositionSide::Long), true)
+            .unwrap();
+        hbt.submit_order(0, request(2, 99.0, 1.0, Side::Sell, PositionSide::Short), true)
+            .unwrap();
+        let state_values = hbt.state_values(0);
+        assert_eq!(state_values.long_position, 3.0);
+        assert_eq!(state_values.short_position, -1.0);
+        assert_eq!(state_values.position, 2.0);
+
+        // Closing the short position is reduce-only against the short leg, even though the net
+        // position is long.
+        hbt.submit_order(0, request(3, 98.0, 2.0, Side::Buy, PositionSide::Short), true)
+            .unwrap();
+        let order = &hbt.orders(0)[&3];
+        assert_eq!(order.status, Status::New);
+        assert_eq!(order.qty, 1.0);
+
+        // The quantity that closes the long position is clipped to the long leg.
+        hbt.submit_order(0, request(4, 99.0, 5.0, Side::Sell, PositionSide::Long), true)
+            .unwrap();
+        let state_values = hbt.state_values(0);
+        assert_eq!(state_values.long_position, 0.0);
+        assert_eq!(state_values.short_position, -1.0);
+        assert_eq!(state_values.position, -1.0);
+
+        // There is no long position left to close.
+        hbt.submit_order(0, request(5, 102.0, 1.0, Side::Sell, PositionSide::Long), true)
+            .unwrap();
+        let order = &hbt.orders(0)[&5];
+        assert_eq!(order.status, Status::Expired);
+        assert_eq!(order.reject_reason, RejectReason::ReduceOnly);
+    }
 }
@@ -0,0 +1,107 @@
+use std::{
+    num::NonZeroUsize,
+    panic,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    backtest::{recorder::BacktestRecorder, Backtest, BacktestError},
+    depth::MarketDepth,
+    types::Bot,
+};
+
+/// Runs a backtest for each of the parameter sets across a pool of threads and collects the
+/// [`BacktestRecorder`] of each run.
+///
+/// To run all backtests over a single copy of the market data, load the data into memory once and
+/// build each backtest with [`DataSource::Data`](crate::backtest::DataSource::Data) clones of it,
+/// which share the loaded buffer instead of copying it. Since the shared buffer must not be
+/// modified, apply the feed latency offset to the data before the sweep rather than setting it
+/// through the asset builder.
+///
+/// **Example**
+/// ```ignore
+/// let data = read_npz_file::<Event>("btcusdt_20240809.npz", "data")?;
+/// let results = ParamSweep::new(vec![0.5, 1.0, 2.0])
+///     .num_threads(3)
+///     .run(
+///         |half_spread| build_backtest(data.clone(), *half_spread),
+///         |hbt, recorder, half_spread| market_making(hbt, recorder, *half_spread),
+///     );
+/// ```
+pub struct ParamSweep<P> {
+    params: Vec<P>,
+    num_threads: usize,
+}
+
+impl<P> ParamSweep<P>
+where
+    P: Sync,
+{
+    /// Constructs an instance of `ParamSweep` that runs a backtest for each of the parameter sets.
+    pub fn new(params: Vec<P>) -> Self {
+        Self {
+            params,
+            num_threads: thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1),
+        }
+    }
+
+    /// Sets the number of threads running the backtests. The default value is the available
+    /// parallelism of the system.
+    pub fn num_threads(self, num_threads: usize) -> Self {
+        Self {
+            num_threads: num_threads.max(1),
+            ..self
+        }
+    }
+
+    /// Runs the backtests and returns the result of each run in the order of the parameter sets.
+    ///
+    /// * `build` - Builds the [`Backtest`] for the parameter set.
+    /// * `strategy` - Runs the strategy with the parameter set. The [`Backtest`] is then closed.
+    pub fn run<MD, B, S, E>(self, build: B, strategy: S) -> Vec<Result<BacktestRecorder, E>>
+    where
+        MD: MarketDepth,
+        B: Fn(&P) -> Result<Backtest<MD>, E> + Sync,
+        S: Fn(&mut Backtest<MD>, &mut BacktestRecorder, &P) -> Result<(), E> + Sync,
+        E: From<BacktestError> + Send,
+    {
+        let next = AtomicUsize::new(0);
+        let run_next = || {
+            let mut results = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(param) = self.params.get(i) else {
+                    break;
+                };
+                let result = build(param).and_then(|mut hbt| {
+                    let mut recorder = BacktestRecorder::new(&hbt);
+                    strategy(&mut hbt, &mut recorder, param)?;
+                    hbt.close()?;
+                    Ok(recorder)
+                });
+                results.push((i, result));
+            }
+            results
+        };
+
+        let mut results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..self.num_threads.min(self.params.len()))
+                .map(|_| s.spawn(run_next))
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err))
+                })
+                .collect()
+        });
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+}
@@ -1,4 +1,7 @@
-use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    ops::Bound,
+};
 
 use super::{
     ApplySnapshot,
@@ -12,7 +15,7 @@ use super::{
 use crate::{
     backtest::{data::Data, BacktestError},
     prelude::{OrderId, Side},
-    types::{Event, BUY_EVENT, SELL_EVENT},
+    types::{Event, BUY_EVENT, DEPTH_SNAPSHOT_EVENT, EXCH_EVENT, LOCAL_EVENT, SELL_EVENT},
 };
 
 /// L2 Market depth implementation based on a B-Tree map.
@@ -209,6 +212,24 @@ impl MarketDepth for BTreeMarketDepth {
     fn ask_qty_at_tick(&self, price_tick: i64) -> f64 {
         *self.ask_depth.get(&price_tick).unwrap_or(&0.0)
     }
+
+    #[inline(always)]
+    fn next_bid_tick(&self, price_tick: i64) -> i64 {
+        self.bid_depth
+            .range(..price_tick)
+            .next_back()
+            .map(|(&price_tick, _)| price_tick)
+            .unwrap_or(INVALID_MIN)
+    }
+
+    #[inline(always)]
+    fn next_ask_tick(&self, price_tick: i64) -> i64 {
+        self.ask_depth
+            .range((Bound::Excluded(price_tick), Bound::Unbounded))
+            .next()
+            .map(|(&price_tick, _)| price_tick)
+            .unwrap_or(INVALID_MAX)
+    }
 }
 
 impl ApplySnapshot for BTreeMarketDepth {
@@ -231,7 +252,37 @@ impl ApplySnapshot for BTreeMarketDepth {
     }
 
     fn snapshot(&self) -> Vec<Event> {
-        todo!()
+        let mut events = Vec::new();
+
+        for (&px_tick, &qty) in self.bid_depth.iter().rev() {
+            events.push(Event {
+                ev: EXCH_EVENT | LOCAL_EVENT | BUY_EVENT | DEPTH_SNAPSHOT_EVENT,
+                // todo: it's not a problem now, but it would be better to have valid timestamps.
+                exch_ts: 0,
+                local_ts: 0,
+                px: px_tick as f64 * self.tick_size,
+                qty,
+                order_id: 0,
+                ival: 0,
+                fval: 0.0,
+            });
+        }
+
+        for (&px_tick, &qty) in self.ask_depth.iter() {
+            events.push(Event {
+                ev: EXCH_EVENT | LOCAL_EVENT | SELL_EVENT | DEPTH_SNAPSHOT_EVENT,
+                // todo: it's not a problem now, but it would be better to have valid timestamps.
+                exch_ts: 0,
+                local_ts: 0,
+                px: px_tick as f64 * self.tick_size,
+                qty,
+                order_id: 0,
+                ival: 0,
+                fval: 0.0,
+            });
+        }
+
+        events
     }
 }
 
@@ -434,7 +485,14 @@ impl L3MarketDepth for BTreeMarketDepth {
 #[cfg(test)]
 mod tests {
     use crate::{
-        depth::{BTreeMarketDepth, L3MarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
+        depth::{
+            BTreeMarketDepth,
+            L2MarketDepth,
+            L3MarketDepth,
+            MarketDepth,
+            INVALID_MAX,
+            INVALID_MIN,
+        },
         types::Side,
     };
 
@@ -658,4 +716,35 @@ mod tests {
         assert_eq_qty!(depth.ask_qty_at_tick(4981), 0.0, lot_size);
         assert_eq_qty!(depth.ask_qty_at_tick(5002), 0.002, lot_size);
     }
+
+    #[test]
+    fn test_l2_levels() {
+        let lot_size = 0.001;
+        let mut depth = BTreeMarketDepth::new(0.1, lot_size);
+
+        depth.update_bid_depth(500.1, 0.001, 0);
+        depth.update_bid_depth(499.3, 0.005, 0);
+        depth.update_bid_depth(499.5, 0.002, 0);
+        depth.update_bid_depth(499.5, 0.0, 0);
+        depth.update_bid_depth(480.0, 0.002, 0);
+        depth.update_ask_depth(500.2, 0.001, 0);
+        depth.update_ask_depth(505.0, 0.003, 0);
+        depth.update_ask_depth(520.0, 0.004, 0);
+
+        assert_eq!(
+            depth.bid_levels().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![5001, 4993, 4800]
+        );
+        assert_eq!(
+            depth.ask_levels().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![5002, 5050, 5200]
+        );
+        assert_eq_qty!(depth.bid_levels().nth(1).unwrap().1, 0.005, lot_size);
+        assert_eq_qty!(depth.ask_levels().nth(1).unwrap().1, 0.003, lot_size);
+
+        assert_eq!(depth.next_bid_tick(5001), 4993);
+        assert_eq!(depth.next_ask_tick(5002), 5050);
+        assert_eq!(depth.next_bid_tick(4800), INVALID_MIN);
+        assert_eq!(depth.next_ask_tick(5200), INVALID_MAX);
+    }
 }
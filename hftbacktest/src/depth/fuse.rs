@@ -329,6 +329,19 @@ impl MarketDepth for FusedHashMapMarketDepth {
             .unwrap_or(&Default::default())
             .qty
     }
+
+    #[inline(always)]
+    fn next_bid_tick(&self, price_tick: i64) -> i64 {
+        depth_below(&self.bid_depth, price_tick, self.low_bid_tick)
+    }
+
+    #[inline(always)]
+    fn next_ask_tick(&self, price_tick: i64) -> i64 {
+        if price_tick >= self.high_ask_tick {
+            return INVALID_MAX;
+        }
+        depth_above(&self.ask_depth, price_tick, self.high_ask_tick)
+    }
 }
 
 impl ApplySnapshot for FusedHashMapMarketDepth {
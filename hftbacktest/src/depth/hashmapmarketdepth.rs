@@ -298,6 +298,19 @@ impl MarketDepth for HashMapMarketDepth {
     fn ask_qty_at_tick(&self, price_tick: i64) -> f64 {
         *self.ask_depth.get(&price_tick).unwrap_or(&0.0)
     }
+
+    #[inline(always)]
+    fn next_bid_tick(&self, price_tick: i64) -> i64 {
+        depth_below(&self.bid_depth, price_tick, self.low_bid_tick)
+    }
+
+    #[inline(always)]
+    fn next_ask_tick(&self, price_tick: i64) -> i64 {
+        if price_tick >= self.high_ask_tick {
+            return INVALID_MAX;
+        }
+        depth_above(&self.ask_depth, price_tick, self.high_ask_tick)
+    }
 }
 
 impl ApplySnapshot for HashMapMarketDepth {
@@ -607,7 +620,14 @@ impl L3MarketDepth for HashMapMarketDepth {
 #[cfg(test)]
 mod tests {
     use crate::{
-        depth::{HashMapMarketDepth, L3MarketDepth, MarketDepth, INVALID_MAX, INVALID_MIN},
+        depth::{
+            HashMapMarketDepth,
+            L2MarketDepth,
+            L3MarketDepth,
+            MarketDepth,
+            INVALID_MAX,
+            INVALID_MIN,
+        },
         types::Side,
     };
 
@@ -831,4 +851,35 @@ mod tests {
         assert_eq_qty!(depth.ask_qty_at_tick(4981), 0.0, lot_size);
         assert_eq_qty!(depth.ask_qty_at_tick(5002), 0.002, lot_size);
     }
+
+    #[test]
+    fn test_l2_levels() {
+        let lot_size = 0.001;
+        let mut depth = HashMapMarketDepth::new(0.1, lot_size);
+
+        depth.update_bid_depth(500.1, 0.001, 0);
+        depth.update_bid_depth(499.3, 0.005, 0);
+        depth.update_bid_depth(499.5, 0.002, 0);
+        depth.update_bid_depth(499.5, 0.0, 0);
+        depth.update_bid_depth(480.0, 0.002, 0);
+        depth.update_ask_depth(500.2, 0.001, 0);
+        depth.update_ask_depth(505.0, 0.003, 0);
+        depth.update_ask_depth(520.0, 0.004, 0);
+
+        assert_eq!(
+            depth.bid_levels().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![5001, 4993, 4800]
+        );
+        assert_eq!(
+            depth.ask_levels().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![5002, 5050, 5200]
+        );
+        assert_eq_qty!(depth.bid_levels().nth(1).unwrap().1, 0.005, lot_size);
+        assert_eq_qty!(depth.ask_levels().nth(1).unwrap().1, 0.003, lot_size);
+
+        assert_eq!(depth.next_bid_tick(5001), 4993);
+        assert_eq!(depth.next_ask_tick(5002), 5050);
+        assert_eq!(depth.next_bid_tick(4800), INVALID_MIN);
+        assert_eq!(depth.next_ask_tick(5200), INVALID_MAX);
+    }
 }
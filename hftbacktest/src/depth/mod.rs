@@ -28,7 +28,7 @@ pub const INVALID_MIN: i64 = i64::MIN;
 pub const INVALID_MAX: i64 = i64::MAX;
 
 /// Provides MarketDepth interface.
-pub trait MarketDepth {
+pub trait MarketDepth: Send {
     /// Returns the best bid price.
     /// If there is no best bid, it returns [`f64::NAN`].
     fn best_bid(&self) -> f64;
@@ -56,6 +56,78 @@ pub trait MarketDepth {
 
     /// Returns the quantity at the ask market depth for a given price in ticks.
     fn ask_qty_at_tick(&self, price_tick: i64) -> f64;
+
+    /// Returns the price in ticks of the nearest bid level below the given price in ticks that has
+    /// quantity. If there is no such level, it returns [`INVALID_MIN`].
+    fn next_bid_tick(&self, price_tick: i64) -> i64;
+
+    /// Returns the price in ticks of the nearest ask level above the given price in ticks that has
+    /// quantity. If there is no such level, it returns [`INVALID_MAX`].
+    fn next_ask_tick(&self, price_tick: i64) -> i64;
+
+    /// Returns an iterator over the bid levels that have quantity, from the best bid downward,
+    /// yielding the price in ticks and the quantity of each level.
+    fn bid_levels(&self) -> DepthLevels<'_, Self>
+    where
+        Self: Sized,
+    {
+        DepthLevels {
+            depth: self,
+            side: Side::Buy,
+            price_tick: self.best_bid_tick(),
+        }
+    }
+
+    /// Returns an iterator over the ask levels that have quantity, from the best ask upward,
+    /// yielding the price in ticks and the quantity of each level.
+    fn ask_levels(&self) -> DepthLevels<'_, Self>
+    where
+        Self: Sized,
+    {
+        DepthLevels {
+            depth: self,
+            side: Side::Sell,
+            price_tick: self.best_ask_tick(),
+        }
+    }
+}
+
+/// An iterator over the levels on one side of the market depth, from the best price outward.
+/// Levels without quantity are skipped however sparse the market depth is.
+///
+/// This is created by [`MarketDepth::bid_levels`] and [`MarketDepth::ask_levels`].
+pub struct DepthLevels<'a, MD> {
+    depth: &'a MD,
+    side: Side,
+    price_tick: i64,
+}
+
+impl<MD> Iterator for DepthLevels<'_, MD>
+where
+    MD: MarketDepth,
+{
+    type Item = (i64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let price_tick = self.price_tick;
+        match self.side {
+            Side::Buy => {
+                if price_tick == INVALID_MIN {
+                    return None;
+                }
+                self.price_tick = self.depth.next_bid_tick(price_tick);
+                Some((price_tick, self.depth.bid_qty_at_tick(price_tick)))
+            }
+            Side::Sell => {
+                if price_tick == INVALID_MAX {
+                    return None;
+                }
+                self.price_tick = self.depth.next_ask_tick(price_tick);
+                Some((price_tick, self.depth.ask_qty_at_tick(price_tick)))
+            }
+            Side::None | Side::Unsupported => unreachable!(),
+        }
+    }
 }
 
 /// Provides Level2-specific market depth functions.
@@ -181,3 +253,31 @@ pub trait L1MarketDepth {
         timestamp: i64,
     ) -> (i64, i64, i64, f64, f64, i64);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::depth::{HashMapMarketDepth, L2MarketDepth, MarketDepth};
+
+    #[test]
+    fn iterate_levels() {
+        let mut depth = HashMapMarketDepth::new(0.1, 0.001);
+        assert_eq!(depth.bid_levels().next(), None);
+        assert_eq!(depth.ask_levels().next(), None);
+
+        depth.update_bid_depth(500.1, 0.001, 0);
+        depth.update_bid_depth(499.3, 0.005, 0);
+        depth.update_bid_depth(480.0, 0.002, 0);
+        depth.update_ask_depth(500.2, 0.001, 0);
+        depth.update_ask_depth(505.0, 0.003, 0);
+        depth.update_ask_depth(520.0, 0.004, 0);
+
+        assert_eq!(
+            depth.bid_levels().collect::<Vec<_>>(),
+            vec![(5001, 0.001), (4993, 0.005), (4800, 0.002)]
+        );
+        assert_eq!(
+            depth.ask_levels().collect::<Vec<_>>(),
+            vec![(5002, 0.001), (5050, 0.003), (5200, 0.004)]
+        );
+    }
+}
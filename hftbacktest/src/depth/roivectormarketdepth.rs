@@ -4,7 +4,7 @@ use super::{ApplySnapshot, L3MarketDepth, L3Order, MarketDepth, INVALID_MAX, INV
 use crate::{
     backtest::{data::Data, BacktestError},
     prelude::{L2MarketDepth, OrderId, Side},
-    types::{Event, BUY_EVENT, SELL_EVENT},
+    types::{Event, BUY_EVENT, DEPTH_SNAPSHOT_EVENT, EXCH_EVENT, LOCAL_EVENT, SELL_EVENT},
 };
 
 /// L2/L3 market depth implementation based on a vector within the range of interest.
@@ -384,6 +384,27 @@ impl MarketDepth for ROIVectorMarketDepth {
             }
         }
     }
+
+    #[inline(always)]
+    fn next_bid_tick(&self, price_tick: i64) -> i64 {
+        // Only the levels within the range of interest are searched.
+        let start = price_tick.saturating_sub(1).min(self.roi_ub);
+        let end = self.low_bid_tick.max(self.roi_lb);
+        (end..=start)
+            .rev()
+            .find(|&t| self.bid_depth[(t - self.roi_lb) as usize] > 0.0)
+            .unwrap_or(INVALID_MIN)
+    }
+
+    #[inline(always)]
+    fn next_ask_tick(&self, price_tick: i64) -> i64 {
+        // Only the levels within the range of interest are searched.
+        let start = price_tick.saturating_add(1).max(self.roi_lb);
+        let end = self.high_ask_tick.min(self.roi_ub);
+        (start..=end)
+            .find(|&t| self.ask_depth[(t - self.roi_lb) as usize] > 0.0)
+            .unwrap_or(INVALID_MAX)
+    }
 }
 
 impl ApplySnapshot for ROIVectorMarketDepth {
@@ -425,7 +446,44 @@ impl ApplySnapshot for ROIVectorMarketDepth {
     }
 
     fn snapshot(&self) -> Vec<Event> {
-        unimplemented!();
+        // Only the levels within the range of interest are held, so the snapshot covers only them.
+        let mut events = Vec::new();
+
+        for (t, &qty) in self.bid_depth.iter().enumerate().rev() {
+            if qty <= 0.0 {
+                continue;
+            }
+            events.push(Event {
+                ev: EXCH_EVENT | LOCAL_EVENT | BUY_EVENT | DEPTH_SNAPSHOT_EVENT,
+                // todo: it's not a problem now, but it would be better to have valid timestamps.
+                exch_ts: 0,
+                local_ts: 0,
+                px: (t as i64 + self.roi_lb) as f64 * self.tick_size,
+                qty,
+                order_id: 0,
+                ival: 0,
+                fval: 0.0,
+            });
+        }
+
+        for (t, &qty) in self.ask_depth.iter().enumerate() {
+            if qty <= 0.0 {
+                continue;
+            }
+            events.push(Event {
+                ev: EXCH_EVENT | LOCAL_EVENT | SELL_EVENT | DEPTH_SNAPSHOT_EVENT,
+                // todo: it's not a problem now, but it would be better to have valid timestamps.
+                exch_ts: 0,
+                local_ts: 0,
+                px: (t as i64 + self.roi_lb) as f64 * self.tick_size,
+                qty,
+                order_id: 0,
+                ival: 0,
+                fval: 0.0,
+            });
+        }
+
+        events
     }
 }
 
@@ -730,7 +788,14 @@ impl L3MarketDepth for ROIVectorMarketDepth {
 #[cfg(test)]
 mod tests {
     use crate::{
-        depth::{L3MarketDepth, MarketDepth, ROIVectorMarketDepth, INVALID_MAX, INVALID_MIN},
+        depth::{
+            L2MarketDepth,
+            L3MarketDepth,
+            MarketDepth,
+            ROIVectorMarketDepth,
+            INVALID_MAX,
+            INVALID_MIN,
+        },
         types::Side,
     };
 
@@ -954,4 +1019,36 @@ mod tests {
         assert_eq_qty!(depth.ask_qty_at_tick(4981), 0.0, lot_size);
         assert_eq_qty!(depth.ask_qty_at_tick(5002), 0.002, lot_size);
     }
+
+    #[test]
+    fn test_l2_levels() {
+        let lot_size = 0.001;
+        let mut depth = ROIVectorMarketDepth::new(0.1, lot_size, 490.0, 510.0);
+
+        depth.update_bid_depth(500.1, 0.001, 0);
+        depth.update_bid_depth(499.3, 0.005, 0);
+        depth.update_bid_depth(499.5, 0.002, 0);
+        depth.update_bid_depth(499.5, 0.0, 0);
+        depth.update_bid_depth(480.0, 0.002, 0);
+        depth.update_ask_depth(500.2, 0.001, 0);
+        depth.update_ask_depth(505.0, 0.003, 0);
+        depth.update_ask_depth(520.0, 0.004, 0);
+
+        // The levels outside the range of interest are not iterated.
+        assert_eq!(
+            depth.bid_levels().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![5001, 4993]
+        );
+        assert_eq!(
+            depth.ask_levels().map(|(t, _)| t).collect::<Vec<_>>(),
+            vec![5002, 5050]
+        );
+        assert_eq_qty!(depth.bid_levels().nth(1).unwrap().1, 0.005, lot_size);
+        assert_eq_qty!(depth.ask_levels().nth(1).unwrap().1, 0.003, lot_size);
+
+        assert_eq!(depth.next_bid_tick(5001), 4993);
+        assert_eq!(depth.next_ask_tick(5002), 5050);
+        assert_eq!(depth.next_bid_tick(4993), INVALID_MIN);
+        assert_eq!(depth.next_ask_tick(5050), INVALID_MAX);
+    }
 }
@@ -27,11 +27,17 @@ use crate::{
         Order,
         OrderId,
         OrderRequest,
+        PositionSide,
+        RejectReason,
         Request,
         Side,
         StateValues,
         Status,
         TimeInForce,
+        TrailingUnit,
+        Trigger,
+        TriggerSource,
+        Unsupported,
         WaitOrderResponse,
         LOCAL_ASK_DEPTH_EVENT,
         LOCAL_BID_DEPTH_EVENT,
@@ -39,6 +45,8 @@ use crate::{
         LOCAL_SELL_TRADE_EVENT,
     },
 };
+#[cfg(feature = "backtest")]
+use crate::live::OrderLatencyRecorder;
 
 #[derive(Error, Debug)]
 pub enum BotError {
@@ -54,10 +62,18 @@ pub enum BotError {
     Timeout,
     #[error("Interrupted")]
     Interrupted,
+    #[error("Unsupported")]
+    Unsupported,
     #[error("Custom: {0}")]
     Custom(String),
 }
 
+impl From<Unsupported> for BotError {
+    fn from(_: Unsupported) -> Self {
+        BotError::Unsupported
+    }
+}
+
 pub type ErrorHandler = Box<dyn Fn(ErrorEvent) -> Result<(), BotError>>;
 pub type OrderRecvHook = Box<dyn Fn(&Order, &Order) -> Result<(), BotError>>;
 
@@ -78,6 +94,8 @@ pub struct LiveBotBuilder<MD> {
     order_hook: Option<OrderRecvHook>,
     depth_builder: Option<DepthBuilder<MD>>,
     last_trades_capacity: usize,
+    #[cfg(feature = "backtest")]
+    latency_recorder: Option<OrderLatencyRecorder>,
 }
 
 impl<MD> LiveBotBuilder<MD> {
@@ -132,6 +150,17 @@ impl<MD> LiveBotBuilder<MD> {
         }
     }
 
+    /// Sets the recorder that records the order latency of every acknowledged request, which can
+    /// be replayed in backtests through
+    /// [`IntpOrderLatency`](crate::backtest::models::IntpOrderLatency).
+    #[cfg(feature = "backtest")]
+    pub fn order_latency_recorder(self, recorder: OrderLatencyRecorder) -> Self {
+        Self {
+            latency_recorder: Some(recorder),
+            ..self
+        }
+    }
+
     /// Sets [`MarketDepth`] build function.
     pub fn depth<Builder>(self, builder: Builder) -> Self
     where
@@ -244,6 +273,8 @@ impl<MD> LiveBotBuilder<MD> {
             order_hook: self.order_hook,
             last_feed_latency,
             last_order_latency,
+            #[cfg(feature = "backtest")]
+            latency_recorder: self.latency_recorder,
         })
     }
 }
@@ -275,6 +306,8 @@ pub struct LiveBot<MD> {
     last_feed_latency: Vec<Option<(i64, i64)>>,
     last_order_latency: Vec<Option<(i64, i64, i64)>>,
     state: Vec<StateValues>,
+    #[cfg(feature = "backtest")]
+    latency_recorder: Option<OrderLatencyRecorder>,
 }
 
 impl<MD> LiveBot<MD>
@@ -289,6 +322,8 @@ where
             order_hook: None,
             depth_builder: None,
             last_trades_capacity: 0,
+            #[cfg(feature = "backtest")]
+            latency_recorder: None,
         }
     }
 
@@ -356,10 +391,11 @@ where
                     } if wait_order_id == order.order_id && wait_order_asset_no == asset_no => true,
                     _ => false,
                 };
+                let resp_timestamp = Utc::now().timestamp_nanos_opt().unwrap();
                 *unsafe { self.last_order_latency.get_unchecked_mut(asset_no) } = Some((
                     order.local_timestamp,
                     order.exch_timestamp,
-                    Utc::now().timestamp_nanos_opt().unwrap(),
+                    resp_timestamp,
                 ));
                 match self
                     .orders
@@ -372,6 +408,12 @@ where
                         if let Some(hook) = self.order_hook.as_mut() {
                             hook(ex_order, &order)?;
                         }
+                        #[cfg(feature = "backtest")]
+                        if let Some(recorder) = self.latency_recorder.as_mut() {
+                            recorder
+                                .record(asset_no, ex_order, &order, resp_timestamp)
+                                .map_err(|error| BotError::Custom(error.to_string()))?;
+                        }
                         if order.exch_timestamp >= ex_order.exch_timestamp {
                             if ex_order.status == Status::Canceled
                                 || ex_order.status == Status::Expired
@@ -391,12 +433,25 @@ where
                     return Ok(true);
                 }
             }
-            LiveEvent::Position { symbol, qty } => {
+            LiveEvent::Position {
+                symbol,
+                qty,
+                position_side,
+            } => {
                 let Some(&asset_no) = self.asset_name_to_no.get(&symbol) else {
                     return Ok(false);
                 };
 
-                unsafe { self.state.get_unchecked_mut(asset_no) }.position = qty;
+                let state = unsafe { self.state.get_unchecked_mut(asset_no) };
+                match position_side {
+                    PositionSide::Both | PositionSide::Unsupported => state.position = qty,
+                    PositionSide::Long => state.long_position = qty,
+                    PositionSide::Short => state.short_position = qty,
+                }
+                if matches!(position_side, PositionSide::Long | PositionSide::Short) {
+                    // In hedge mode, the net position is the sum of both legs.
+                    state.position = state.long_position + state.short_position;
+                }
             }
             LiveEvent::Error(error) => {
                 if let Some(handler) = self.error_handler.as_mut() {
@@ -452,6 +507,11 @@ where
         order_type: OrdType,
         wait: bool,
         side: Side,
+        trigger: Option<&Trigger>,
+        expire_timestamp: i64,
+        display_qty: f64,
+        reduce_only: bool,
+        position_side: PositionSide,
     ) -> Result<bool, BotError> {
         let orders = self
             .orders
@@ -463,7 +523,7 @@ where
         let (_name, asset) = self.assets.get(asset_no).unwrap();
         let symbol = asset.symbol.clone();
         let tick_size = asset.tick_size;
-        let order = Order {
+        let mut order = Order {
             order_id,
             price_tick: (price / tick_size).round() as i64,
             qty,
@@ -481,7 +541,20 @@ where
             // Invalid information
             q: Box::new(()),
             maker: false,
+            reject_reason: RejectReason::None,
+            trigger_price_tick: 0,
+            trailing_offset: 0.0,
+            trailing_unit: TrailingUnit::Ticks,
+            trigger_source: TriggerSource::LastPrice,
+            triggered: false,
+            expire_timestamp,
+            display_qty,
+            reduce_only,
+            position_side,
         };
+        if let Some(trigger) = trigger {
+            order.set_trigger(trigger);
+        }
         let order_id = order.order_id;
         orders.insert(order_id, order.clone());
 
@@ -572,6 +645,11 @@ where
             order_type,
             wait,
             Side::Buy,
+            None,
+            0,
+            f64::INFINITY,
+            false,
+            PositionSide::Both,
         )
     }
 
@@ -595,6 +673,11 @@ where
             order_type,
             wait,
             Side::Sell,
+            None,
+            0,
+            f64::INFINITY,
+            false,
+            PositionSide::Both,
         )
     }
 
@@ -613,6 +696,11 @@ where
             order.order_type,
             wait,
             order.side,
+            order.trigger.as_ref(),
+            order.expire_timestamp,
+            order.display_qty.unwrap_or(f64::INFINITY),
+            order.reduce_only,
+            order.position_side,
         )
     }
 
@@ -701,6 +789,12 @@ where
     }
 
     fn close(&mut self) -> Result<(), Self::Error> {
+        #[cfg(feature = "backtest")]
+        if let Some(recorder) = self.latency_recorder.as_mut() {
+            recorder
+                .flush()
+                .map_err(|error| BotError::Custom(error.to_string()))?;
+        }
         Ok(())
     }
 
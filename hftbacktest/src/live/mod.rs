@@ -1,12 +1,16 @@
 use std::time::Duration;
 
 pub use bot::{BotError, LiveBot, LiveBotBuilder};
+#[cfg(feature = "backtest")]
+pub use order_latency::OrderLatencyRecorder;
 pub use recorder::LoggingRecorder;
 
 use crate::{prelude::Request, types::LiveEvent};
 
 mod bot;
 pub mod ipc;
+#[cfg(feature = "backtest")]
+mod order_latency;
 mod recorder;
 
 /// Provides asset information for internal use.
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Error, ErrorKind},
+    mem,
+    path::{Path, PathBuf},
+};
+
+use chrono::DateTime;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{
+    backtest::{
+        data::{read_npz_file, write_npy},
+        models::OrderLatencyRow,
+    },
+    types::{Order, Status},
+};
+
+const ONE_DAY: i64 = 86_400_000_000_000;
+
+/// Records the order latency that [`LiveBot`](crate::live::LiveBot) experiences, in the
+/// [`OrderLatencyRow`] layout that [`IntpOrderLatency`](crate::backtest::models::IntpOrderLatency)
+/// reads, so that backtests can replay the order latency measured in live trading.
+///
+/// A row is recorded for every response that acknowledges a new, cancel, or modify request. The
+/// rows are rotated by the UTC day of the request timestamp and are written to
+/// `{prefix}{asset_no}_{yyyymmdd}.npz` under the directory, with the rows stored under the name
+/// `data`. If the file already exists, as when the bot restarts within the same day, the existing
+/// rows are kept and the new rows are merged into them.
+///
+/// A response whose exchange timestamp is zero or negative is recorded as is, since
+/// [`IntpOrderLatency`](crate::backtest::models::IntpOrderLatency) treats it as a rejection. Other
+/// rows with a negative latency, which can only come from the clock offset between the local and
+/// the exchange, are discarded.
+///
+/// **Example**
+/// ```no_run
+/// use hftbacktest::{live::{LiveBot, OrderLatencyRecorder}, prelude::HashMapMarketDepth};
+///
+/// let mut hbt = LiveBot::builder()
+///     .add("connector_name", "symbol", 0.1, 0.001)
+///     .depth(|asset| HashMapMarketDepth::new(asset.tick_size, asset.lot_size))
+///     .order_latency_recorder(
+///         OrderLatencyRecorder::new("latency", "order_latency_").max_latency(1_000_000_000),
+///     )
+///     .build()
+///     .unwrap();
+/// ```
+pub struct OrderLatencyRecorder {
+    dir: PathBuf,
+    prefix: String,
+    max_latency: Option<i64>,
+    // The day and the rows being recorded for each asset.
+    rows: HashMap<usize, (i64, Vec<OrderLatencyRow>)>,
+}
+
+impl OrderLatencyRecorder {
+    /// Constructs an `OrderLatencyRecorder` that writes the files under the directory, with their
+    /// names starting with the prefix.
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.to_string(),
+            max_latency: None,
+            rows: HashMap::new(),
+        }
+    }
+
+    /// Discards a row as an outlier if its entry or response latency exceeds `max_latency` in
+    /// nanoseconds. For a rejection, the round-trip latency is checked instead. By default, no
+    /// outliers are discarded.
+    pub fn max_latency(self, max_latency: i64) -> Self {
+        Self {
+            max_latency: Some(max_latency),
+            ..self
+        }
+    }
+
+    /// Records the order latency if the response acknowledges the request. `req` is the order as
+    /// it was requested, `resp` is the order in the response, and `resp_timestamp` is the local
+    /// timestamp at which the response is received.
+    ///
+    /// When a request falls on a different day than the rows being recorded for the asset, the
+    /// previous day's rows are written first.
+    pub fn record(
+        &mut self,
+        asset_no: usize,
+        req: &Order,
+        resp: &Order,
+        resp_timestamp: i64,
+    ) -> Result<(), Error> {
+        let requested = matches!(req.req, Status::New | Status::Canceled | Status::Replaced);
+        if !requested || resp.req != Status::None {
+            return Ok(());
+        }
+
+        let req_ts = req.local_timestamp;
+        let exch_ts = resp.exch_timestamp.max(0);
+        let latencies = if exch_ts > 0 {
+            [exch_ts - req_ts, resp_timestamp - exch_ts]
+        } else {
+            [0, resp_timestamp - req_ts]
+        };
+        if latencies.iter().any(|latency| *latency < 0) {
+            return Ok(());
+        }
+        if let Some(max_latency) = self.max_latency {
+            if latencies.iter().any(|latency| *latency > max_latency) {
+                return Ok(());
+            }
+        }
+
+        let day = req_ts.div_euclid(ONE_DAY);
+        let (rows_day, rows) = self
+            .rows
+            .entry(asset_no)
+            .or_insert_with(|| (day, Vec::new()));
+        if *rows_day != day {
+            let prev_day = mem::replace(rows_day, day);
+            let mut prev_rows = mem::take(rows);
+            write_rows(&self.dir, &self.prefix, asset_no, prev_day, &mut prev_rows)?;
+        }
+        rows.push(OrderLatencyRow {
+            req_ts,
+            exch_ts,
+            resp_ts: resp_timestamp,
+            _padding: 0,
+        });
+        Ok(())
+    }
+
+    /// Writes all recorded rows that have not been written yet.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        for (asset_no, (day, rows)) in self.rows.iter_mut() {
+            write_rows(&self.dir, &self.prefix, *asset_no, *day, rows)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_rows(
+    dir: &Path,
+    prefix: &str,
+    asset_no: usize,
+    day: i64,
+    rows: &mut Vec<OrderLatencyRow>,
+) -> Result<(), Error> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let date = DateTime::from_timestamp_nanos(day * ONE_DAY).format("%Y%m%d");
+    let file_path = dir.join(format!("{prefix}{asset_no}_{date}.npz"));
+
+    let mut data = Vec::new();
+    if file_path.exists() {
+        let file_path = file_path
+            .to_str()
+            .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "non UTF-8 path"))?;
+        let existing = read_npz_file::<OrderLatencyRow>(file_path, "data")?;
+        for row_num in 0..existing.len() {
+            data.push(existing[row_num].clone());
+        }
+    }
+    data.append(rows);
+    // Responses can arrive out of request order, but IntpOrderLatency expects the rows in
+    // chronological order.
+    data.sort_by_key(|row| row.req_ts);
+
+    let mut zip = ZipWriter::new(File::create(&file_path)?);
+    zip.start_file(
+        "data.npy",
+        SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::DEFLATE)
+            .compression_level(Some(9)),
+    )?;
+    write_npy(&mut zip, &data)?;
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        fs,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use super::{OrderLatencyRecorder, ONE_DAY};
+    use crate::{
+        backtest::{data::read_npz_file, models::OrderLatencyRow},
+        types::{OrdType, Order, Side, Status, TimeInForce},
+    };
+
+    // 2023-12-09 in nanoseconds.
+    const DAY: i64 = 19_700 * ONE_DAY;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("{name}_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Returns a new order request sent at `req_ts` and the response that acknowledges it with
+    /// the exchange timestamp.
+    fn new_order(req_ts: i64, exch_ts: i64) -> (Order, Order) {
+        let mut req = Order::new(1, 100, 0.1, 1.0, Side::Buy, OrdType::Limit, TimeInForce::GTC);
+        req.req = Status::New;
+        req.local_timestamp = req_ts;
+        let mut resp = req.clone();
+        resp.req = Status::None;
+        resp.status = Status::New;
+        resp.exch_timestamp = exch_ts;
+        (req, resp)
+    }
+
+    fn record(recorder: &mut OrderLatencyRecorder, req_ts: i64, exch_ts: i64, resp_ts: i64) {
+        let (req, resp) = new_order(req_ts, exch_ts);
+        recorder.record(0, &req, &resp, resp_ts).unwrap();
+    }
+
+    fn read_rows(file_path: &Path) -> Vec<(i64, i64, i64)> {
+        let data = read_npz_file::<OrderLatencyRow>(file_path.to_str().unwrap(), "data").unwrap();
+        (0..data.len())
+            .map(|row_num| {
+                let row = &data[row_num];
+                (row.req_ts, row.exch_ts, row.resp_ts)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotate_by_day() {
+        let dir = temp_dir("rotate_order_latency");
+        let mut recorder = OrderLatencyRecorder::new(&dir, "latency_");
+        record(&mut recorder, DAY + 100, DAY + 200, DAY + 300);
+        record(&mut recorder, DAY + ONE_DAY - 100, DAY + ONE_DAY + 100, DAY + ONE_DAY + 200);
+        assert!(!dir.join("latency_0_20231209.npz").exists());
+
+        // The rows are rotated by the day of the request timestamp, not the response timestamp.
+        record(&mut recorder, DAY + ONE_DAY + 100, DAY + ONE_DAY + 200, DAY + ONE_DAY + 300);
+        assert_eq!(
+            read_rows(&dir.join("latency_0_20231209.npz")),
+            vec![
+                (DAY + 100, DAY + 200, DAY + 300),
+                (DAY + ONE_DAY - 100, DAY + ONE_DAY + 100, DAY + ONE_DAY + 200),
+            ]
+        );
+        assert!(!dir.join("latency_0_20231210.npz").exists());
+
+        recorder.flush().unwrap();
+        assert_eq!(
+            read_rows(&dir.join("latency_0_20231210.npz")),
+            vec![(DAY + ONE_DAY + 100, DAY + ONE_DAY + 200, DAY + ONE_DAY + 300)]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_into_existing_file() {
+        let dir = temp_dir("merge_order_latency");
+        let file_path = dir.join("latency_0_20231209.npz");
+        let mut recorder = OrderLatencyRecorder::new(&dir, "latency_");
+        record(&mut recorder, DAY + 200, DAY + 300, DAY + 400);
+        recorder.flush().unwrap();
+        // Flushing again doesn't write the same rows twice.
+        recorder.flush().unwrap();
+        assert_eq!(read_rows(&file_path), vec![(DAY + 200, DAY + 300, DAY + 400)]);
+
+        // As when the bot restarts within the same day, and the responses arrive out of request
+        // order.
+        let mut recorder = OrderLatencyRecorder::new(&dir, "latency_");
+        record(&mut recorder, DAY + 500, DAY + 600, DAY + 700);
+        record(&mut recorder, DAY + 100, DAY + 800, DAY + 900);
+        recorder.flush().unwrap();
+        assert_eq!(
+            read_rows(&file_path),
+            vec![
+                (DAY + 100, DAY + 800, DAY + 900),
+                (DAY + 200, DAY + 300, DAY + 400),
+                (DAY + 500, DAY + 600, DAY + 700),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn discard_invalid_latency() {
+        let dir = temp_dir("discard_order_latency");
+        let mut recorder = OrderLatencyRecorder::new(&dir, "latency_").max_latency(1_000);
+        // The exchange timestamp precedes the request.
+        record(&mut recorder, DAY + 100, DAY + 50, DAY + 200);
+        // The response is received before the exchange timestamp.
+        record(&mut recorder, DAY + 200, DAY + 400, DAY + 300);
+        // The entry latency exceeds the maximum latency.
+        record(&mut recorder, DAY + 300, DAY + 1_400, DAY + 1_500);
+        // The round-trip latency of the rejection exceeds the maximum latency.
+        record(&mut recorder, DAY + 400, 0, DAY + 1_500);
+        // The rejections within the maximum latency are recorded with the exchange timestamp of
+        // zero.
+        record(&mut recorder, DAY + 500, 0, DAY + 900);
+        record(&mut recorder, DAY + 600, -1, DAY + 900);
+        record(&mut recorder, DAY + 700, DAY + 800, DAY + 900);
+
+        // Only the responses that acknowledge a request are recorded.
+        let (mut req, resp) = new_order(DAY + 800, DAY + 900);
+        req.req = Status::None;
+        recorder.record(0, &req, &resp, DAY + 1_000).unwrap();
+        let (req, mut resp) = new_order(DAY + 800, DAY + 900);
+        resp.req = Status::Rejected;
+        recorder.record(0, &req, &resp, DAY + 1_000).unwrap();
+
+        recorder.flush().unwrap();
+        assert_eq!(
+            read_rows(&dir.join("latency_0_20231209.npz")),
+            vec![
+                (DAY + 500, 0, DAY + 900),
+                (DAY + 600, 0, DAY + 900),
+                (DAY + 700, DAY + 800, DAY + 900),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
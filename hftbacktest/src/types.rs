@@ -141,7 +141,11 @@ pub enum LiveEvent {
     // FeedBatch { symbol: String, events: Vec<Event> },
     Feed { symbol: String, event: Event },
     Order { symbol: String, order: Order },
-    Position { symbol: String, qty: f64 },
+    Position {
+        symbol: String,
+        qty: f64,
+        position_side: PositionSide,
+    },
     Error(LiveError),
 }
 
@@ -182,6 +186,13 @@ pub const MODIFY_ORDER_EVENT: u64 = 12;
 /// Indicates that an order in the order book has been filled.
 pub const FILL_EVENT: u64 = 13;
 
+/// Indicates that the funding of a perpetual futures contract is settled. The funding rate is in
+/// `fval` and the mark price is in `px`.
+pub const FUNDING_EVENT: u64 = 20;
+
+/// Indicates that the mark price is updated. The mark price is in `px`.
+pub const MARK_PRICE_EVENT: u64 = 21;
+
 /// Indicates that it is a valid event to be handled by the exchange processor at the exchange
 /// timestamp.
 pub const EXCH_EVENT: u64 = 1 << 31;
@@ -297,11 +308,26 @@ pub const EXCH_MODIFY_ORDER_EVENT: u64 = EXCH_EVENT | MODIFY_ORDER_EVENT;
 /// Represents a combination of [`EXCH_EVENT`] and [`FILL_EVENT`].
 pub const EXCH_FILL_EVENT: u64 = EXCH_EVENT | FILL_EVENT;
 
+/// Represents a combination of [`LOCAL_EVENT`] and [`FUNDING_EVENT`].
+pub const LOCAL_FUNDING_EVENT: u64 = LOCAL_EVENT | FUNDING_EVENT;
+
+/// Represents a combination of [`EXCH_EVENT`] and [`FUNDING_EVENT`].
+pub const EXCH_FUNDING_EVENT: u64 = EXCH_EVENT | FUNDING_EVENT;
+
+/// Represents a combination of [`LOCAL_EVENT`] and [`MARK_PRICE_EVENT`].
+pub const LOCAL_MARK_PRICE_EVENT: u64 = LOCAL_EVENT | MARK_PRICE_EVENT;
+
+/// Represents a combination of [`EXCH_EVENT`] and [`MARK_PRICE_EVENT`].
+pub const EXCH_MARK_PRICE_EVENT: u64 = EXCH_EVENT | MARK_PRICE_EVENT;
+
 /// Indicates that one should continue until the end of the data.
 pub const UNTIL_END_OF_DATA: i64 = i64::MAX;
 
 pub type OrderId = u64;
 
+/// The order ID of the orders issued by the exchange to liquidate the position.
+pub const LIQUIDATION_ORDER_ID: OrderId = OrderId::MAX;
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum WaitOrderResponse {
     None,
@@ -390,6 +416,33 @@ impl AsRef<str> for Side {
     }
 }
 
+/// The position that an order opens or closes. In hedge mode, the long and short positions are held
+/// separately, so that an order specifies which of them it trades.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Decode, Encode)]
+#[repr(u8)]
+pub enum PositionSide {
+    /// The single net position in one-way mode.
+    Both = 0,
+    /// The long position in hedge mode, which a buy order opens and a sell order closes.
+    Long = 1,
+    /// The short position in hedge mode, which a sell order opens and a buy order closes.
+    Short = 2,
+    /// This occurs when the [`Connector`](`crate::connector::Connector`) receives a position side
+    /// value that does not have a corresponding enum value.
+    Unsupported = 255,
+}
+
+impl AsRef<str> for PositionSide {
+    fn as_ref(&self) -> &'static str {
+        match self {
+            PositionSide::Both => "BOTH",
+            PositionSide::Long => "LONG",
+            PositionSide::Short => "SHORT",
+            PositionSide::Unsupported => panic!("PositionSide::Unsupported"),
+        }
+    }
+}
+
 /// Order status
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Decode, Encode)]
 #[repr(u8)]
@@ -401,11 +454,49 @@ pub enum Status {
     Canceled = 4,
     PartiallyFilled = 5,
     Rejected = 6,
+    /// Used as a request status to modify the price and quantity of an opened order.
+    Replaced = 7,
+    /// The order issued by the exchange to liquidate the position when the maintenance margin is
+    /// breached. It is executed as soon as it is issued.
+    Liquidated = 8,
     /// This occurs when the [`Connector`](`crate::connector::Connector`) receives an order status
     /// value that does not have a corresponding enum value.
     Unsupported = 255,
 }
 
+/// The reason why the exchange rejected a request, which is set along with [`Status::Rejected`]
+/// in the request status of the order response.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Decode, Encode)]
+#[repr(u8)]
+pub enum RejectReason {
+    None = 0,
+    /// The request exceeded the request rate limit.
+    RateLimited = 1,
+    /// The new order exceeded the maximum number of open orders.
+    MaxOpenOrders = 2,
+    /// The margin available is insufficient to open the new order.
+    InsufficientMargin = 3,
+    /// The trigger condition of the conditional order is invalid, or the order would trigger
+    /// immediately.
+    InvalidTrigger = 4,
+    /// The reduce-only order would increase the position.
+    ReduceOnly = 5,
+    /// The price is out of the allowed range or is not a multiple of the tick size.
+    PriceFilter = 6,
+    /// The quantity is out of the allowed range or is not a multiple of the lot size.
+    LotSize = 7,
+    /// The notional value of the order is less than the minimum notional.
+    MinNotional = 8,
+    /// The price deviates too far from the current price.
+    PercentPrice = 9,
+    /// The exchange does not hold the order to cancel or modify, either because the order has
+    /// already been filled, canceled, or expired, or because the new order has not arrived yet.
+    UnknownOrder = 10,
+    /// The modified quantity does not exceed the quantity of the order that has already been
+    /// filled.
+    BelowFilledQty = 11,
+}
+
 /// Time In Force
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Decode, Encode)]
 #[repr(u8)]
@@ -418,6 +509,8 @@ pub enum TimeInForce {
     FOK = 2,
     /// Immediate or Cancel
     IOC = 3,
+    /// Good 'Til Date, which is canceled by the exchange once its expire timestamp is reached.
+    GTD = 4,
     /// This occurs when the [`Connector`](`crate::connector::Connector`) receives a time-in-force
     /// value that does not have a corresponding enum value.
     Unsupported = 255,
@@ -430,6 +523,7 @@ impl AsRef<str> for TimeInForce {
             TimeInForce::GTX => "GTX",
             TimeInForce::FOK => "FOK",
             TimeInForce::IOC => "IOC",
+            TimeInForce::GTD => "GTD",
             TimeInForce::Unsupported => panic!("TimeInForce::Unsupported"),
         }
     }
@@ -441,19 +535,107 @@ impl AsRef<str> for TimeInForce {
 pub enum OrdType {
     Limit = 0,
     Market = 1,
+    /// A market order that is triggered when the price rises to the trigger price for a buy order,
+    /// or falls to it for a sell order.
+    StopMarket = 2,
+    /// A limit order that is triggered in the same way as [`OrdType::StopMarket`].
+    StopLimit = 3,
+    /// A market order that is triggered when the price falls to the trigger price for a buy order,
+    /// or rises to it for a sell order.
+    TakeProfitMarket = 4,
+    /// A limit order that is triggered in the same way as [`OrdType::TakeProfitMarket`].
+    TakeProfitLimit = 5,
+    /// A stop market order whose trigger price trails the best price reached since the order was
+    /// placed by the trailing offset.
+    TrailingStopMarket = 6,
     Unsupported = 255,
 }
 
+impl OrdType {
+    /// Returns `true` if the order is held by the exchange until its trigger condition is met.
+    pub fn is_conditional(&self) -> bool {
+        matches!(
+            self,
+            OrdType::StopMarket
+                | OrdType::StopLimit
+                | OrdType::TakeProfitMarket
+                | OrdType::TakeProfitLimit
+                | OrdType::TrailingStopMarket
+        )
+    }
+}
+
 impl AsRef<str> for OrdType {
     fn as_ref(&self) -> &'static str {
         match self {
             OrdType::Limit => "LIMIT",
             OrdType::Market => "MARKET",
+            OrdType::StopMarket => "STOP_MARKET",
+            OrdType::StopLimit => "STOP",
+            OrdType::TakeProfitMarket => "TAKE_PROFIT_MARKET",
+            OrdType::TakeProfitLimit => "TAKE_PROFIT",
+            OrdType::TrailingStopMarket => "TRAILING_STOP_MARKET",
             OrdType::Unsupported => panic!("OrdType::Unsupported"),
         }
     }
 }
 
+/// The price that the trigger price of a conditional order is compared with.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Decode, Encode)]
+#[repr(u8)]
+pub enum TriggerSource {
+    /// The last traded price.
+    LastPrice = 0,
+    /// The mark price.
+    MarkPrice = 1,
+}
+
+/// The unit of the trailing offset of [`OrdType::TrailingStopMarket`].
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Decode, Encode)]
+#[repr(u8)]
+pub enum TrailingUnit {
+    Ticks = 0,
+    Percent = 1,
+}
+
+/// The trigger condition of a conditional order.
+#[derive(Clone, Copy, PartialEq, Debug, Decode, Encode)]
+pub struct Trigger {
+    /// The trigger price. This is not used by [`OrdType::TrailingStopMarket`], whose trigger price
+    /// is set by the exchange.
+    pub price: f64,
+    /// The trailing offset of [`OrdType::TrailingStopMarket`].
+    pub trailing_offset: f64,
+    pub trailing_unit: TrailingUnit,
+    pub source: TriggerSource,
+}
+
+impl Trigger {
+    /// Constructs a trigger at the given price.
+    pub fn new(price: f64, source: TriggerSource) -> Self {
+        Self {
+            price,
+            trailing_offset: 0.0,
+            trailing_unit: TrailingUnit::Ticks,
+            source,
+        }
+    }
+
+    /// Constructs a trigger of [`OrdType::TrailingStopMarket`] with the given trailing offset.
+    pub fn trailing(
+        trailing_offset: f64,
+        trailing_unit: TrailingUnit,
+        source: TriggerSource,
+    ) -> Self {
+        Self {
+            price: 0.0,
+            trailing_offset,
+            trailing_unit,
+            source,
+        }
+    }
+}
+
 /// Provides cloning of `Box<dyn Any>`, which is utilized in [Order] for the additional data used in
 /// [`QueueModel`](`crate::backtest::models::QueueModel`).
 ///
@@ -518,10 +700,36 @@ pub struct Order {
     /// Request status:
     ///   * [`Status::New`]: Request to open a new order.
     ///   * [`Status::Canceled`]: Request to cancel an opened order.
+    ///   * [`Status::Replaced`]: Request to modify the price and quantity of an opened order.
     pub req: Status,
     pub status: Status,
     pub side: Side,
     pub time_in_force: TimeInForce,
+    /// The reason why the exchange rejected the last request of this order, only available in
+    /// backtesting.
+    pub reject_reason: RejectReason,
+    /// The trigger price in ticks of the conditional order types. For
+    /// [`OrdType::TrailingStopMarket`], it is the current trigger price set by the exchange.
+    pub trigger_price_tick: i64,
+    /// The trailing offset of [`OrdType::TrailingStopMarket`].
+    pub trailing_offset: f64,
+    pub trailing_unit: TrailingUnit,
+    pub trigger_source: TriggerSource,
+    /// Whether the conditional order has been triggered. Once triggered, the order type becomes
+    /// [`OrdType::Market`] or [`OrdType::Limit`].
+    pub triggered: bool,
+    /// The timestamp at which the exchange expires the [`TimeInForce::GTD`] order. It's 0 for the
+    /// other time-in-force.
+    pub expire_timestamp: i64,
+    /// The maximum quantity that the order displays in the order book at a time. An iceberg order
+    /// displays its quantity in slices of this size, and a hidden order with 0 displays none of it.
+    /// It's infinite if the entire quantity is displayed.
+    pub display_qty: f64,
+    /// Whether the order can only reduce the position. The exchange clips its quantity to the
+    /// position, and cancels it if it would increase the position.
+    pub reduce_only: bool,
+    /// The position that the order trades, which is [`PositionSide::Both`] unless in hedge mode.
+    pub position_side: PositionSide,
 }
 
 impl Order {
@@ -552,9 +760,27 @@ impl Order {
             q: Box::new(()),
             maker: false,
             order_type,
+            reject_reason: RejectReason::None,
+            trigger_price_tick: 0,
+            trailing_offset: 0.0,
+            trailing_unit: TrailingUnit::Ticks,
+            trigger_source: TriggerSource::LastPrice,
+            triggered: false,
+            expire_timestamp: 0,
+            display_qty: f64::INFINITY,
+            reduce_only: false,
+            position_side: PositionSide::Both,
         }
     }
 
+    /// Sets the trigger condition of the conditional order.
+    pub fn set_trigger(&mut self, trigger: &Trigger) {
+        self.trigger_price_tick = (trigger.price / self.tick_size).round() as i64;
+        self.trailing_offset = trigger.trailing_offset;
+        self.trailing_unit = trigger.trailing_unit;
+        self.trigger_source = trigger.source;
+    }
+
     /// Returns the order price.
     pub fn price(&self) -> f64 {
         self.price_tick as f64 * self.tick_size
@@ -565,6 +791,39 @@ impl Order {
         self.exec_price_tick as f64 * self.tick_size
     }
 
+    /// Returns the trigger price of the conditional order.
+    pub fn trigger_price(&self) -> f64 {
+        self.trigger_price_tick as f64 * self.tick_size
+    }
+
+    /// Returns the trigger condition if the order is of a conditional order type.
+    pub fn trigger(&self) -> Option<Trigger> {
+        self.order_type.is_conditional().then(|| Trigger {
+            price: self.trigger_price(),
+            trailing_offset: self.trailing_offset,
+            trailing_unit: self.trailing_unit,
+            source: self.trigger_source,
+        })
+    }
+
+    /// Returns `true` if the order doesn't display any of its quantity in the order book.
+    pub fn is_hidden(&self) -> bool {
+        self.display_qty == 0.0
+    }
+
+    /// Returns the quantity that can be executed before the iceberg order displays its next slice,
+    /// which is the unexecuted part of the current slice. For the other orders, including the
+    /// hidden orders, it's the leaves quantity.
+    pub fn slice_qty(&self, lot_size: f64) -> f64 {
+        if self.is_hidden() || !self.display_qty.is_finite() {
+            return self.leaves_qty;
+        }
+        let display_lots = ((self.display_qty / lot_size).round() as i64).max(1);
+        let exec_lots = ((self.qty - self.leaves_qty) / lot_size).round() as i64;
+        let slice_lots = display_lots - exec_lots % display_lots;
+        (slice_lots as f64 * lot_size).min(self.leaves_qty)
+    }
+
     /// Returns whether this order is cancelable.
     pub fn cancellable(&self) -> bool {
         (self.status == Status::New || self.status == Status::PartiallyFilled)
@@ -606,6 +865,16 @@ impl Order {
         self.q = order.q.clone();
         self.maker = order.maker;
         self.order_type = order.order_type;
+        self.reject_reason = order.reject_reason;
+        self.trigger_price_tick = order.trigger_price_tick;
+        self.trailing_offset = order.trailing_offset;
+        self.trailing_unit = order.trailing_unit;
+        self.trigger_source = order.trigger_source;
+        self.triggered = order.triggered;
+        self.expire_timestamp = order.expire_timestamp;
+        self.display_qty = order.display_qty;
+        self.reduce_only = order.reduce_only;
+        self.position_side = order.position_side;
     }
 }
 
@@ -627,6 +896,16 @@ impl Debug for Order {
             .field("order_id", &self.order_id)
             .field("maker", &self.maker)
             .field("order_type", &self.order_type)
+            .field("reject_reason", &self.reject_reason)
+            .field("trigger_price_tick", &self.trigger_price_tick)
+            .field("trailing_offset", &self.trailing_offset)
+            .field("trailing_unit", &self.trailing_unit)
+            .field("trigger_source", &self.trigger_source)
+            .field("triggered", &self.triggered)
+            .field("expire_timestamp", &self.expire_timestamp)
+            .field("display_qty", &self.display_qty)
+            .field("reduce_only", &self.reduce_only)
+            .field("position_side", &self.position_side)
             .finish()
     }
 }
@@ -651,6 +930,16 @@ impl Decode for Order {
             status: Decode::decode(decoder)?,
             side: Decode::decode(decoder)?,
             time_in_force: Decode::decode(decoder)?,
+            reject_reason: Decode::decode(decoder)?,
+            trigger_price_tick: Decode::decode(decoder)?,
+            trailing_offset: Decode::decode(decoder)?,
+            trailing_unit: Decode::decode(decoder)?,
+            trigger_source: Decode::decode(decoder)?,
+            triggered: Decode::decode(decoder)?,
+            expire_timestamp: Decode::decode(decoder)?,
+            display_qty: Decode::decode(decoder)?,
+            reduce_only: Decode::decode(decoder)?,
+            position_side: Decode::decode(decoder)?,
         })
     }
 }
@@ -675,6 +964,16 @@ impl<'de> BorrowDecode<'de> for Order {
             status: Decode::decode(decoder)?,
             side: Decode::decode(decoder)?,
             time_in_force: Decode::decode(decoder)?,
+            reject_reason: Decode::decode(decoder)?,
+            trigger_price_tick: Decode::decode(decoder)?,
+            trailing_offset: Decode::decode(decoder)?,
+            trailing_unit: Decode::decode(decoder)?,
+            trigger_source: Decode::decode(decoder)?,
+            triggered: Decode::decode(decoder)?,
+            expire_timestamp: Decode::decode(decoder)?,
+            display_qty: Decode::decode(decoder)?,
+            reduce_only: Decode::decode(decoder)?,
+            position_side: Decode::decode(decoder)?,
         })
     }
 }
@@ -697,6 +996,16 @@ impl Encode for Order {
         self.status.encode(encoder)?;
         self.side.encode(encoder)?;
         self.time_in_force.encode(encoder)?;
+        self.reject_reason.encode(encoder)?;
+        self.trigger_price_tick.encode(encoder)?;
+        self.trailing_offset.encode(encoder)?;
+        self.trailing_unit.encode(encoder)?;
+        self.trigger_source.encode(encoder)?;
+        self.triggered.encode(encoder)?;
+        self.expire_timestamp.encode(encoder)?;
+        self.display_qty.encode(encoder)?;
+        self.reduce_only.encode(encoder)?;
+        self.position_side.encode(encoder)?;
         Ok(())
     }
 }
@@ -712,10 +1021,10 @@ pub enum Request {
 
 /// Provides state values.
 ///
-/// **Note:** In a live bot, currently only `position` value is delivered correctly, and other
-/// values are invalid.
+/// **Note:** In a live bot, currently only `position`, `long_position` and `short_position` values
+/// are delivered correctly, and other values are invalid.
 #[repr(C)]
-#[derive(PartialEq, Clone, Debug, Default)]
+#[derive(PartialEq, Clone, Debug, Default, Decode, Encode)]
 pub struct StateValues {
     pub position: f64,
     /// Backtest only
@@ -730,6 +1039,14 @@ pub struct StateValues {
     pub trading_volume: f64,
     /// Backtest only
     pub trading_value: f64,
+    /// The cumulative amount of the funding settlements, which is included in `balance`.
+    ///
+    /// Backtest only
+    pub funding: f64,
+    /// The long position in hedge mode, which is included in `position`.
+    pub long_position: f64,
+    /// The short position in hedge mode as a negative quantity, which is included in `position`.
+    pub short_position: f64,
 }
 
 /// Provides errors that can occur in builders.
@@ -747,6 +1064,11 @@ pub enum BuildError {
     Error(#[from] anyhow::Error),
 }
 
+/// Returned by a [`Bot`] operation that the implementor doesn't support.
+#[derive(Error, Clone, Copy, Eq, PartialEq, Debug)]
+#[error("unsupported")]
+pub struct Unsupported;
+
 /// Used to submit an order in a live bot.
 #[derive(Decode, Encode)]
 pub struct OrderRequest {
@@ -756,6 +1078,17 @@ pub struct OrderRequest {
     pub side: Side,
     pub time_in_force: TimeInForce,
     pub order_type: OrdType,
+    /// The trigger condition, which is required for the conditional order types.
+    pub trigger: Option<Trigger>,
+    /// The expire timestamp, which is required for [`TimeInForce::GTD`].
+    pub expire_timestamp: i64,
+    /// The quantity displayed in the order book at a time, which makes the order an iceberg order,
+    /// or a hidden order if it's 0. If `None`, the entire quantity is displayed.
+    pub display_qty: Option<f64>,
+    /// Whether the order can only reduce the position.
+    pub reduce_only: bool,
+    /// The position that the order trades in hedge mode.
+    pub position_side: PositionSide,
 }
 
 /// Provides a bot interface for backtesting and live trading.
@@ -853,7 +1186,9 @@ where
         wait: bool,
     ) -> Result<bool, Self::Error>;
 
-    /// Places an order.
+    /// Places an order. A conditional order type requires the trigger condition in
+    /// [`OrderRequest::trigger`], and [`TimeInForce::GTD`] requires
+    /// [`OrderRequest::expire_timestamp`].
     fn submit_order(
         &mut self,
         asset_no: usize,
@@ -873,6 +1208,30 @@ where
         wait: bool,
     ) -> Result<bool, Self::Error>;
 
+    /// Modifies the price and quantity of the specified order.
+    ///
+    /// * `asset_no` - Asset number at which this command will be executed.
+    /// * `order_id` - Order ID to modify.
+    /// * `price` - New order price.
+    /// * `qty` - New order quantity.
+    /// * `wait` - If true, wait until the order modification response is received.
+    ///
+    /// By default, this returns [`Unsupported`] without sending a request, so an implementor that
+    /// can modify orders needs to override it.
+    fn modify(
+        &mut self,
+        _asset_no: usize,
+        _order_id: OrderId,
+        _price: f64,
+        _qty: f64,
+        _wait: bool,
+    ) -> Result<bool, Self::Error>
+    where
+        Self::Error: From<Unsupported>,
+    {
+        Err(Unsupported.into())
+    }
+
     /// Clears inactive orders from the local orders whose status is neither [`Status::New`] nor
     /// [`Status::PartiallyFilled`].
     fn clear_inactive_orders(&mut self, asset_no: Option<usize>);
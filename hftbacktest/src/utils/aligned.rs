@@ -16,6 +16,9 @@ pub struct AlignedArray<T, const ALIGNMENT: usize> {
     len: usize,
 }
 
+// `AlignedArray` owns its buffer exclusively, just like `Vec`.
+unsafe impl<T: Send, const ALIGNMENT: usize> Send for AlignedArray<T, ALIGNMENT> {}
+
 impl<T, const ALIGNMENT: usize> Drop for AlignedArray<T, ALIGNMENT> {
     #[inline]
     fn drop(&mut self) {
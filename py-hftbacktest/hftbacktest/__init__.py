@@ -25,8 +25,14 @@ from .order import (
     CANCELED,
     GTC,
     GTX,
+    GTD,
     LIMIT,
     MARKET,
+    STOP_MARKET,
+    STOP_LIMIT,
+    TAKE_PROFIT_MARKET,
+    TAKE_PROFIT_LIMIT,
+    TRAILING_STOP_MARKET,
 )
 from .recorder import Recorder
 from .types import (
@@ -41,6 +47,8 @@ from .types import (
     CANCEL_ORDER_EVENT,
     MODIFY_ORDER_EVENT,
     FILL_EVENT,
+    FUNDING_EVENT,
+    MARK_PRICE_EVENT,
     EXCH_EVENT,
     LOCAL_EVENT,
     BUY_EVENT,
@@ -64,6 +72,8 @@ __all__ = (
     'CANCEL_ORDER_EVENT',
     'MODIFY_ORDER_EVENT',
     'FILL_EVENT',
+    'FUNDING_EVENT',
+    'MARK_PRICE_EVENT',
     'EXCH_EVENT',
     'LOCAL_EVENT',
     'EXCH_EVENT',
@@ -85,9 +95,15 @@ __all__ = (
     # Time-In-Force
     'GTC',
     'GTX',
+    'GTD',
 
     'LIMIT',
     'MARKET',
+    'STOP_MARKET',
+    'STOP_LIMIT',
+    'TAKE_PROFIT_MARKET',
+    'TAKE_PROFIT_LIMIT',
+    'TRAILING_STOP_MARKET',
     
     'Recorder'
 )
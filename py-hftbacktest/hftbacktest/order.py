@@ -41,6 +41,45 @@ PARTIALLY_FILLED = 5
 #: REJECTED
 REJECTED = 6
 
+#: REPLACED
+REPLACED = 7
+
+#: LIQUIDATED
+LIQUIDATED = 8
+
+#: The request is not rejected.
+REJECT_REASON_NONE = 0
+
+#: The request exceeded the request rate limit.
+RATE_LIMITED = 1
+
+#: The new order exceeded the maximum number of open orders.
+MAX_OPEN_ORDERS = 2
+
+#: The margin available is insufficient to open the new order.
+INSUFFICIENT_MARGIN = 3
+
+#: The trigger condition of the conditional order is invalid, or the order would trigger immediately.
+INVALID_TRIGGER = 4
+
+#: The reduce-only order would increase the position.
+REDUCE_ONLY = 5
+
+#: The price is out of the allowed range or is not a multiple of the tick size.
+PRICE_FILTER = 6
+
+#: The quantity is out of the allowed range or is not a multiple of the lot size.
+LOT_SIZE = 7
+
+#: The notional value of the order is less than the minimum notional.
+MIN_NOTIONAL = 8
+
+#: The price deviates too far from the current price.
+PERCENT_PRICE = 9
+
+#: The exchange does not hold the order to cancel or modify.
+UNKNOWN_ORDER = 10
+
 #: Good 'till cancel
 GTC = 0
 
@@ -53,12 +92,51 @@ FOK = 2
 #: Immediate or cancel
 IOC = 3
 
+#: Good 'till date, which the exchange expires at the order's expire timestamp
+GTD = 4
+
 #: LIMIT
 LIMIT = 0
 
 #: MARKET
 MARKET = 1
 
+#: A market order triggered when the price rises to the trigger price for a buy order, or falls to it for a sell order.
+STOP_MARKET = 2
+
+#: A limit order triggered in the same way as :const:`STOP_MARKET`.
+STOP_LIMIT = 3
+
+#: A market order triggered when the price falls to the trigger price for a buy order, or rises to it for a sell order.
+TAKE_PROFIT_MARKET = 4
+
+#: A limit order triggered in the same way as :const:`TAKE_PROFIT_MARKET`.
+TAKE_PROFIT_LIMIT = 5
+
+#: A stop market order whose trigger price trails the best price reached since the order was placed.
+TRAILING_STOP_MARKET = 6
+
+#: The trigger price is compared with the last traded price.
+TRIGGER_LAST_PRICE = 0
+
+#: The trigger price is compared with the mark price.
+TRIGGER_MARK_PRICE = 1
+
+#: The trailing offset is in ticks.
+TRAILING_TICKS = 0
+
+#: The trailing offset is in percent.
+TRAILING_PERCENT = 1
+
+#: The single net position in one-way mode.
+POSITION_BOTH = 0
+
+#: The long position in hedge mode.
+POSITION_LONG = 1
+
+#: The short position in hedge mode.
+POSITION_SHORT = 2
+
 
 class Order:
     arr: from_dtype(order_dtype)[:]
@@ -163,6 +241,13 @@ class Order:
 
             * :const:`MARKET`
             * :const:`LIMIT`
+            * :const:`STOP_MARKET`
+            * :const:`STOP_LIMIT`
+            * :const:`TAKE_PROFIT_MARKET`
+            * :const:`TAKE_PROFIT_LIMIT`
+            * :const:`TRAILING_STOP_MARKET`
+
+        Once a conditional order is triggered, its type becomes :const:`MARKET` or :const:`LIMIT`.
         """
         return self.arr[0].order_type
 
@@ -212,8 +297,113 @@ class Order:
             * :const:`GTX`
             * :const:`FOK`
             * :const:`IOC`
+            * :const:`GTD`
         """
         return self.arr[0].time_in_force
 
+    @property
+    def reject_reason(self) -> uint8:
+        """
+        Returns the reason why the exchange rejected the last request of the order. This is only available in
+        backtesting.
+
+            * :const:`REJECT_REASON_NONE`
+            * :const:`RATE_LIMITED`
+            * :const:`MAX_OPEN_ORDERS`
+            * :const:`INSUFFICIENT_MARGIN`
+            * :const:`INVALID_TRIGGER`
+            * :const:`REDUCE_ONLY`
+            * :const:`PRICE_FILTER`
+            * :const:`LOT_SIZE`
+            * :const:`MIN_NOTIONAL`
+            * :const:`PERCENT_PRICE`
+            * :const:`UNKNOWN_ORDER`
+        """
+        return self.arr[0].reject_reason
+
+    @property
+    def trigger_price_tick(self) -> int64:
+        """
+        Returns the trigger price in ticks of the conditional order. For :const:`TRAILING_STOP_MARKET`, it is the
+        current trigger price set by the exchange.
+        """
+        return self.arr[0].trigger_price_tick
+
+    @property
+    def trigger_price(self) -> float64:
+        """
+        Returns the trigger price of the conditional order.
+        """
+        return self.arr[0].trigger_price_tick * self.arr[0].tick_size
+
+    @property
+    def trailing_offset(self) -> float64:
+        """
+        Returns the trailing offset of :const:`TRAILING_STOP_MARKET`, in the unit of :obj:`trailing_unit`.
+        """
+        return self.arr[0].trailing_offset
+
+    @property
+    def trailing_unit(self) -> uint8:
+        """
+        Returns the unit of the trailing offset.
+
+            * :const:`TRAILING_TICKS`
+            * :const:`TRAILING_PERCENT`
+        """
+        return self.arr[0].trailing_unit
+
+    @property
+    def trigger_source(self) -> uint8:
+        """
+        Returns the price that the trigger price is compared with.
+
+            * :const:`TRIGGER_LAST_PRICE`
+            * :const:`TRIGGER_MARK_PRICE`
+        """
+        return self.arr[0].trigger_source
+
+    @property
+    def triggered(self) -> bool:
+        """
+        Returns whether the conditional order has been triggered.
+        """
+        return self.arr[0].triggered
+
+    @property
+    def expire_timestamp(self) -> int64:
+        """
+        Returns the timestamp at which the exchange expires the :const:`GTD` order. It is 0 for the other
+        Time-In-Force.
+        """
+        return self.arr[0].expire_timestamp
+
+    @property
+    def display_qty(self) -> float64:
+        """
+        Returns the maximum quantity that the order displays in the order book at a time. An iceberg order displays
+        slices of this quantity, and a hidden order displays nothing, which is 0. It is infinite if the order displays
+        its entire quantity.
+        """
+        return self.arr[0].display_qty
+
+    @property
+    def reduce_only(self) -> bool:
+        """
+        Returns whether the order can only reduce the position.
+        """
+        return self.arr[0].reduce_only
+
+    @property
+    def position_side(self) -> uint8:
+        """
+        Returns the position that the order trades.
+
+            * :const:`POSITION_BOTH`
+            * :const:`POSITION_LONG`
+            * :const:`POSITION_SHORT`
+        """
+        return self.arr[0].position_side
+
 
 Order_ = jitclass(Order)
@@ -37,5 +37,17 @@ class StateValues:
     def trading_value(self) -> float64:
         return self.arr[0].trading_value
 
+    @property
+    def funding(self) -> float64:
+        return self.arr[0].funding
+
+    @property
+    def long_position(self) -> float64:
+        return self.arr[0].long_position
+
+    @property
+    def short_position(self) -> float64:
+        return self.arr[0].short_position
+
 
 StateValues_ = jitclass(StateValues)
@@ -32,6 +32,13 @@ MODIFY_ORDER_EVENT = 12
 #: Indicates that an order in the order book has been filled.
 FILL_EVENT = 13
 
+#: Indicates that the funding of a perpetual futures contract is settled. The funding rate is in ``fval`` and the mark
+#: price is in ``px``.
+FUNDING_EVENT = 20
+
+#: Indicates that the mark price is updated. The mark price is in ``px``.
+MARK_PRICE_EVENT = 21
+
 # todo: fix WAIT_ORDER_RESPONSE flags.
 WAIT_ORDER_RESPONSE_NONE = -1
 WAIT_ORDER_RESPONSE_ANY = -2
@@ -66,7 +73,10 @@ state_values_dtype = np.dtype(
         ('fee', 'f8'),
         ('num_trades', 'i8'),
         ('trading_volume', 'f8'),
-        ('trading_value', 'f8')
+        ('trading_value', 'f8'),
+        ('funding', 'f8'),
+        ('long_position', 'f8'),
+        ('short_position', 'f8')
     ],
     align=True
 )
@@ -105,7 +115,17 @@ order_dtype = np.dtype(
         ('req', 'u1'),
         ('status', 'u1'),
         ('side', 'i1'),
-        ('time_in_force', 'u1')
+        ('time_in_force', 'u1'),
+        ('reject_reason', 'u1'),
+        ('trigger_price_tick', 'i8'),
+        ('trailing_offset', 'f8'),
+        ('trailing_unit', 'u1'),
+        ('trigger_source', 'u1'),
+        ('triggered', 'bool'),
+        ('expire_timestamp', 'i8'),
+        ('display_qty', 'f8'),
+        ('reduce_only', 'bool'),
+        ('position_side', 'u1')
     ],
     align=True
 )
@@ -119,7 +139,8 @@ record_dtype = np.dtype(
         ('fee', 'f8'),
         ('num_trades', 'i8'),
         ('trading_volume', 'f8'),
-        ('trading_value', 'f8')
+        ('trading_value', 'f8'),
+        ('funding', 'f8')
     ],
     align=True
 )
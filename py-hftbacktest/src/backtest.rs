@@ -62,6 +62,7 @@ pub extern "C" fn hashmapbt_close(hbt_ptr: *mut HashMapMarketDepthBacktest) -> i
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -78,6 +79,7 @@ pub extern "C" fn hashmapbt_elapse(hbt_ptr: *mut HashMapMarketDepthBacktest, dur
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -97,6 +99,7 @@ pub extern "C" fn hashmapbt_elapse_bt(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -124,6 +127,7 @@ pub extern "C" fn hashmapbt_wait_order_response(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -144,6 +148,7 @@ pub extern "C" fn hashmapbt_wait_next_feed(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -178,6 +183,7 @@ pub extern "C" fn hashmapbt_submit_buy_order(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -211,6 +217,7 @@ pub extern "C" fn hashmapbt_submit_sell_order(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -232,6 +239,31 @@ pub extern "C" fn hashmapbt_cancel(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
+        Err(BacktestError::DataError(_)) => 100,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn hashmapbt_modify(
+    hbt_ptr: *mut HashMapMarketDepthBacktest,
+    asset_no: usize,
+    order_id: u64,
+    price: f64,
+    qty: f64,
+    wait: bool,
+) -> i64 {
+    let hbt = unsafe { &mut *hbt_ptr };
+    match hbt.modify(asset_no, order_id, price, qty, wait) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(BacktestError::OrderIdExist) => 10,
+        Err(BacktestError::OrderRequestInProcess) => 11,
+        Err(BacktestError::OrderNotFound) => 12,
+        Err(BacktestError::InvalidOrderRequest) => 13,
+        Err(BacktestError::InvalidOrderStatus) => 14,
+        Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -334,6 +366,7 @@ pub extern "C" fn hashmapbt_goto_end(hbt_ptr: *mut HashMapMarketDepthBacktest) -
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -388,6 +421,7 @@ pub extern "C" fn roivecbt_close(hbt_ptr: *mut ROIVectorMarketDepthBacktest) ->
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -407,6 +441,7 @@ pub extern "C" fn roivecbt_elapse(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -426,6 +461,7 @@ pub extern "C" fn roivecbt_elapse_bt(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -453,6 +489,7 @@ pub extern "C" fn roivecbt_wait_order_response(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -473,6 +510,7 @@ pub extern "C" fn roivecbt_wait_next_feed(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -507,6 +545,7 @@ pub extern "C" fn roivecbt_submit_buy_order(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -540,6 +579,7 @@ pub extern "C" fn roivecbt_submit_sell_order(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -561,6 +601,31 @@ pub extern "C" fn roivecbt_cancel(
         Err(BacktestError::InvalidOrderRequest) => 13,
         Err(BacktestError::InvalidOrderStatus) => 14,
         Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
+        Err(BacktestError::DataError(_)) => 100,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn roivecbt_modify(
+    hbt_ptr: *mut ROIVectorMarketDepthBacktest,
+    asset_no: usize,
+    order_id: u64,
+    price: f64,
+    qty: f64,
+    wait: bool,
+) -> i64 {
+    let hbt = unsafe { &mut *hbt_ptr };
+    match hbt.modify(asset_no, order_id, price, qty, wait) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(BacktestError::OrderIdExist) => 10,
+        Err(BacktestError::OrderRequestInProcess) => 11,
+        Err(BacktestError::OrderNotFound) => 12,
+        Err(BacktestError::InvalidOrderRequest) => 13,
+        Err(BacktestError::InvalidOrderStatus) => 14,
+        Err(BacktestError::EndOfData) => 15,
+        Err(BacktestError::Unsupported) => 16,
         Err(BacktestError::DataError(_)) => 100,
     }
 }
@@ -27,6 +27,7 @@ use hftbacktest::{
         proc::{
             L3Local,
             L3NoPartialFillExchange,
+            L3PartialFillExchange,
             Local,
             LocalProcessor,
             NoPartialFillExchange,
@@ -42,7 +43,7 @@ use hftbacktest::{
 };
 use hftbacktest_derive::build_asset;
 pub use order::*;
-use pyo3::{exceptions::PyValueError, prelude::*};
+use pyo3::prelude::*;
 
 mod backtest;
 mod depth;
@@ -464,14 +465,6 @@ pub fn build_hashmap_backtest(assets: Vec<PyRefMut<BacktestAsset>>) -> PyResult<
     let mut local = Vec::new();
     let mut exch = Vec::new();
     for asset in assets {
-        if let (QueueModel::L3FIFOQueueModel {}, ExchangeKind::PartialFillExchange {}) =
-            (&asset.queue_model, &asset.exch_kind)
-        {
-            return PyResult::Err(PyErr::new::<PyValueError, _>(
-                "L3PartialFillExchange is unsupported.",
-            ));
-        }
-
         let asst = build_asset!(
             asset,
             HashMapMarketDepth,
@@ -518,14 +511,6 @@ pub fn build_roivec_backtest(assets: Vec<PyRefMut<BacktestAsset>>) -> PyResult<u
     let mut local = Vec::new();
     let mut exch = Vec::new();
     for asset in assets {
-        if let (QueueModel::L3FIFOQueueModel {}, ExchangeKind::PartialFillExchange {}) =
-            (&asset.queue_model, &asset.exch_kind)
-        {
-            return PyResult::Err(PyErr::new::<PyValueError, _>(
-                "L3PartialFillExchange is unsupported.",
-            ));
-        }
-
         let asst = build_asset!(
             asset,
             ROIVectorMarketDepth,